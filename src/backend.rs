@@ -1,21 +1,26 @@
+use crate::file_dialog::FileResponse;
 use crate::message_dialog::MessageDialogResult;
-use crate::FileHandle;
+use crate::{Error, FileHandle};
 use std::future::Future;
-#[cfg(not(target_arch = "wasm32"))]
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 
-#[cfg(all(
-    any(
-        target_os = "linux",
-        target_os = "freebsd",
-        target_os = "dragonfly",
-        target_os = "netbsd",
-        target_os = "openbsd"
-    ),
-    not(feature = "gtk3")
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
 ))]
 mod linux;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub(crate) use linux::active_backend;
 
 #[cfg(all(
     any(
@@ -30,6 +35,8 @@ mod linux;
 mod gtk3;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
+mod testing;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 #[cfg(target_os = "windows")]
@@ -42,9 +49,20 @@ mod win_cid;
         target_os = "netbsd",
         target_os = "openbsd"
     ),
-    not(feature = "gtk3")
+    feature = "xdg-portal"
 ))]
 mod xdg_desktop_portal;
+#[cfg(all(
+    any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ),
+    feature = "xdg-portal"
+))]
+pub(crate) use xdg_desktop_portal::{document_host_path, make_document_persistent};
 
 //
 // Sync
@@ -52,22 +70,93 @@ mod xdg_desktop_portal;
 
 /// Dialog used to pick file/files
 #[cfg(not(target_arch = "wasm32"))]
-pub trait FilePickerDialogImpl {
+pub trait FilePickerDialogImpl: Sized {
     fn pick_file(self) -> Option<PathBuf>;
     fn pick_files(self) -> Option<Vec<PathBuf>>;
+
+    // Backends that can't tell failures apart from cancellation just never fail
+    fn try_pick_file(self) -> Result<Option<PathBuf>, Error> {
+        Ok(self.pick_file())
+    }
+    fn try_pick_files(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        Ok(self.pick_files())
+    }
+
+    // Backends that can't tell which filter was chosen just don't report one
+    fn try_pick_file_with_response(self) -> Result<Option<FileResponse<PathBuf>>, Error> {
+        Ok(self.try_pick_file()?.map(FileResponse::new))
+    }
+    fn try_pick_files_with_response(self) -> Result<Option<FileResponse<Vec<PathBuf>>>, Error> {
+        Ok(self.try_pick_files()?.map(FileResponse::new))
+    }
 }
 
 /// Dialog used to save file
 #[cfg(not(target_arch = "wasm32"))]
-pub trait FileSaveDialogImpl {
+pub trait FileSaveDialogImpl: Sized {
     fn save_file(self) -> Option<PathBuf>;
+
+    fn try_save_file(self) -> Result<Option<PathBuf>, Error> {
+        Ok(self.save_file())
+    }
+
+    fn try_save_file_with_response(self) -> Result<Option<FileResponse<PathBuf>>, Error> {
+        Ok(self.try_save_file()?.map(FileResponse::new))
+    }
 }
 
 /// Dialog used to pick folder
 #[cfg(not(target_arch = "wasm32"))]
-pub trait FolderPickerDialogImpl {
+pub trait FolderPickerDialogImpl: Sized {
     fn pick_folder(self) -> Option<PathBuf>;
     fn pick_folders(self) -> Option<Vec<PathBuf>>;
+
+    fn try_pick_folder(self) -> Result<Option<PathBuf>, Error> {
+        Ok(self.pick_folder())
+    }
+    fn try_pick_folders(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        Ok(self.pick_folders())
+    }
+}
+
+/// Dialog used to save multiple files into one folder
+#[cfg(not(target_arch = "wasm32"))]
+pub trait FilesSaveDialogImpl: FolderPickerDialogImpl + Sized {
+    // Backends without a native equivalent emulate it with a folder picker
+    fn try_save_files(self, file_names: Vec<String>) -> Result<Option<Vec<PathBuf>>, Error> {
+        let folder = self.try_pick_folder()?;
+        Ok(folder.map(|folder| paths_in_folder(&folder, &file_names)))
+    }
+}
+
+/// Paths of `file_names` inside of `folder`, renaming the ones that would overwrite
+/// an existing file (or each other) to `name (1).ext`, `name (2).ext`, ...
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn paths_in_folder(folder: &Path, file_names: &[String]) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::with_capacity(file_names.len());
+
+    for file_name in file_names {
+        let name = Path::new(file_name);
+        let stem = name
+            .file_stem()
+            .unwrap_or(name.as_os_str())
+            .to_string_lossy();
+        let extension = name.extension().map(|ext| ext.to_string_lossy());
+
+        let mut path = folder.join(file_name);
+        let mut n = 1;
+        while path.exists() || paths.contains(&path) {
+            path = match &extension {
+                Some(ext) => folder.join(format!("{stem} ({n}).{ext}")),
+                None => folder.join(format!("{stem} ({n})")),
+            };
+            n += 1;
+        }
+
+        paths.push(path);
+    }
+
+    paths
 }
 
 /// Dialog used to pick folder or files
@@ -77,6 +166,13 @@ pub trait FileOrFolderPickerDialogImpl {
     fn pick_files_or_folders(self) -> Option<Vec<PathBuf>>;
 }
 
+/// Opens files and URLs with another application
+pub trait LauncherImpl {
+    fn open_uri(self, uri: &str) -> Result<(), Error>;
+    fn open_file(self, path: &Path) -> Result<(), Error>;
+    fn show_in_folder(self, paths: &[PathBuf]) -> Result<(), Error>;
+}
+
 pub trait MessageDialogImpl {
     fn show(self) -> MessageDialogResult;
 }
@@ -92,16 +188,65 @@ pub type DialogFutureType<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 pub type DialogFutureType<T> = Pin<Box<dyn Future<Output = T>>>;
 
 /// Dialog used to pick file/files
-pub trait AsyncFilePickerDialogImpl {
+pub trait AsyncFilePickerDialogImpl: Sized {
     fn pick_file_async(self) -> DialogFutureType<Option<FileHandle>>;
     fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>>;
+
+    fn try_pick_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        let future = self.pick_file_async();
+        Box::pin(async move { Ok(future.await) })
+    }
+    fn try_pick_files_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        let future = self.pick_files_async();
+        Box::pin(async move { Ok(future.await) })
+    }
+
+    fn try_pick_file_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<FileHandle>>, Error>> {
+        let future = self.try_pick_file_async();
+        Box::pin(async move { Ok(future.await?.map(FileResponse::new)) })
+    }
+    fn try_pick_files_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<Vec<FileHandle>>>, Error>> {
+        let future = self.try_pick_files_async();
+        Box::pin(async move { Ok(future.await?.map(FileResponse::new)) })
+    }
 }
 
 /// Dialog used to pick folder
 #[cfg(not(target_arch = "wasm32"))]
-pub trait AsyncFolderPickerDialogImpl {
+pub trait AsyncFolderPickerDialogImpl: Sized {
     fn pick_folder_async(self) -> DialogFutureType<Option<FileHandle>>;
     fn pick_folders_async(self) -> DialogFutureType<Option<Vec<FileHandle>>>;
+
+    fn try_pick_folder_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        let future = self.pick_folder_async();
+        Box::pin(async move { Ok(future.await) })
+    }
+    fn try_pick_folders_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        let future = self.pick_folders_async();
+        Box::pin(async move { Ok(future.await) })
+    }
+}
+
+/// Dialog used to save multiple files into one folder
+#[cfg(not(target_arch = "wasm32"))]
+pub trait AsyncFilesSaveDialogImpl: AsyncFolderPickerDialogImpl + Sized {
+    fn try_save_files_async(
+        self,
+        file_names: Vec<String>,
+    ) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        let future = self.try_pick_folder_async();
+        Box::pin(async move {
+            let Some(folder) = future.await? else {
+                return Ok(None);
+            };
+            let paths = paths_in_folder(folder.path(), &file_names);
+            Ok(Some(paths.into_iter().map(FileHandle::wrap).collect()))
+        })
+    }
 }
 
 /// Dialog used to pick folder or files
@@ -112,10 +257,56 @@ pub trait AsyncFileOrFolderPickerDialogImpl {
 }
 
 /// Dialog used to pick folder
-pub trait AsyncFileSaveDialogImpl {
+pub trait AsyncFileSaveDialogImpl: Sized {
     fn save_file_async(self) -> DialogFutureType<Option<FileHandle>>;
+
+    fn try_save_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        let future = self.save_file_async();
+        Box::pin(async move { Ok(future.await) })
+    }
+
+    fn try_save_file_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<FileHandle>>, Error>> {
+        let future = self.try_save_file_async();
+        Box::pin(async move { Ok(future.await?.map(FileResponse::new)) })
+    }
 }
 
 pub trait AsyncMessageDialogImpl {
     fn show_async(self) -> DialogFutureType<MessageDialogResult>;
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::paths_in_folder;
+
+    #[test]
+    fn unique_paths_in_folder() {
+        let folder = std::env::temp_dir().join(format!("rfd-save-files-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("a.png"), []).unwrap();
+
+        let paths = paths_in_folder(
+            &folder,
+            &[
+                "a.png".into(),
+                "b.png".into(),
+                "b.png".into(),
+                "README".into(),
+            ],
+        );
+
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            paths,
+            [
+                folder.join("a (1).png"),
+                folder.join("b.png"),
+                folder.join("b (1).png"),
+                folder.join("README"),
+            ]
+        );
+    }
+}
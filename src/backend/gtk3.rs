@@ -1,11 +1,27 @@
+// Only the availability check is used with the `testing` feature, which answers dialogs itself
+#[cfg(not(feature = "testing"))]
 mod file_dialog;
+#[cfg(not(feature = "testing"))]
 mod message_dialog;
 
+#[cfg(not(feature = "testing"))]
+pub(crate) use file_dialog::{pick_file, pick_files, pick_folder, pick_folders, save_file};
+#[cfg(not(feature = "testing"))]
+pub(crate) use message_dialog::show as message;
+
+#[cfg(not(feature = "testing"))]
 mod gtk_future;
 
 mod utils;
 
+/// Whether GTK could be initialized, eg. there is a display
+pub(crate) fn is_available() -> bool {
+    utils::GtkGlobalThread::instance().is_ok()
+}
+
+#[cfg(not(feature = "testing"))]
 pub(self) trait AsGtkDialog {
     fn gtk_dialog_ptr(&self) -> *mut gtk_sys::GtkDialog;
     unsafe fn show(&self);
+    unsafe fn hide(&self);
 }
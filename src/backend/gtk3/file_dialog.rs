@@ -4,9 +4,7 @@ use dialog_ffi::GtkFileDialog;
 
 use std::path::PathBuf;
 
-use super::utils::GtkGlobalThread;
-use crate::backend::DialogFutureType;
-use crate::{FileDialog, FileHandle};
+use crate::{Error, FileDialog, FileResponse};
 
 use super::gtk_future::GtkDialogFuture;
 
@@ -14,170 +12,94 @@ use super::gtk_future::GtkDialogFuture;
 // File Picker
 //
 
-use crate::backend::FilePickerDialogImpl;
-impl FilePickerDialogImpl for FileDialog {
-    fn pick_file(self) -> Option<PathBuf> {
-        GtkGlobalThread::instance().run_blocking(move || {
-            let dialog = GtkFileDialog::build_pick_file(&self);
-
-            if dialog.run() == gtk_sys::GTK_RESPONSE_ACCEPT {
-                dialog.get_result()
-            } else {
-                None
-            }
-        })
-    }
-
-    fn pick_files(self) -> Option<Vec<PathBuf>> {
-        GtkGlobalThread::instance().run_blocking(move || {
-            let dialog = GtkFileDialog::build_pick_files(&self);
-
-            if dialog.run() == gtk_sys::GTK_RESPONSE_ACCEPT {
-                Some(dialog.get_results())
-            } else {
-                None
-            }
-        })
-    }
+pub(crate) async fn pick_file(dialog: FileDialog) -> Result<Option<FileResponse<PathBuf>>, Error> {
+    let builder = move || GtkFileDialog::build_pick_file(&dialog);
+
+    let future = GtkDialogFuture::new(builder, |dialog, res_id| {
+        if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
+            dialog.get_result().map(|path| FileResponse {
+                selection: path,
+                filter: dialog.get_filter(),
+                choices: dialog.get_choices(),
+            })
+        } else {
+            None
+        }
+    })?;
+
+    Ok(future.await)
 }
 
-use crate::backend::AsyncFilePickerDialogImpl;
-impl AsyncFilePickerDialogImpl for FileDialog {
-    fn pick_file_async(self) -> DialogFutureType<Option<FileHandle>> {
-        let builder = move || GtkFileDialog::build_pick_file(&self);
-
-        let future = GtkDialogFuture::new(builder, |dialog, res_id| {
-            if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
-                dialog.get_result().map(FileHandle::wrap)
-            } else {
-                None
-            }
-        });
-
-        Box::pin(future)
-    }
-
-    fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
-        let builder = move || GtkFileDialog::build_pick_files(&self);
-
-        let future = GtkDialogFuture::new(builder, |dialog, res_id| {
-            if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
-                Some(
-                    dialog
-                        .get_results()
-                        .into_iter()
-                        .map(FileHandle::wrap)
-                        .collect(),
-                )
-            } else {
-                None
-            }
-        });
-
-        Box::pin(future)
-    }
+pub(crate) async fn pick_files(
+    dialog: FileDialog,
+) -> Result<Option<FileResponse<Vec<PathBuf>>>, Error> {
+    let builder = move || GtkFileDialog::build_pick_files(&dialog);
+
+    let future = GtkDialogFuture::new(builder, |dialog, res_id| {
+        if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
+            Some(FileResponse {
+                selection: dialog.get_results(),
+                filter: dialog.get_filter(),
+                choices: dialog.get_choices(),
+            })
+        } else {
+            None
+        }
+    })?;
+
+    Ok(future.await)
 }
 
 //
 // Folder Picker
 //
 
-use crate::backend::FolderPickerDialogImpl;
-impl FolderPickerDialogImpl for FileDialog {
-    fn pick_folder(self) -> Option<PathBuf> {
-        GtkGlobalThread::instance().run_blocking(move || {
-            let dialog = GtkFileDialog::build_pick_folder(&self);
-
-            if dialog.run() == gtk_sys::GTK_RESPONSE_ACCEPT {
-                dialog.get_result()
-            } else {
-                None
-            }
-        })
-    }
-
-    fn pick_folders(self) -> Option<Vec<PathBuf>> {
-        GtkGlobalThread::instance().run_blocking(move || {
-            let dialog = GtkFileDialog::build_pick_folders(&self);
-
-            if dialog.run() == gtk_sys::GTK_RESPONSE_ACCEPT {
-                Some(dialog.get_results())
-            } else {
-                None
-            }
-        })
-    }
+pub(crate) async fn pick_folder(dialog: FileDialog) -> Result<Option<PathBuf>, Error> {
+    let builder = move || GtkFileDialog::build_pick_folder(&dialog);
+
+    let future = GtkDialogFuture::new(builder, |dialog, res_id| {
+        if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
+            dialog.get_result()
+        } else {
+            None
+        }
+    })?;
+
+    Ok(future.await)
 }
 
-use crate::backend::AsyncFolderPickerDialogImpl;
-impl AsyncFolderPickerDialogImpl for FileDialog {
-    fn pick_folder_async(self) -> DialogFutureType<Option<FileHandle>> {
-        let builder = move || GtkFileDialog::build_pick_folder(&self);
-
-        let future = GtkDialogFuture::new(builder, |dialog, res_id| {
-            if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
-                dialog.get_result().map(FileHandle::wrap)
-            } else {
-                None
-            }
-        });
-
-        Box::pin(future)
-    }
-
-    fn pick_folders_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
-        let builder = move || GtkFileDialog::build_pick_folders(&self);
-
-        let future = GtkDialogFuture::new(builder, |dialog, res_id| {
-            if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
-                Some(
-                    dialog
-                        .get_results()
-                        .into_iter()
-                        .map(FileHandle::wrap)
-                        .collect(),
-                )
-            } else {
-                None
-            }
-        });
-
-        Box::pin(future)
-    }
+pub(crate) async fn pick_folders(dialog: FileDialog) -> Result<Option<Vec<PathBuf>>, Error> {
+    let builder = move || GtkFileDialog::build_pick_folders(&dialog);
+
+    let future = GtkDialogFuture::new(builder, |dialog, res_id| {
+        if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
+            Some(dialog.get_results())
+        } else {
+            None
+        }
+    })?;
+
+    Ok(future.await)
 }
 
 //
 // File Save
 //
 
-use crate::backend::FileSaveDialogImpl;
-impl FileSaveDialogImpl for FileDialog {
-    fn save_file(self) -> Option<PathBuf> {
-        GtkGlobalThread::instance().run_blocking(move || {
-            let dialog = GtkFileDialog::build_save_file(&self);
-
-            if dialog.run() == gtk_sys::GTK_RESPONSE_ACCEPT {
-                dialog.get_result()
-            } else {
-                None
-            }
-        })
-    }
-}
-
-use crate::backend::AsyncFileSaveDialogImpl;
-impl AsyncFileSaveDialogImpl for FileDialog {
-    fn save_file_async(self) -> DialogFutureType<Option<FileHandle>> {
-        let builder = move || GtkFileDialog::build_save_file(&self);
-
-        let future = GtkDialogFuture::new(builder, |dialog, res_id| {
-            if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
-                dialog.get_result().map(FileHandle::wrap)
-            } else {
-                None
-            }
-        });
-
-        Box::pin(future)
-    }
+pub(crate) async fn save_file(dialog: FileDialog) -> Result<Option<FileResponse<PathBuf>>, Error> {
+    let builder = move || GtkFileDialog::build_save_file(&dialog);
+
+    let future = GtkDialogFuture::new(builder, |dialog, res_id| {
+        if res_id == gtk_sys::GTK_RESPONSE_ACCEPT {
+            dialog.get_result().map(|path| FileResponse {
+                selection: path,
+                filter: dialog.get_filter(),
+                choices: dialog.get_choices(),
+            })
+        } else {
+            None
+        }
+    })?;
+
+    Ok(future.await)
 }
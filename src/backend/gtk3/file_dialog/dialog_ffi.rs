@@ -18,11 +18,21 @@ pub enum GtkFileChooserAction {
 
 pub struct GtkFileDialog {
     pub ptr: *mut GtkFileChooserNative,
+    /// Added filters, along with their index in `FileDialog::filters`
+    filters: Vec<(usize, *mut gtk_sys::GtkFileFilter)>,
+    /// Ids of added choices
+    choices: Vec<CString>,
 }
 
 impl GtkFileDialog {
-    fn new(title: &str, action: GtkFileChooserAction, parent: *mut gtk_sys::GtkWindow) -> Self {
+    fn new(
+        title: &str,
+        action: GtkFileChooserAction,
+        parent: *mut gtk_sys::GtkWindow,
+        accept_label: Option<&str>,
+    ) -> Self {
         let title = CString::new(title).unwrap();
+        let accept_label = accept_label.and_then(|label| CString::new(label).ok());
 
         let ptr = unsafe {
             let dialog = gtk_sys::gtk_file_chooser_native_new(
@@ -30,17 +40,24 @@ impl GtkFileDialog {
                 parent,
                 action as i32,
                 // passing null for the texts will use the default text, which has full support for i18n
-                std::ptr::null(),
+                accept_label
+                    .as_ref()
+                    .map(|label| label.as_ptr())
+                    .unwrap_or(std::ptr::null()),
                 std::ptr::null(),
             );
             dialog as _
         };
 
-        Self { ptr }
+        Self {
+            ptr,
+            filters: Vec::new(),
+            choices: Vec::new(),
+        }
     }
 
-    fn add_filters(&mut self, filters: &[crate::file_dialog::Filter]) {
-        for f in filters.iter() {
+    fn add_filters(&mut self, filters: &[crate::file_dialog::Filter], current: Option<usize>) {
+        for (id, f) in filters.iter().enumerate() {
             if let Ok(name) = CString::new(f.name.as_str()) {
                 unsafe {
                     let filter = gtk_sys::gtk_file_filter_new();
@@ -48,7 +65,9 @@ impl GtkFileDialog {
                     let paterns: Vec<_> = f
                         .extensions
                         .iter()
-                        .filter_map(|e| CString::new(format!("*.{}", e)).ok())
+                        .map(|e| format!("*.{}", e))
+                        .chain(f.patterns.iter().cloned())
+                        .filter_map(|p| CString::new(p).ok())
                         .collect();
 
                     gtk_sys::gtk_file_filter_set_name(filter, name.as_ptr());
@@ -57,12 +76,111 @@ impl GtkFileDialog {
                         gtk_sys::gtk_file_filter_add_pattern(filter, p.as_ptr());
                     }
 
+                    for mime_type in f.mime_types.iter() {
+                        if let Ok(mime_type) = CString::new(mime_type.as_str()) {
+                            gtk_sys::gtk_file_filter_add_mime_type(filter, mime_type.as_ptr());
+                        }
+                    }
+
                     gtk_sys::gtk_file_chooser_add_filter(self.ptr as _, filter);
+
+                    if current == Some(id) {
+                        gtk_sys::gtk_file_chooser_set_filter(self.ptr as _, filter);
+                    }
+
+                    self.filters.push((id, filter));
                 }
             }
         }
     }
 
+    /// Index of the filter that is currently active, in `FileDialog::filters` order
+    pub fn get_filter(&self) -> Option<usize> {
+        let current = unsafe { gtk_sys::gtk_file_chooser_get_filter(self.ptr as _) };
+        if current.is_null() {
+            return None;
+        }
+
+        self.filters
+            .iter()
+            .find(|(_, filter)| *filter == current)
+            .map(|(id, _)| *id)
+    }
+
+    fn add_choices(&mut self, choices: &[crate::file_dialog::Choice]) {
+        for choice in choices {
+            let (Ok(id), Ok(label), Ok(default)) = (
+                CString::new(choice.id.as_str()),
+                CString::new(choice.label.as_str()),
+                CString::new(choice.default.as_str()),
+            ) else {
+                continue;
+            };
+
+            let (option_ids, option_labels): (Vec<_>, Vec<_>) = choice
+                .options
+                .iter()
+                .filter_map(|(id, label)| {
+                    Some((
+                        CString::new(id.as_str()).ok()?,
+                        CString::new(label.as_str()).ok()?,
+                    ))
+                })
+                .unzip();
+
+            // NULL terminated arrays, or NULL for a checkbox
+            let mut option_id_ptrs: Vec<_> = option_ids
+                .iter()
+                .map(|o| o.as_ptr())
+                .chain([std::ptr::null()])
+                .collect();
+            let mut option_label_ptrs: Vec<_> = option_labels
+                .iter()
+                .map(|o| o.as_ptr())
+                .chain([std::ptr::null()])
+                .collect();
+
+            let (options, labels) = if option_ids.is_empty() {
+                (std::ptr::null_mut(), std::ptr::null_mut())
+            } else {
+                (option_id_ptrs.as_mut_ptr(), option_label_ptrs.as_mut_ptr())
+            };
+
+            unsafe {
+                gtk_sys::gtk_file_chooser_add_choice(
+                    self.ptr as _,
+                    id.as_ptr(),
+                    label.as_ptr(),
+                    options,
+                    labels,
+                );
+                gtk_sys::gtk_file_chooser_set_choice(self.ptr as _, id.as_ptr(), default.as_ptr());
+            }
+
+            self.choices.push(id);
+        }
+    }
+
+    /// `(id, value)` of every added choice
+    pub fn get_choices(&self) -> Vec<(String, String)> {
+        self.choices
+            .iter()
+            .filter_map(|id| {
+                let value =
+                    unsafe { gtk_sys::gtk_file_chooser_get_choice(self.ptr as _, id.as_ptr()) };
+                if value.is_null() {
+                    return None;
+                }
+
+                let value = unsafe { CStr::from_ptr(value) };
+                Some((
+                    id.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                ))
+            })
+            .collect()
+    }
+
     fn set_file_name(&self, name: Option<&str>) {
         if let Some(name) = name {
             if let Ok(name) = CString::new(name) {
@@ -154,10 +272,6 @@ impl GtkFileDialog {
 
         paths
     }
-
-    pub fn run(&self) -> i32 {
-        unsafe { gtk_sys::gtk_native_dialog_run(self.ptr as *mut _) }
-    }
 }
 
 fn parent_gtk_window(opt: &FileDialog) -> *mut gtk_sys::GtkWindow {
@@ -174,9 +288,11 @@ impl GtkFileDialog {
             opt.title.as_deref().unwrap_or("Open File"),
             GtkFileChooserAction::Open,
             parent_gtk_window(opt),
+            opt.accept_label.as_deref(),
         );
 
-        dialog.add_filters(&opt.filters);
+        dialog.add_filters(&opt.filters, opt.current_filter);
+        dialog.add_choices(&opt.choices);
         dialog.set_path(opt.starting_directory.as_deref());
         dialog.set_show_hidden(opt.show_hidden_files);
 
@@ -197,15 +313,20 @@ impl GtkFileDialog {
             opt.title.as_deref().unwrap_or("Save File"),
             GtkFileChooserAction::Save,
             parent_gtk_window(opt),
+            opt.accept_label.as_deref(),
         );
 
         unsafe { gtk_sys::gtk_file_chooser_set_do_overwrite_confirmation(dialog.ptr as _, 1) };
 
-        dialog.add_filters(&opt.filters);
+        dialog.add_filters(&opt.filters, opt.current_filter);
+        dialog.add_choices(&opt.choices);
         dialog.set_path(opt.starting_directory.as_deref());
         dialog.set_show_hidden(opt.show_hidden_files);
 
-        if let (Some(mut path), Some(file_name)) =
+        if let Some(path) = opt.existing_file.as_deref() {
+            // "Save As" of an already saved document
+            dialog.set_file_name(path.to_str());
+        } else if let (Some(mut path), Some(file_name)) =
             (opt.starting_directory.to_owned(), opt.file_name.as_deref())
         {
             path.push(file_name);
@@ -229,6 +350,7 @@ impl GtkFileDialog {
             opt.title.as_deref().unwrap_or("Select Folder"),
             GtkFileChooserAction::SelectFolder,
             parent_gtk_window(opt),
+            opt.accept_label.as_deref(),
         );
         dialog.set_path(opt.starting_directory.as_deref());
         dialog.set_show_hidden(opt.show_hidden_files);
@@ -250,6 +372,7 @@ impl GtkFileDialog {
             opt.title.as_deref().unwrap_or("Select Folder"),
             GtkFileChooserAction::SelectFolder,
             parent_gtk_window(opt),
+            opt.accept_label.as_deref(),
         );
         unsafe { gtk_sys::gtk_file_chooser_set_select_multiple(dialog.ptr as _, 1) };
         dialog.set_path(opt.starting_directory.as_deref());
@@ -272,10 +395,12 @@ impl GtkFileDialog {
             opt.title.as_deref().unwrap_or("Open File"),
             GtkFileChooserAction::Open,
             parent_gtk_window(opt),
+            opt.accept_label.as_deref(),
         );
 
         unsafe { gtk_sys::gtk_file_chooser_set_select_multiple(dialog.ptr as _, 1) };
-        dialog.add_filters(&opt.filters);
+        dialog.add_filters(&opt.filters, opt.current_filter);
+        dialog.add_choices(&opt.choices);
         dialog.set_path(opt.starting_directory.as_deref());
         dialog.set_show_hidden(opt.show_hidden_files);
 
@@ -300,6 +425,10 @@ impl AsGtkDialog for GtkFileDialog {
     unsafe fn show(&self) {
         gtk_sys::gtk_native_dialog_show(self.ptr as *mut _);
     }
+
+    unsafe fn hide(&self) {
+        gtk_sys::gtk_native_dialog_hide(self.ptr as *mut _);
+    }
 }
 
 impl Drop for GtkFileDialog {
@@ -6,6 +6,7 @@ use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
 
 use super::AsGtkDialog;
+use crate::Error;
 
 struct FutureState<R, D> {
     waker: Option<Waker>,
@@ -15,14 +16,14 @@ struct FutureState<R, D> {
 
 unsafe impl<R, D> Send for FutureState<R, D> {}
 
-pub(super) struct GtkDialogFuture<R, D> {
+pub(super) struct GtkDialogFuture<R: 'static, D: AsGtkDialog + 'static> {
     state: Arc<Mutex<FutureState<R, D>>>,
 }
 
-unsafe impl<R, D> Send for GtkDialogFuture<R, D> {}
+unsafe impl<R: 'static, D: AsGtkDialog + 'static> Send for GtkDialogFuture<R, D> {}
 
 impl<R: Default + 'static, D: AsGtkDialog + 'static> GtkDialogFuture<R, D> {
-    pub fn new<B, F>(build: B, cb: F) -> Self
+    pub fn new<B, F>(build: B, cb: F) -> Result<Self, Error>
     where
         B: FnOnce() -> D + Send + 'static,
         F: Fn(&mut D, i32) -> R + Send + 'static,
@@ -51,7 +52,7 @@ impl<R: Default + 'static, D: AsGtkDialog + 'static> GtkDialogFuture<R, D> {
                 }
             };
 
-            GtkGlobalThread::instance().run(move || {
+            GtkGlobalThread::instance()?.run(move || {
                 let mut state = state.lock().unwrap();
                 state.dialog = Some(build());
 
@@ -65,11 +66,11 @@ impl<R: Default + 'static, D: AsGtkDialog + 'static> GtkDialogFuture<R, D> {
             });
         }
 
-        Self { state }
+        Ok(Self { state })
     }
 }
 
-impl<R, D> std::future::Future for GtkDialogFuture<R, D> {
+impl<R: 'static, D: AsGtkDialog + 'static> std::future::Future for GtkDialogFuture<R, D> {
     type Output = R;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -84,6 +85,22 @@ impl<R, D> std::future::Future for GtkDialogFuture<R, D> {
     }
 }
 
+impl<R: 'static, D: AsGtkDialog + 'static> Drop for GtkDialogFuture<R, D> {
+    fn drop(&mut self) {
+        // The dialog is still open when the future is dropped before the user answered it
+        let Ok(thread) = GtkGlobalThread::instance() else {
+            return;
+        };
+        let state = self.state.clone();
+        thread.run(move || {
+            let dialog = state.lock().unwrap().dialog.take();
+            if let Some(dialog) = dialog {
+                unsafe { dialog.hide() };
+            }
+        });
+    }
+}
+
 use gobject_sys::GCallback;
 use gtk_sys::{GtkDialog, GtkResponseType};
 use std::ffi::c_void;
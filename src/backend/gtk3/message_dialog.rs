@@ -2,11 +2,10 @@ use std::ffi::CString;
 use std::ptr;
 
 use super::gtk_future::GtkDialogFuture;
-use super::utils::GtkGlobalThread;
 use super::AsGtkDialog;
 
 use crate::message_dialog::{MessageButtons, MessageDialog, MessageLevel};
-use crate::MessageDialogResult;
+use crate::{Error, MessageDialogResult};
 
 pub struct GtkMessageDialog {
     buttons: MessageButtons,
@@ -123,9 +122,8 @@ impl GtkMessageDialog {
         }
     }
 
-    pub fn run(self) -> MessageDialogResult {
-        let res = unsafe { gtk_sys::gtk_dialog_run(self.ptr) };
-
+    /// Result of the button with the `res` response id
+    pub fn response(&self, res: i32) -> MessageDialogResult {
         use MessageButtons::*;
         match (&self.buttons, res) {
             (Ok | OkCancel, gtk_sys::GTK_RESPONSE_OK) => MessageDialogResult::Ok,
@@ -189,34 +187,16 @@ impl AsGtkDialog for GtkMessageDialog {
     unsafe fn show(&self) {
         gtk_sys::gtk_widget_show_all(self.ptr as *mut _);
     }
-}
-
-use crate::backend::MessageDialogImpl;
 
-impl MessageDialogImpl for MessageDialog {
-    fn show(self) -> MessageDialogResult {
-        GtkGlobalThread::instance().run_blocking(move || {
-            let dialog = GtkMessageDialog::new(self);
-            dialog.run()
-        })
+    unsafe fn hide(&self) {
+        gtk_sys::gtk_widget_hide(self.ptr as *mut _);
     }
 }
 
-use crate::backend::AsyncMessageDialogImpl;
-use crate::backend::DialogFutureType;
-
-impl AsyncMessageDialogImpl for MessageDialog {
-    fn show_async(self) -> DialogFutureType<MessageDialogResult> {
-        let builder = move || GtkMessageDialog::new(self);
-
-        let future = GtkDialogFuture::new(builder, |_, res| match res {
-            gtk_sys::GTK_RESPONSE_OK => MessageDialogResult::Ok,
-            gtk_sys::GTK_RESPONSE_CANCEL => MessageDialogResult::Cancel,
-            gtk_sys::GTK_RESPONSE_YES => MessageDialogResult::Yes,
-            gtk_sys::GTK_RESPONSE_NO => MessageDialogResult::No,
-            gtk_sys::GTK_RESPONSE_DELETE_EVENT => MessageDialogResult::Cancel,
-            _ => unreachable!(),
-        });
-        Box::pin(future)
-    }
+pub(crate) async fn show(dialog: MessageDialog) -> Result<MessageDialogResult, Error> {
+    let builder = move || GtkMessageDialog::new(dialog);
+
+    let future = GtkDialogFuture::new(builder, |dialog, res| dialog.response(res))?;
+
+    Ok(future.await)
 }
@@ -1,10 +1,12 @@
 use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::OnceLock;
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::{mpsc, Arc};
 use std::thread::spawn;
 
-static GTK_THREAD: OnceLock<GtkGlobalThread> = OnceLock::new();
+use crate::Error;
+
+static GTK_THREAD: OnceLock<Option<GtkGlobalThread>> = OnceLock::new();
 
 /// GTK functions are not thread-safe, and must all be called from the thread that initialized GTK. To ensure this, we
 /// spawn one thread the first time a GTK dialog is opened and keep it open for the entire lifetime of the application,
@@ -15,20 +17,27 @@ pub struct GtkGlobalThread {
 }
 
 impl GtkGlobalThread {
-    /// Return the global, lazily-initialized instance of the global GTK thread.
-    pub(super) fn instance() -> &'static Self {
-        GTK_THREAD.get_or_init(|| Self::new())
+    /// Return the global, lazily-initialized instance of the global GTK thread, or an error if GTK
+    /// could not be initialized, eg. without a display.
+    pub(super) fn instance() -> Result<&'static Self, Error> {
+        GTK_THREAD
+            .get_or_init(Self::new)
+            .as_ref()
+            .ok_or_else(|| Error::BackendUnavailable("GTK could not be initialized".into()))
     }
 
-    fn new() -> Self {
+    fn new() -> Option<Self> {
         // When the GtkGlobalThread is eventually dropped, we will set `running` to false and wake up the loop so
         // gtk_main_iteration unblocks and we exit the thread on the next iteration.
         let running = Arc::new(AtomicBool::new(true));
         let thread_running = Arc::clone(&running);
 
+        let (sender, receiver) = mpsc::channel();
+
         spawn(move || {
             let initialized =
                 unsafe { gtk_sys::gtk_init_check(ptr::null_mut(), ptr::null_mut()) == 1 };
+            sender.send(initialized).ok();
             if !initialized {
                 return;
             }
@@ -44,45 +53,13 @@ impl GtkGlobalThread {
             }
         });
 
-        Self {
+        receiver.recv().unwrap_or(false).then(|| Self {
             running: Arc::new(AtomicBool::new(true)),
-        }
-    }
-
-    /// Run a function on the GTK thread, blocking on the result which is then passed back.
-    pub(super) fn run_blocking<
-        T: Send + Clone + std::fmt::Debug + 'static,
-        F: FnOnce() -> T + Send + 'static,
-    >(
-        &self,
-        cb: F,
-    ) -> T {
-        let data: Arc<(Mutex<Option<T>>, _)> = Arc::new((Mutex::new(None), Condvar::new()));
-        let thread_data = Arc::clone(&data);
-        let mut cb = Some(cb);
-        unsafe {
-            connect_idle(move || {
-                // connect_idle takes a FnMut; convert our FnOnce into that by ensuring we only call it once
-                let res = cb.take().expect("Callback should only be called once")();
-
-                // pass the result back to the main thread
-                let (lock, cvar) = &*thread_data;
-                *lock.lock().unwrap() = Some(res);
-                cvar.notify_all();
-
-                glib_sys::GFALSE
-            });
-        };
-
-        // wait for GTK thread to execute the callback and place the result into `data`
-        let lock_res = data
-            .1
-            .wait_while(data.0.lock().unwrap(), |res| res.is_none())
-            .unwrap();
-        lock_res.as_ref().unwrap().clone()
+        })
     }
 
     /// Launch a function on the GTK thread without blocking.
+    #[cfg(not(feature = "testing"))]
     pub(super) fn run<F: FnOnce() + Send + 'static>(&self, cb: F) {
         let mut cb = Some(cb);
         unsafe {
@@ -101,6 +78,7 @@ impl Drop for GtkGlobalThread {
     }
 }
 
+#[cfg(not(feature = "testing"))]
 unsafe fn connect_idle<F: FnMut() -> glib_sys::gboolean + Send + 'static>(f: F) {
     unsafe extern "C" fn response_trampoline<F: FnMut() -> glib_sys::gboolean + Send + 'static>(
         f: glib_sys::gpointer,
@@ -124,6 +102,7 @@ unsafe fn connect_idle<F: FnMut() -> glib_sys::gboolean + Send + 'static>(f: F)
     );
 }
 
+#[cfg(not(feature = "testing"))]
 pub(super) unsafe fn find_gtk_window(
     parent: &raw_window_handle::RawWindowHandle,
 ) -> *mut gtk_sys::GtkWindow {
@@ -0,0 +1,497 @@
+//! Trait impls of Linux & BSDs, showing each dialog with the first available backend of
+//! [`crate::set_backends`].
+
+use std::path::PathBuf;
+
+use pollster::block_on;
+
+use super::{kdialog, zenity};
+#[cfg(feature = "gtk3")]
+use crate::backend::gtk3;
+#[cfg(feature = "xdg-portal")]
+use crate::backend::xdg_desktop_portal as portal;
+use crate::backend::{paths_in_folder, DialogFutureType};
+use crate::error::log_error;
+use crate::linux_backend::backends;
+use crate::message_dialog::MessageDialog;
+use crate::MessageDialogResult;
+use crate::{Backend, Error, FileDialog, FileHandle, FileResponse, MessageButtons};
+
+type BackendResult<T> = Result<Option<T>, Error>;
+
+/// Try `show` with every backend in order, until one of them is available.
+///
+/// `show` returns `None` for backends that can't show the dialog at all, eg. message dialogs of
+/// the portal.
+async fn first_available<T>(
+    mut show: impl FnMut(Backend) -> Option<DialogFutureType<Result<T, Error>>>,
+) -> Result<T, Error> {
+    let mut reasons = Vec::new();
+
+    for backend in backends() {
+        let Some(future) = show(backend) else {
+            continue;
+        };
+
+        match future.await {
+            Err(Error::BackendUnavailable(reason)) => {
+                log::debug!("Backend {backend} is not available: {reason}");
+                reasons.push(format!("{backend}: {reason}"));
+            }
+            res => return res,
+        }
+    }
+
+    if reasons.is_empty() {
+        reasons.push("none of the selected backends can show this dialog".into());
+    }
+    Err(Error::BackendUnavailable(reasons.join("; ")))
+}
+
+/// Helper programs don't report which filter was active, so it is inferred from the path
+fn helper_response(dialog: &FileDialog, path: PathBuf) -> FileResponse<PathBuf> {
+    FileResponse {
+        filter: zenity::infer_filter(dialog, &path),
+        ..FileResponse::new(path)
+    }
+}
+
+fn helper_responses(
+    dialog: &FileDialog,
+    paths: Vec<PathBuf>,
+) -> Option<FileResponse<Vec<PathBuf>>> {
+    let filter = paths
+        .first()
+        .and_then(|path| zenity::infer_filter(dialog, path));
+    (!paths.is_empty()).then(|| FileResponse {
+        filter,
+        ..FileResponse::new(paths)
+    })
+}
+
+//
+// Dialogs
+//
+
+async fn pick_file(dialog: FileDialog) -> BackendResult<FileResponse<PathBuf>> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => Some(Box::pin(portal::pick_file(dialog))),
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(gtk3::pick_file(dialog))),
+            Backend::Zenity => Some(Box::pin(async move {
+                let path = zenity::pick_file(&dialog).await?;
+                Ok(path.map(|path| helper_response(&dialog, path)))
+            })),
+            Backend::Kdialog => Some(Box::pin(async move {
+                let path = kdialog::pick_file(&dialog).await?;
+                Ok(path.map(|path| helper_response(&dialog, path)))
+            })),
+            // Not compiled in
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn pick_files(dialog: FileDialog) -> BackendResult<FileResponse<Vec<PathBuf>>> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => Some(Box::pin(portal::pick_files(dialog))),
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(gtk3::pick_files(dialog))),
+            Backend::Zenity => Some(Box::pin(async move {
+                let paths = zenity::pick_files(&dialog).await?;
+                Ok(helper_responses(&dialog, paths))
+            })),
+            Backend::Kdialog => Some(Box::pin(async move {
+                let paths = kdialog::pick_files(&dialog).await?;
+                Ok(helper_responses(&dialog, paths))
+            })),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn pick_folder(dialog: FileDialog) -> BackendResult<PathBuf> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => Some(Box::pin(portal::pick_folder(dialog))),
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(gtk3::pick_folder(dialog))),
+            Backend::Zenity => Some(Box::pin(async move { zenity::pick_folder(&dialog).await })),
+            Backend::Kdialog => Some(Box::pin(async move { kdialog::pick_folder(&dialog).await })),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn pick_folders(dialog: FileDialog) -> BackendResult<Vec<PathBuf>> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => Some(Box::pin(portal::pick_folders(dialog))),
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(gtk3::pick_folders(dialog))),
+            Backend::Zenity => Some(Box::pin(async move {
+                let paths = zenity::pick_folders(&dialog).await?;
+                Ok((!paths.is_empty()).then_some(paths))
+            })),
+            // Only picks a single folder
+            Backend::Kdialog => Some(Box::pin(async move {
+                let folder = kdialog::pick_folder(&dialog).await?;
+                Ok(folder.map(|folder| vec![folder]))
+            })),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn save_file(dialog: FileDialog) -> BackendResult<FileResponse<PathBuf>> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => Some(Box::pin(portal::save_file(dialog))),
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(gtk3::save_file(dialog))),
+            Backend::Zenity => Some(Box::pin(async move {
+                let path = zenity::save_file(&dialog).await?;
+                Ok(path.map(|path| helper_response(&dialog, path)))
+            })),
+            Backend::Kdialog => Some(Box::pin(async move {
+                let path = kdialog::save_file(&dialog).await?;
+                Ok(path.map(|path| helper_response(&dialog, path)))
+            })),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn save_files(dialog: FileDialog, file_names: Vec<String>) -> BackendResult<Vec<PathBuf>> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        let file_names = file_names.clone();
+        match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => Some(Box::pin(portal::save_files(dialog, file_names))),
+            // Emulated with the folder picker
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(async move {
+                let folder = gtk3::pick_folder(dialog).await?;
+                Ok(folder.map(|folder| paths_in_folder(&folder, &file_names)))
+            })),
+            Backend::Zenity => Some(Box::pin(async move {
+                let folder = zenity::pick_folder(&dialog).await?;
+                Ok(folder.map(|folder| paths_in_folder(&folder, &file_names)))
+            })),
+            Backend::Kdialog => Some(Box::pin(async move {
+                let folder = kdialog::pick_folder(&dialog).await?;
+                Ok(folder.map(|folder| paths_in_folder(&folder, &file_names)))
+            })),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn message(dialog: MessageDialog) -> Result<MessageDialogResult, Error> {
+    first_available(move |backend| -> Option<DialogFutureType<_>> {
+        let dialog = dialog.clone();
+        match backend {
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => Some(Box::pin(gtk3::message(dialog))),
+            Backend::Zenity => Some(Box::pin(zenity_message(dialog))),
+            Backend::Kdialog => Some(Box::pin(kdialog_message(dialog))),
+            // The portal has no message dialogs
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    })
+    .await
+}
+
+async fn zenity_message(dialog: MessageDialog) -> Result<MessageDialogResult, Error> {
+    let parent = zenity::Parent::new(dialog.parent.as_ref());
+    match &dialog.buttons {
+        MessageButtons::Ok | MessageButtons::OkCustom(_) => {
+            zenity::message(
+                &dialog.level,
+                &dialog.buttons,
+                &dialog.title,
+                &dialog.description,
+                parent,
+            )
+            .await
+        }
+        MessageButtons::OkCancel
+        | MessageButtons::YesNo
+        | MessageButtons::OkCancelCustom(..)
+        | MessageButtons::YesNoCancel
+        | MessageButtons::YesNoCancelCustom(..) => {
+            zenity::question(&dialog.buttons, &dialog.title, &dialog.description, parent).await
+        }
+    }
+}
+
+async fn kdialog_message(dialog: MessageDialog) -> Result<MessageDialogResult, Error> {
+    match &dialog.buttons {
+        MessageButtons::Ok | MessageButtons::OkCustom(_) => {
+            kdialog::message(
+                &dialog.level,
+                &dialog.buttons,
+                &dialog.title,
+                &dialog.description,
+            )
+            .await
+        }
+        MessageButtons::OkCancel
+        | MessageButtons::YesNo
+        | MessageButtons::OkCancelCustom(..)
+        | MessageButtons::YesNoCancel
+        | MessageButtons::YesNoCancelCustom(..) => {
+            kdialog::question(
+                &dialog.level,
+                &dialog.buttons,
+                &dialog.title,
+                &dialog.description,
+            )
+            .await
+        }
+    }
+}
+
+fn wrap_response(res: FileResponse<PathBuf>) -> FileResponse<FileHandle> {
+    FileResponse {
+        selection: FileHandle::wrap(res.selection),
+        filter: res.filter,
+        choices: res.choices,
+    }
+}
+
+fn wrap_responses(res: FileResponse<Vec<PathBuf>>) -> FileResponse<Vec<FileHandle>> {
+    FileResponse {
+        selection: res.selection.into_iter().map(FileHandle::wrap).collect(),
+        filter: res.filter,
+        choices: res.choices,
+    }
+}
+
+fn wrap_paths(paths: Vec<PathBuf>) -> Vec<FileHandle> {
+    paths.into_iter().map(FileHandle::wrap).collect()
+}
+
+//
+// File Picker
+//
+
+use crate::backend::FilePickerDialogImpl;
+impl FilePickerDialogImpl for FileDialog {
+    fn pick_file(self) -> Option<PathBuf> {
+        log_error(FilePickerDialogImpl::try_pick_file(self))
+    }
+
+    fn pick_files(self) -> Option<Vec<PathBuf>> {
+        log_error(FilePickerDialogImpl::try_pick_files(self))
+    }
+
+    fn try_pick_file(self) -> Result<Option<PathBuf>, Error> {
+        block_on(pick_file(self)).map(|res| res.map(|res| res.selection))
+    }
+
+    fn try_pick_files(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        block_on(pick_files(self)).map(|res| res.map(|res| res.selection))
+    }
+
+    fn try_pick_file_with_response(self) -> Result<Option<FileResponse<PathBuf>>, Error> {
+        block_on(pick_file(self))
+    }
+
+    fn try_pick_files_with_response(self) -> Result<Option<FileResponse<Vec<PathBuf>>>, Error> {
+        block_on(pick_files(self))
+    }
+}
+
+use crate::backend::AsyncFilePickerDialogImpl;
+impl AsyncFilePickerDialogImpl for FileDialog {
+    fn pick_file_async(self) -> DialogFutureType<Option<FileHandle>> {
+        let future = self.try_pick_file_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+        let future = self.try_pick_files_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn try_pick_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        Box::pin(async move {
+            let res = pick_file(self).await?;
+            Ok(res.map(|res| FileHandle::wrap(res.selection)))
+        })
+    }
+
+    fn try_pick_files_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        Box::pin(async move {
+            let res = pick_files(self).await?;
+            Ok(res.map(|res| wrap_paths(res.selection)))
+        })
+    }
+
+    fn try_pick_file_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<FileHandle>>, Error>> {
+        Box::pin(async move { Ok(pick_file(self).await?.map(wrap_response)) })
+    }
+
+    fn try_pick_files_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<Vec<FileHandle>>>, Error>> {
+        Box::pin(async move { Ok(pick_files(self).await?.map(wrap_responses)) })
+    }
+}
+
+//
+// Folder Picker
+//
+
+use crate::backend::FolderPickerDialogImpl;
+impl FolderPickerDialogImpl for FileDialog {
+    fn pick_folder(self) -> Option<PathBuf> {
+        log_error(FolderPickerDialogImpl::try_pick_folder(self))
+    }
+
+    fn pick_folders(self) -> Option<Vec<PathBuf>> {
+        log_error(FolderPickerDialogImpl::try_pick_folders(self))
+    }
+
+    fn try_pick_folder(self) -> Result<Option<PathBuf>, Error> {
+        block_on(pick_folder(self))
+    }
+
+    fn try_pick_folders(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        block_on(pick_folders(self))
+    }
+}
+
+use crate::backend::AsyncFolderPickerDialogImpl;
+impl AsyncFolderPickerDialogImpl for FileDialog {
+    fn pick_folder_async(self) -> DialogFutureType<Option<FileHandle>> {
+        let future = self.try_pick_folder_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn pick_folders_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+        let future = self.try_pick_folders_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn try_pick_folder_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        Box::pin(async move { Ok(pick_folder(self).await?.map(FileHandle::wrap)) })
+    }
+
+    fn try_pick_folders_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        Box::pin(async move { Ok(pick_folders(self).await?.map(wrap_paths)) })
+    }
+}
+
+//
+// File Save
+//
+
+use crate::backend::FileSaveDialogImpl;
+impl FileSaveDialogImpl for FileDialog {
+    fn save_file(self) -> Option<PathBuf> {
+        log_error(FileSaveDialogImpl::try_save_file(self))
+    }
+
+    fn try_save_file(self) -> Result<Option<PathBuf>, Error> {
+        block_on(save_file(self)).map(|res| res.map(|res| res.selection))
+    }
+
+    fn try_save_file_with_response(self) -> Result<Option<FileResponse<PathBuf>>, Error> {
+        block_on(save_file(self))
+    }
+}
+
+use crate::backend::AsyncFileSaveDialogImpl;
+impl AsyncFileSaveDialogImpl for FileDialog {
+    fn save_file_async(self) -> DialogFutureType<Option<FileHandle>> {
+        let future = self.try_save_file_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn try_save_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        Box::pin(async move {
+            let res = save_file(self).await?;
+            Ok(res.map(|res| FileHandle::wrap(res.selection)))
+        })
+    }
+
+    fn try_save_file_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<FileHandle>>, Error>> {
+        Box::pin(async move { Ok(save_file(self).await?.map(wrap_response)) })
+    }
+}
+
+//
+// Files Save
+//
+
+use crate::backend::FilesSaveDialogImpl;
+impl FilesSaveDialogImpl for FileDialog {
+    fn try_save_files(self, file_names: Vec<String>) -> Result<Option<Vec<PathBuf>>, Error> {
+        block_on(save_files(self, file_names))
+    }
+}
+
+use crate::backend::AsyncFilesSaveDialogImpl;
+impl AsyncFilesSaveDialogImpl for FileDialog {
+    fn try_save_files_async(
+        self,
+        file_names: Vec<String>,
+    ) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        Box::pin(async move { Ok(save_files(self, file_names).await?.map(wrap_paths)) })
+    }
+}
+
+//
+// Message Dialog
+//
+
+use crate::backend::MessageDialogImpl;
+impl MessageDialogImpl for MessageDialog {
+    fn show(self) -> MessageDialogResult {
+        block_on(self.show_async())
+    }
+}
+
+use crate::backend::AsyncMessageDialogImpl;
+impl AsyncMessageDialogImpl for MessageDialog {
+    fn show_async(self) -> DialogFutureType<MessageDialogResult> {
+        Box::pin(async move {
+            message(self).await.unwrap_or_else(|err| {
+                log::error!("Failed to show the message dialog: {err}");
+                MessageDialogResult::Cancel
+            })
+        })
+    }
+}
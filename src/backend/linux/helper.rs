@@ -0,0 +1,399 @@
+//! Running the helper programs that show the dialogs, eg. zenity or kdialog.
+//!
+//! Inside of Flatpak the helpers are usually not part of the app, so they run on the host through
+//! `flatpak-spawn --host` instead, if the app is allowed to talk to `org.freedesktop.Flatpak`.
+
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    io::Read,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+};
+
+#[cfg(not(feature = "testing"))]
+use std::path::PathBuf;
+
+use super::sandbox::{self, FlatpakInfo};
+use crate::Error;
+
+/// Kills the helper process if the dialog is dropped before it exited
+struct KillOnDrop {
+    child: Arc<Mutex<Child>>,
+    on_host: bool,
+}
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let mut child = self.child.lock().unwrap();
+        if let Ok(None) = child.try_wait() {
+            if self.on_host {
+                // `flatpak-spawn` forwards SIGTERM to the host process, SIGKILL would only end
+                // `flatpak-spawn` itself and leave the dialog open
+                unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+            } else {
+                child.kill().ok();
+            }
+        }
+    }
+}
+
+/// Whether `program` is installed on the host, and can be run with `flatpak-spawn --host`.
+///
+/// Checked once per program, as every check starts a process on the host.
+fn is_installed_on_host(program: &str) -> bool {
+    static INSTALLED: Mutex<BTreeMap<String, bool>> = Mutex::new(BTreeMap::new());
+
+    if let Some(installed) = INSTALLED.lock().unwrap().get(program) {
+        return *installed;
+    }
+
+    let installed = Command::new("flatpak-spawn")
+        .args(["--host", "sh", "-c", r#"command -v "$1""#, "sh", program])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    INSTALLED
+        .lock()
+        .unwrap()
+        .insert(program.to_owned(), installed);
+    installed
+}
+
+/// `command` wrapped in `flatpak-spawn --host`, with the paths of the sandbox in its arguments
+/// translated to the host
+fn host_command(command: &Command, info: &FlatpakInfo) -> Command {
+    let mut host = Command::new("flatpak-spawn");
+    host.arg("--host");
+
+    if let Some(dir) = command.get_current_dir() {
+        let mut arg = OsString::from("--directory=");
+        arg.push(info.host_path(dir));
+        host.arg(arg);
+    }
+
+    host.arg(command.get_program());
+    host.args(command.get_args().map(|arg| host_arg(arg, info)));
+
+    host
+}
+
+/// `arg` with the path of the sandbox translated to the host, if it is one.
+///
+/// A path that doesn't exist yet, eg. the name of a new file in a save dialog, is translated
+/// through its folder.
+fn host_arg(arg: &OsStr, info: &FlatpakInfo) -> OsString {
+    let path = Path::new(arg);
+    if !path.is_absolute() {
+        return arg.to_owned();
+    }
+
+    let mut host_path = if path.exists() {
+        info.host_path(path).into_os_string()
+    } else {
+        match (path.parent(), path.file_name()) {
+            (Some(folder), Some(name)) if folder.is_dir() => {
+                info.host_path(folder).join(name).into_os_string()
+            }
+            _ => return arg.to_owned(),
+        }
+    };
+    // Zenity opens the folder of `--filename` when it ends with a `/`
+    if arg.as_bytes().ends_with(b"/") && !host_path.as_bytes().ends_with(b"/") {
+        host_path.push("/");
+    }
+    host_path
+}
+
+/// `command` as it has to be started in the sandbox the app runs in, if any, and whether it
+/// runs on the host
+fn sandboxed_command(command: Command, program: &str) -> Result<(Command, bool), Error> {
+    if !sandbox::is_sandboxed() || is_installed(program) {
+        return Ok((command, false));
+    }
+
+    match FlatpakInfo::get() {
+        Some(info) if info.can_spawn_on_host => {
+            if is_installed_on_host(program) {
+                Ok((host_command(&command, info), true))
+            } else {
+                Err(Error::BackendUnavailable(format!(
+                    "{program} is installed neither in the Flatpak sandbox nor on the host"
+                )))
+            }
+        }
+        Some(_) => Err(Error::BackendUnavailable(format!(
+            "{program} is not installed in the Flatpak sandbox, and running it on the host \
+             needs --talk-name=org.freedesktop.Flatpak"
+        ))),
+        None => Err(Error::BackendUnavailable(format!(
+            "{program} is not installed in the Snap sandbox"
+        ))),
+    }
+}
+
+/// Exit status and raw output of a helper
+#[cfg(not(feature = "testing"))]
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    /// The helper ran on the host, so the paths it printed are host paths, see [`picked_paths`]
+    pub on_host: bool,
+}
+
+/// Run `command` until it exits, returning its exit status and raw output
+#[cfg(not(feature = "testing"))]
+pub async fn output(command: Command) -> Result<Output, Error> {
+    let (status, stdout, on_host) = run(command, true).await?;
+    Ok(Output {
+        status,
+        stdout,
+        on_host,
+    })
+}
+
+/// Run `command` until it exits, without reading its output.
+///
+/// Unlike `output` it doesn't wait for the processes started by `command`, which inherit its
+/// stdout and keep it open, eg. the application opened by `xdg-open`.
+pub async fn status(command: Command) -> Result<ExitStatus, Error> {
+    Ok(run(command, false).await?.0)
+}
+
+/// Exit status, stdout if captured, and whether `command` ran on the host
+async fn run(command: Command, capture_stdout: bool) -> Result<(ExitStatus, Vec<u8>, bool), Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let (mut command, on_host) = sandboxed_command(command, &program)?;
+    let helper_error = |err: std::io::Error| {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            Error::BackendUnavailable(format!("{program} is not installed"))
+        } else {
+            Error::Helper(format!("{program}: {err}"))
+        }
+    };
+
+    let stdout = if capture_stdout {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(helper_error)?;
+    let stdout = child.stdout.take();
+    let pid = child.id();
+
+    let child = Arc::new(Mutex::new(child));
+    let _kill_on_drop = KillOnDrop {
+        child: child.clone(),
+        on_host,
+    };
+
+    let (tx, rx) = crate::oneshot::channel();
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let res = stdout
+            .map_or(Ok(0), |mut stdout| stdout.read_to_end(&mut buffer))
+            .and_then(|_| {
+                wait_exited(pid)?;
+                child.lock().unwrap().wait()
+            })
+            .map(|status| (status, buffer));
+        tx.send(res).ok();
+    });
+    let (status, stdout) = rx
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|res| res)
+        .map_err(helper_error)?;
+
+    Ok((status, stdout, on_host))
+}
+
+/// Wait for the process to exit without reaping it, so `KillOnDrop` can still signal it meanwhile
+fn wait_exited(pid: u32) -> std::io::Result<()> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let flags = libc::WEXITED | libc::WNOWAIT;
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Error of a helper that exited neither accepted nor cancelled
+pub fn failed(command: &str, status: ExitStatus) -> Error {
+    Error::Helper(format!("{command}: exited with {status}"))
+}
+
+/// Whether `program` is an executable file in one of the `PATH` directories
+pub fn is_installed(program: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        std::fs::metadata(dir.join(program))
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    })
+}
+
+/// Whether `program` can be run, inside of Flatpak also on the host
+pub fn is_available(program: &str) -> bool {
+    is_installed(program)
+        || FlatpakInfo::get().is_some_and(|info| info.can_spawn_on_host)
+            && is_installed_on_host(program)
+}
+
+/// What the paths printed by a helper are, see [`picked_paths`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg(not(feature = "testing"))]
+pub enum Picked {
+    Files,
+    /// The file of a save dialog, which may not exist yet
+    NewFile,
+    Folders,
+}
+
+/// Paths picked in a helper, as the app sees them.
+///
+/// A helper that ran on the host prints host paths, which the sandbox can't open unless they are
+/// mounted into it. Files are exported through the Documents portal with
+/// `flatpak document-export`, like the FileChooser portal does. Folders can't be, those only get
+/// `/app` and `/usr` translated back.
+#[cfg(not(feature = "testing"))]
+pub async fn picked_paths(paths: Vec<PathBuf>, on_host: bool, picked: Picked) -> Vec<PathBuf> {
+    let Some(info) = FlatpakInfo::get().filter(|_| on_host) else {
+        return paths;
+    };
+
+    let mut sandbox_paths = Vec::with_capacity(paths.len());
+    for path in paths {
+        let sandbox_path = info.sandbox_path(&path);
+        // Part of the app or its runtime
+        if sandbox_path != path || picked == Picked::Folders {
+            sandbox_paths.push(sandbox_path);
+            continue;
+        }
+
+        match export_document(&path, info, picked == Picked::NewFile).await {
+            Ok(document) => sandbox_paths.push(document),
+            Err(err) => {
+                log::error!("Failed to export {} to the sandbox: {err}", path.display());
+                sandbox_paths.push(path);
+            }
+        }
+    }
+    sandbox_paths
+}
+
+/// [`picked_paths`] of a single path
+#[cfg(not(feature = "testing"))]
+pub async fn picked_path(path: PathBuf, on_host: bool, picked: Picked) -> PathBuf {
+    let paths = picked_paths(vec![path.clone()], on_host, picked).await;
+    paths.into_iter().next().unwrap_or(path)
+}
+
+/// Path of the document `path` of the host is exported as, readable and writable by the app
+#[cfg(not(feature = "testing"))]
+async fn export_document(
+    path: &Path,
+    info: &FlatpakInfo,
+    new_file: bool,
+) -> Result<PathBuf, Error> {
+    let Some(app_id) = info.app_id.as_deref() else {
+        return Err(Error::Helper("the app id is unknown".into()));
+    };
+
+    // Runs on the host already, `flatpak-spawn` itself is part of every sandbox
+    let mut command = Command::new("flatpak-spawn");
+    command.args([
+        "--host",
+        "flatpak",
+        "document-export",
+        "--allow-read",
+        "--allow-write",
+    ]);
+    command.arg(format!("--app={app_id}"));
+    if new_file {
+        command.arg("--noexist");
+    }
+    command.arg("--").arg(path);
+
+    let output = output(command).await?;
+    if !output.status.success() {
+        return Err(failed("flatpak document-export", output.status));
+    }
+    let document = output.stdout.strip_suffix(b"\n").unwrap_or(&output.stdout);
+    Ok(OsStr::from_bytes(document).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlatpakInfo;
+    use std::ffi::OsStr;
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn host_arg() {
+        let info = FlatpakInfo::parse("[Instance]\nruntime-path=/runtime/files\n");
+        let host_arg = |arg: &str| super::host_arg(OsStr::new(arg), &info);
+
+        assert_eq!(host_arg("/usr/share"), "/runtime/files/share");
+        // Zenity opens the folder
+        assert_eq!(host_arg("/usr/share/"), "/runtime/files/share/");
+        // The name of a new file in a save dialog
+        assert_eq!(
+            host_arg("/usr/share/rfd-new-file.txt"),
+            Path::new("/runtime/files/share/rfd-new-file.txt")
+        );
+        assert_eq!(host_arg("--save"), "--save");
+    }
+
+    #[test]
+    fn host_command() {
+        let info = FlatpakInfo::parse("[Instance]\nruntime-path=/runtime/files\n");
+        let mut command = Command::new("zenity");
+        command.args(["--file-selection", "--save", "--filename"]);
+        command.arg("/usr/share/rfd-new-file.txt");
+
+        let host = super::host_command(&command, &info);
+        assert_eq!(host.get_program(), "flatpak-spawn");
+        assert_eq!(
+            host.get_args().collect::<Vec<_>>(),
+            [
+                "--host",
+                "zenity",
+                "--file-selection",
+                "--save",
+                "--filename",
+                "/runtime/files/share/rfd-new-file.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn status_does_not_wait_for_children() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 5 & exit 3"]);
+
+        let start = Instant::now();
+        let status = pollster::block_on(super::status(command)).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,324 @@
+//! [kdialog](https://invent.kde.org/utilities/kdialog), the KDE counterpart of zenity.
+
+use std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    process::{Command, ExitStatus},
+};
+
+use super::helper::{self, Picked};
+use super::zenity::{filter_globs, ordered_filters};
+use crate::{
+    message_dialog::{MessageButtons, MessageLevel},
+    Error, FileDialog, MessageDialogResult,
+};
+
+pub type KdialogResult<T> = Result<T, Error>;
+
+fn command(title: Option<&str>) -> Command {
+    let mut command = Command::new("kdialog");
+    if let Some(title) = title {
+        command.args(["--title", title]);
+    }
+    command
+}
+
+/// One `Name (*.a *.b)` line per filter, kdialog selects the first one by default
+fn filter_arg(dialog: &FileDialog) -> String {
+    ordered_filters(dialog)
+        .map(|filter| format!("{} ({})", filter.name, filter_globs(filter).join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Folder the dialog starts in, along with the preselected file name if any
+fn start_path(dialog: &FileDialog) -> PathBuf {
+    if let Some(path) = dialog.existing_file.as_ref() {
+        return path.clone();
+    }
+
+    let dir = dialog.starting_directory.clone().unwrap_or_default();
+    match dialog.file_name.as_ref() {
+        Some(name) => dir.join(name),
+        None => dir,
+    }
+}
+
+/// `mode` takes the start path, and for files the filter, as positional arguments
+fn file_dialog_command(dialog: &FileDialog, options: &[&str], mode: &str) -> Command {
+    let mut command = command(dialog.title.as_deref());
+    command.args(options);
+    command.arg(mode);
+    command.arg(start_path(dialog));
+
+    if mode != "--getexistingdirectory" && !dialog.filters.is_empty() {
+        command.arg(filter_arg(dialog));
+    }
+
+    command
+}
+
+/// kdialog prints one path per line, ending with a newline.
+///
+/// The output is read as raw bytes, so file names that aren't UTF-8 or end with `\r` are
+/// returned as they are. File names containing a newline are not supported.
+fn parse_paths(output: &[u8]) -> Vec<PathBuf> {
+    output
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| PathBuf::from(OsStr::from_bytes(line)))
+        .collect()
+}
+
+/// Paths picked in a file dialog as the app sees them, empty if it was cancelled
+async fn run(command: Command, picked: Picked) -> KdialogResult<Vec<PathBuf>> {
+    let output = helper::output(command).await?;
+
+    match output.status.code() {
+        Some(0) => {
+            let paths = parse_paths(&output.stdout);
+            Ok(helper::picked_paths(paths, output.on_host, picked).await)
+        }
+        // Cancelled, or closed
+        Some(1) => Ok(Vec::new()),
+        _ => Err(helper::failed("kdialog", output.status)),
+    }
+}
+
+pub async fn pick_file(dialog: &FileDialog) -> KdialogResult<Option<PathBuf>> {
+    let command = file_dialog_command(dialog, &[], "--getopenfilename");
+    Ok(run(command, Picked::Files).await?.into_iter().next())
+}
+
+pub async fn pick_files(dialog: &FileDialog) -> KdialogResult<Vec<PathBuf>> {
+    let command = file_dialog_command(
+        dialog,
+        &["--multiple", "--separate-output"],
+        "--getopenfilename",
+    );
+    run(command, Picked::Files).await
+}
+
+pub async fn pick_folder(dialog: &FileDialog) -> KdialogResult<Option<PathBuf>> {
+    let command = file_dialog_command(dialog, &[], "--getexistingdirectory");
+    Ok(run(command, Picked::Folders).await?.into_iter().next())
+}
+
+pub async fn save_file(dialog: &FileDialog) -> KdialogResult<Option<PathBuf>> {
+    let command = file_dialog_command(dialog, &[], "--getsavefilename");
+    Ok(run(command, Picked::NewFile).await?.into_iter().next())
+}
+
+pub async fn message(
+    level: &MessageLevel,
+    btns: &MessageButtons,
+    title: &str,
+    description: &str,
+) -> KdialogResult<MessageDialogResult> {
+    let mode = match level {
+        MessageLevel::Info => "--msgbox",
+        MessageLevel::Warning => "--sorry",
+        MessageLevel::Error => "--error",
+    };
+
+    let mut command = command(Some(title));
+    if let MessageButtons::OkCustom(ok) = btns {
+        command.args(["--ok-label", ok.as_str()]);
+    }
+    command.args([mode, description]);
+
+    let status = helper::output(command).await?.status;
+    if !status.success() {
+        return Err(helper::failed("kdialog", status));
+    }
+
+    Ok(match btns {
+        MessageButtons::OkCustom(ok) => MessageDialogResult::Custom(ok.clone()),
+        _ => MessageDialogResult::Ok,
+    })
+}
+
+fn question_command(
+    level: &MessageLevel,
+    btns: &MessageButtons,
+    title: &str,
+    description: &str,
+) -> Command {
+    let warning = !matches!(level, MessageLevel::Info);
+    let mut command = command(Some(title));
+
+    let mode = match btns {
+        MessageButtons::YesNoCancel | MessageButtons::YesNoCancelCustom(..) if warning => {
+            "--warningyesnocancel"
+        }
+        MessageButtons::YesNoCancel | MessageButtons::YesNoCancelCustom(..) => "--yesnocancel",
+        _ if warning => "--warningyesno",
+        _ => "--yesno",
+    };
+
+    match btns {
+        MessageButtons::OkCancel => {
+            command.args(["--yes-label", "Ok", "--no-label", "Cancel"]);
+        }
+        MessageButtons::OkCancelCustom(ok, cancel) => {
+            command.args(["--yes-label", ok.as_str(), "--no-label", cancel.as_str()]);
+        }
+        MessageButtons::YesNoCancelCustom(yes, no, cancel) => {
+            command.args(["--yes-label", yes.as_str(), "--no-label", no.as_str()]);
+            command.args(["--cancel-label", cancel.as_str()]);
+        }
+        _ => {}
+    }
+
+    command.args([mode, description]);
+    command
+}
+
+/// Button of a question, kdialog exits with 0 for yes, 1 for no and 2 for cancel
+fn question_result(btns: &MessageButtons, status: ExitStatus) -> Option<MessageDialogResult> {
+    let res = match (btns, status.code()?) {
+        (MessageButtons::OkCancel, 0) => MessageDialogResult::Ok,
+        (MessageButtons::OkCancel, 1) => MessageDialogResult::Cancel,
+        (MessageButtons::YesNo | MessageButtons::YesNoCancel, 0) => MessageDialogResult::Yes,
+        (MessageButtons::YesNo | MessageButtons::YesNoCancel, 1) => MessageDialogResult::No,
+        (MessageButtons::YesNoCancel, 2) => MessageDialogResult::Cancel,
+        (MessageButtons::OkCancelCustom(ok, _), 0) => MessageDialogResult::Custom(ok.clone()),
+        (MessageButtons::OkCancelCustom(_, cancel), 1) => {
+            MessageDialogResult::Custom(cancel.clone())
+        }
+        (MessageButtons::YesNoCancelCustom(yes, _, _), 0) => {
+            MessageDialogResult::Custom(yes.clone())
+        }
+        (MessageButtons::YesNoCancelCustom(_, no, _), 1) => MessageDialogResult::Custom(no.clone()),
+        (MessageButtons::YesNoCancelCustom(_, _, cancel), 2) => {
+            MessageDialogResult::Custom(cancel.clone())
+        }
+        _ => return None,
+    };
+    Some(res)
+}
+
+pub async fn question(
+    level: &MessageLevel,
+    btns: &MessageButtons,
+    title: &str,
+    description: &str,
+) -> KdialogResult<MessageDialogResult> {
+    let command = question_command(level, btns, title, description);
+    let status = helper::output(command).await?.status;
+    question_result(btns, status).ok_or_else(|| helper::failed("kdialog", status))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_dialog::{MessageButtons, MessageLevel};
+    use crate::{FileDialog, MessageDialogResult};
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::PathBuf;
+    use std::process::ExitStatus;
+
+    #[test]
+    fn file_dialog_command() {
+        let dialog = FileDialog::new()
+            .set_title("Open")
+            .add_filter("Text", &["txt", "md"])
+            .add_filter("Rust", &["rs"])
+            .set_current_filter(1)
+            .set_directory("/home/user");
+        let command = super::file_dialog_command(
+            &dialog,
+            &["--multiple", "--separate-output"],
+            "--getopenfilename",
+        );
+
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--title",
+                "Open",
+                "--multiple",
+                "--separate-output",
+                "--getopenfilename",
+                "/home/user",
+                "Rust (*.rs)\nText (*.txt *.md)",
+            ]
+        );
+    }
+
+    #[test]
+    fn save_file_start_path() {
+        let dialog = FileDialog::new()
+            .set_directory("/home/user")
+            .set_file_name("notes.txt");
+        assert_eq!(
+            super::start_path(&dialog),
+            PathBuf::from("/home/user/notes.txt")
+        );
+
+        let dialog = dialog.set_existing_file("/tmp/draft.txt");
+        assert_eq!(super::start_path(&dialog), PathBuf::from("/tmp/draft.txt"));
+    }
+
+    #[test]
+    fn parse_paths() {
+        assert_eq!(
+            super::parse_paths(b"/a/with space.txt\n/b/c.md\n"),
+            [PathBuf::from("/a/with space.txt"), PathBuf::from("/b/c.md")]
+        );
+        assert_eq!(
+            super::parse_paths(b"/a/not \xff utf-8\n/b/carriage return\r\n"),
+            [
+                PathBuf::from(OsStr::from_bytes(b"/a/not \xff utf-8")),
+                PathBuf::from("/b/carriage return\r"),
+            ]
+        );
+        assert!(super::parse_paths(b"").is_empty());
+    }
+
+    #[test]
+    fn question_result() {
+        let exit = |code| ExitStatus::from_raw(code << 8);
+        let custom =
+            MessageButtons::YesNoCancelCustom("Save".into(), "Discard".into(), "Back".into());
+
+        assert_eq!(
+            super::question_result(&MessageButtons::YesNoCancel, exit(2)),
+            Some(MessageDialogResult::Cancel)
+        );
+        assert_eq!(
+            super::question_result(&custom, exit(1)),
+            Some(MessageDialogResult::Custom("Discard".into()))
+        );
+        assert_eq!(
+            super::question_result(&MessageButtons::YesNo, exit(254)),
+            None
+        );
+
+        let command = super::question_command(&MessageLevel::Warning, &custom, "Quit", "Save?");
+        let args: Vec<_> = command.get_args().collect();
+        assert!(args.contains(&"--warningyesnocancel".as_ref()));
+        assert!(args.contains(&"--cancel-label".as_ref()));
+    }
+
+    #[test]
+    #[ignore]
+    fn pick_file() {
+        pollster::block_on(super::pick_file(&FileDialog::default())).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn question() {
+        pollster::block_on(super::question(
+            &MessageLevel::Info,
+            &MessageButtons::YesNoCancel,
+            "hi",
+            "me",
+        ))
+        .unwrap();
+    }
+}
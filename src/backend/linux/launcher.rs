@@ -0,0 +1,140 @@
+//! Opening files and URLs through the OpenURI portal, or `xdg-open` without a portal, and
+//! showing files in the file manager.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use pollster::block_on;
+
+use super::helper;
+#[cfg(feature = "xdg-portal")]
+use super::sandbox;
+#[cfg(feature = "xdg-portal")]
+use crate::backend::xdg_desktop_portal as portal;
+use crate::backend::LauncherImpl;
+use crate::{Error, Launcher};
+
+impl LauncherImpl for Launcher {
+    fn open_uri(self, uri: &str) -> Result<(), Error> {
+        block_on(open_uri(self, uri))
+    }
+
+    fn open_file(self, path: &Path) -> Result<(), Error> {
+        block_on(open_file(self, path))
+    }
+
+    fn show_in_folder(self, paths: &[PathBuf]) -> Result<(), Error> {
+        block_on(show_in_folder(self, paths))
+    }
+}
+
+/// Whether the portal may be used, it can be deselected with `set_backends` or `RFD_BACKEND`
+#[cfg(feature = "xdg-portal")]
+fn use_portal() -> bool {
+    crate::linux_backend::backends().contains(&crate::Backend::XdgPortal)
+}
+
+async fn open_uri(launcher: Launcher, uri: &str) -> Result<(), Error> {
+    #[cfg(feature = "xdg-portal")]
+    if use_portal() {
+        match portal::open_uri(launcher, uri).await {
+            Err(Error::BackendUnavailable(reason)) => {
+                log::debug!("Using xdg-open: {reason}");
+            }
+            res => return res,
+        }
+    }
+
+    #[cfg(not(feature = "xdg-portal"))]
+    let _ = launcher;
+
+    xdg_open(uri.as_ref()).await
+}
+
+async fn open_file(launcher: Launcher, path: &Path) -> Result<(), Error> {
+    #[cfg(feature = "xdg-portal")]
+    if use_portal() {
+        match portal::open_file(launcher, path).await {
+            Err(Error::BackendUnavailable(reason)) => {
+                log::debug!("Using xdg-open: {reason}");
+            }
+            res => return res,
+        }
+    }
+
+    #[cfg(not(feature = "xdg-portal"))]
+    let _ = launcher;
+
+    xdg_open(path.as_os_str()).await
+}
+
+async fn xdg_open(target: &OsStr) -> Result<(), Error> {
+    let mut command = Command::new("xdg-open");
+    command.arg(target);
+
+    // Only waits for `xdg-open`, not for the application it opens
+    let status = helper::status(command).await?;
+    if !status.success() {
+        return Err(helper::failed("xdg-open", status));
+    }
+    Ok(())
+}
+
+async fn show_in_folder(launcher: Launcher, paths: &[PathBuf]) -> Result<(), Error> {
+    let paths = paths
+        .iter()
+        .map(std::path::absolute)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::InvalidOptions(err.to_string()))?;
+    let Some(first) = paths.first() else {
+        return Err(Error::InvalidOptions("No paths to show".into()));
+    };
+
+    #[cfg(feature = "xdg-portal")]
+    {
+        // The file manager of the host can't see paths inside of the sandbox
+        let res = if sandbox::is_sandboxed() {
+            if use_portal() {
+                open_directories(&launcher, &paths).await
+            } else {
+                Err(Error::BackendUnavailable(
+                    "XDG portal is not selected".into(),
+                ))
+            }
+        } else {
+            portal::show_items(&paths).await
+        };
+
+        match res {
+            Err(Error::BackendUnavailable(reason)) => {
+                log::debug!("Using xdg-open: {reason}");
+            }
+            res => return res,
+        }
+    }
+
+    #[cfg(not(feature = "xdg-portal"))]
+    let _ = launcher;
+
+    // Loses the selection, but at least shows the folder
+    let folder = first.parent().unwrap_or(first);
+    xdg_open(folder.as_os_str()).await
+}
+
+/// `OpenDirectory` shows one folder per call, so one call per distinct folder
+#[cfg(feature = "xdg-portal")]
+async fn open_directories(launcher: &Launcher, paths: &[PathBuf]) -> Result<(), Error> {
+    let mut folders = Vec::new();
+    for path in paths {
+        let folder = path.parent();
+        if folders.contains(&folder) {
+            continue;
+        }
+        folders.push(folder);
+        portal::open_directory(launcher, path).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+//! Expands MIME types into glob patterns using the
+//! [shared-mime-info](https://specifications.freedesktop.org/shared-mime-info-spec/latest/)
+//! database, for helpers that only understand globs.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+#[derive(Debug, Default)]
+pub struct MimeDatabase {
+    /// Mime type -> glob patterns
+    globs: HashMap<String, Vec<String>>,
+    /// Mime type -> parent mime types
+    parents: HashMap<String, Vec<String>>,
+    /// Alias -> canonical mime type
+    aliases: HashMap<String, String>,
+}
+
+static DATABASE: OnceLock<MimeDatabase> = OnceLock::new();
+
+fn mime_dirs() -> Vec<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|dir| dir.join("mime"))
+        .collect()
+}
+
+impl MimeDatabase {
+    /// Return the global, lazily-loaded database of the system
+    pub fn get() -> &'static Self {
+        DATABASE.get_or_init(Self::load)
+    }
+
+    fn load() -> Self {
+        let mut db = Self::default();
+
+        for dir in mime_dirs() {
+            if let Ok(globs2) = std::fs::read_to_string(dir.join("globs2")) {
+                db.parse_globs2(&globs2);
+            } else if let Ok(globs) = std::fs::read_to_string(dir.join("globs")) {
+                db.parse_globs(&globs);
+            }
+
+            if let Ok(subclasses) = std::fs::read_to_string(dir.join("subclasses")) {
+                db.parse_subclasses(&subclasses);
+            }
+
+            if let Ok(aliases) = std::fs::read_to_string(dir.join("aliases")) {
+                db.parse_aliases(&aliases);
+            }
+        }
+
+        if db.globs.is_empty() {
+            log::warn!("shared-mime-info database not found, MIME type filters will be empty");
+        }
+
+        db
+    }
+
+    fn add_glob(&mut self, mime_type: &str, glob: &str) {
+        let globs = self.globs.entry(mime_type.to_owned()).or_default();
+        if !globs.iter().any(|g| g == glob) {
+            globs.push(glob.to_owned());
+        }
+    }
+
+    fn lines(data: &str) -> impl Iterator<Item = &str> {
+        data.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    }
+
+    /// `weight:mime/type:glob[:flags]`
+    fn parse_globs2(&mut self, data: &str) {
+        for line in Self::lines(data) {
+            let mut fields = line.splitn(4, ':');
+            if let (Some(_weight), Some(mime_type), Some(glob)) =
+                (fields.next(), fields.next(), fields.next())
+            {
+                self.add_glob(mime_type, glob);
+            }
+        }
+    }
+
+    /// `mime/type:glob`
+    fn parse_globs(&mut self, data: &str) {
+        for line in Self::lines(data) {
+            if let Some((mime_type, glob)) = line.split_once(':') {
+                self.add_glob(mime_type, glob);
+            }
+        }
+    }
+
+    /// `mime/type parent/type`
+    fn parse_subclasses(&mut self, data: &str) {
+        for line in Self::lines(data) {
+            if let Some((mime_type, parent)) = line.split_once(' ') {
+                self.parents
+                    .entry(mime_type.to_owned())
+                    .or_default()
+                    .push(parent.trim().to_owned());
+            }
+        }
+    }
+
+    /// `alias/type canonical/type`
+    fn parse_aliases(&mut self, data: &str) {
+        for line in Self::lines(data) {
+            if let Some((alias, mime_type)) = line.split_once(' ') {
+                self.aliases
+                    .insert(alias.to_owned(), mime_type.trim().to_owned());
+            }
+        }
+    }
+
+    fn canonical<'a>(&'a self, mime_type: &'a str) -> &'a str {
+        self.aliases
+            .get(mime_type)
+            .map(String::as_str)
+            .unwrap_or(mime_type)
+    }
+
+    /// Whether `mime_type` is `ancestor` or (transitively) a subclass of it
+    fn is_a(&self, mime_type: &str, ancestor: &str, visited: &mut HashSet<String>) -> bool {
+        if mime_type == ancestor {
+            return true;
+        }
+        if !visited.insert(mime_type.to_owned()) {
+            return false;
+        }
+
+        self.parents.get(mime_type).is_some_and(|parents| {
+            parents
+                .iter()
+                .any(|parent| self.is_a(self.canonical(parent), ancestor, visited))
+        })
+    }
+
+    /// Glob patterns of files matching any of the given mime types.
+    ///
+    /// Like GTK's MIME filters, this includes subclasses (eg. `text/markdown` for `text/plain`),
+    /// and `media/*` wildcards match every type of that media.
+    pub fn globs(&self, mime_types: &[String]) -> Vec<String> {
+        let mut known: Vec<&String> = self.globs.keys().collect();
+        known.sort();
+
+        let mut out: Vec<String> = Vec::new();
+
+        for mime_type in mime_types {
+            let mime_type = self.canonical(mime_type);
+
+            let matches = |candidate: &str| match mime_type.strip_suffix("/*") {
+                Some(media) => candidate
+                    .split_once('/')
+                    .is_some_and(|(candidate_media, _)| candidate_media == media),
+                None => self.is_a(candidate, mime_type, &mut HashSet::new()),
+            };
+
+            for candidate in known.iter().filter(|candidate| matches(candidate)) {
+                for glob in &self.globs[*candidate] {
+                    if !out.contains(glob) {
+                        out.push(glob.clone());
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MimeDatabase;
+
+    fn database() -> MimeDatabase {
+        let mut db = MimeDatabase::default();
+        db.parse_globs2(
+            "# comment\n\
+             50:text/plain:*.txt\n\
+             50:text/markdown:*.md\n\
+             50:text/markdown:*.markdown\n\
+             50:image/png:*.png\n\
+             50:image/jpeg:*.jpg\n\
+             50:image/jpeg:*.jpeg\n\
+             50:application/gzip:*.gz\n",
+        );
+        db.parse_subclasses("text/markdown text/plain\n");
+        db.parse_aliases("text/x-markdown text/markdown\n");
+        db
+    }
+
+    #[test]
+    fn exact() {
+        let db = database();
+        assert_eq!(db.globs(&["text/markdown".into()]), ["*.md", "*.markdown"]);
+    }
+
+    #[test]
+    fn alias() {
+        let db = database();
+        assert_eq!(
+            db.globs(&["text/x-markdown".into()]),
+            ["*.md", "*.markdown"]
+        );
+    }
+
+    #[test]
+    fn subclasses() {
+        let db = database();
+        assert_eq!(
+            db.globs(&["text/plain".into()]),
+            ["*.md", "*.markdown", "*.txt"]
+        );
+    }
+
+    #[test]
+    fn wildcard() {
+        let db = database();
+        assert_eq!(
+            db.globs(&["image/*".into(), "application/gzip".into()]),
+            ["*.jpg", "*.jpeg", "*.png", "*.gz"]
+        );
+    }
+}
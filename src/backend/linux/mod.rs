@@ -1 +1,51 @@
+// The dialogs, which the `testing` feature answers itself
+#[cfg(not(feature = "testing"))]
+mod dispatch;
+#[cfg(not(feature = "testing"))]
+pub(crate) mod kdialog;
+#[cfg(not(feature = "testing"))]
+pub(crate) mod mime;
+#[cfg(not(feature = "testing"))]
 pub(crate) mod zenity;
+
+mod helper;
+mod launcher;
+mod sandbox;
+
+#[cfg(feature = "gtk3")]
+use crate::backend::gtk3;
+#[cfg(feature = "xdg-portal")]
+use crate::backend::xdg_desktop_portal as portal;
+use crate::linux_backend::backends;
+use crate::Backend;
+
+/// First backend of [`crate::set_backends`] that is available right now, along with its version:
+/// the FileChooser version of the portal, or the major version of zenity
+pub(crate) fn active_backend() -> Option<(Backend, Option<u32>)> {
+    backends().into_iter().find_map(|backend| {
+        let available = match backend {
+            #[cfg(feature = "xdg-portal")]
+            Backend::XdgPortal => match pollster::block_on(portal::version()) {
+                Ok(version) => return Some((backend, Some(version))),
+                Err(err) => {
+                    log::debug!("Backend {backend} is not available: {err}");
+                    false
+                }
+            },
+            #[cfg(feature = "gtk3")]
+            Backend::Gtk3 => gtk3::is_available(),
+            Backend::Zenity => {
+                let available = helper::is_available("zenity");
+                #[cfg(not(feature = "testing"))]
+                if available {
+                    return Some((backend, zenity::major_version()));
+                }
+                available
+            }
+            Backend::Kdialog => helper::is_available("kdialog"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        };
+        available.then_some((backend, None))
+    })
+}
@@ -0,0 +1,157 @@
+//! Detecting whether the app runs inside a Flatpak or Snap sandbox, where the host has to be
+//! reached through the XDG portals, or `flatpak-spawn --host` for the helper programs.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Flatpak mounts `/.flatpak-info` into every sandbox
+pub fn is_flatpak() -> bool {
+    Path::new("/.flatpak-info").exists()
+}
+
+/// Snap sets `SNAP` for every app it runs
+pub fn is_snap() -> bool {
+    std::env::var_os("SNAP").is_some()
+}
+
+pub fn is_sandboxed() -> bool {
+    is_flatpak() || is_snap()
+}
+
+/// The parts of `/.flatpak-info` needed to run helpers on the host
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FlatpakInfo {
+    /// Id of the app, which documents picked on the host are exported to
+    pub app_id: Option<String>,
+    /// Host folder that is mounted at `/app`
+    app_path: Option<PathBuf>,
+    /// Host folder that is mounted at `/usr`
+    runtime_path: Option<PathBuf>,
+    /// `flatpak-spawn --host` needs `--talk-name=org.freedesktop.Flatpak`
+    pub can_spawn_on_host: bool,
+}
+
+impl FlatpakInfo {
+    /// `None` outside of Flatpak
+    pub fn get() -> Option<&'static Self> {
+        static INFO: OnceLock<Option<FlatpakInfo>> = OnceLock::new();
+        INFO.get_or_init(|| {
+            let contents = std::fs::read_to_string("/.flatpak-info").ok()?;
+            Some(Self::parse(&contents))
+        })
+        .as_ref()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut info = Self::default();
+        let mut section = "";
+
+        for line in contents.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name;
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            match (section, key.trim(), value.trim()) {
+                ("Application", "name", name) => info.app_id = Some(name.into()),
+                ("Instance", "app-path", path) => info.app_path = Some(path.into()),
+                ("Instance", "runtime-path", path) => info.runtime_path = Some(path.into()),
+                ("Session Bus Policy", "org.freedesktop.Flatpak", "talk" | "own") => {
+                    info.can_spawn_on_host = true
+                }
+                _ => {}
+            }
+        }
+
+        info
+    }
+
+    fn mounts(&self) -> impl Iterator<Item = (&Path, &Path)> {
+        let app = self
+            .app_path
+            .as_deref()
+            .map(|host| (Path::new("/app"), host));
+        let runtime = self
+            .runtime_path
+            .as_deref()
+            .map(|host| (Path::new("/usr"), host));
+        app.into_iter().chain(runtime)
+    }
+
+    /// Where `path` of the sandbox is on the host, `/app` and `/usr` are the only folders that are
+    /// mounted somewhere else, everything the app can see of the host keeps its path
+    pub fn host_path(&self, path: &Path) -> PathBuf {
+        self.mounts()
+            .find_map(|(sandbox, host)| Some(host.join(path.strip_prefix(sandbox).ok()?)))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    /// The reverse of [`Self::host_path`], for paths picked in a helper that ran on the host
+    #[cfg(any(test, not(feature = "testing")))]
+    pub fn sandbox_path(&self, path: &Path) -> PathBuf {
+        self.mounts()
+            .find_map(|(sandbox, host)| Some(sandbox.join(path.strip_prefix(host).ok()?)))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlatpakInfo;
+    use std::path::Path;
+
+    const INFO: &str = "\
+[Application]
+name=org.example.App
+runtime=runtime/org.freedesktop.Platform/x86_64/24.08
+
+[Instance]
+app-path=/var/lib/flatpak/app/org.example.App/x86_64/stable/abc/files
+runtime-path=/var/lib/flatpak/runtime/org.freedesktop.Platform/x86_64/24.08/def/files
+
+[Session Bus Policy]
+org.freedesktop.Flatpak=talk
+";
+
+    #[test]
+    fn parse() {
+        let info = FlatpakInfo::parse(INFO);
+        assert!(info.can_spawn_on_host);
+        assert_eq!(info.app_id.as_deref(), Some("org.example.App"));
+        assert!(!FlatpakInfo::parse("[Application]\nname=org.example.App\n").can_spawn_on_host);
+    }
+
+    #[test]
+    fn host_path() {
+        let info = FlatpakInfo::parse(INFO);
+        let app = Path::new("/var/lib/flatpak/app/org.example.App/x86_64/stable/abc/files");
+
+        assert_eq!(
+            info.host_path(Path::new("/app/share/examples")),
+            app.join("share/examples")
+        );
+        assert_eq!(
+            info.host_path(Path::new("/home/user/a.txt")),
+            Path::new("/home/user/a.txt")
+        );
+        // Only whole components are prefixes
+        assert_eq!(
+            info.host_path(Path::new("/application")),
+            Path::new("/application")
+        );
+
+        assert_eq!(
+            info.sandbox_path(&app.join("share/examples")),
+            Path::new("/app/share/examples")
+        );
+        assert_eq!(
+            info.sandbox_path(Path::new("/home/user/a.txt")),
+            Path::new("/home/user/a.txt")
+        );
+    }
+}
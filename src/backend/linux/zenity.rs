@@ -1,146 +1,349 @@
-use std::{error::Error, fmt::Display, path::PathBuf, process::Command};
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use raw_window_handle::RawWindowHandle;
 
+use super::helper::{self, Picked};
+use super::mime::MimeDatabase;
 use crate::{
-    file_dialog::Filter,
+    file_dialog::{glob_matches, Filter},
     message_dialog::{MessageButtons, MessageLevel},
-    FileDialog, MessageDialogResult,
+    Error, FileDialog, MessageDialogResult,
 };
 
-#[derive(Debug)]
-pub enum ZenityError {
-    Io(std::io::Error),
-    FromUtf8Error(std::string::FromUtf8Error),
+pub type ZenityResult<T> = Result<T, Error>;
+
+const ZENITY: &str = "zenity";
+
+fn command(program: &OsStr) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.arg("--no-markup");
+    cmd
 }
 
-impl Error for ZenityError {}
+/// Window set with `set_parent`, zenity can only attach dialogs to X11 windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parent {
+    None,
+    X11(std::os::raw::c_ulong),
+    Other,
+}
 
-impl Display for ZenityError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ZenityError::Io(io) => write!(f, "{io}"),
-            ZenityError::FromUtf8Error(err) => err.fmt(f),
+impl Parent {
+    pub fn new(handle: Option<&RawWindowHandle>) -> Self {
+        match handle {
+            None => Self::None,
+            Some(RawWindowHandle::Xlib(handle)) => Self::X11(handle.window),
+            Some(RawWindowHandle::Xcb(handle)) => Self::X11(handle.window.get().into()),
+            Some(_) => Self::Other,
         }
     }
+
+    /// Whether zenity can attach to the parent, `--attach` is gone since zenity 4 (GTK 4)
+    fn can_attach(self) -> bool {
+        matches!(self, Self::X11(_)) && major_version().is_some_and(|major| major < 4)
+    }
+
+    /// `--title` and the arguments attaching the dialog to the parent.
+    ///
+    /// A dialog that can't be attached opens as a window of its own, eg. on Wayland, so it gets
+    /// at least the name of the app as title to tell which window it belongs to.
+    fn args(self, title: Option<&str>, can_attach: bool) -> Vec<OsString> {
+        let mut args = Vec::new();
+
+        let title = title.filter(|title| !title.is_empty());
+        let app_name = || {
+            let exe = std::env::current_exe().ok()?;
+            Some(exe.file_name()?.to_string_lossy().into_owned())
+        };
+        let title = match self {
+            Self::X11(_) if can_attach => title.map(str::to_owned),
+            Self::None => title.map(str::to_owned),
+            _ => title.map(str::to_owned).or_else(app_name),
+        };
+        if let Some(title) = title {
+            args.push("--title".into());
+            args.push(title.into());
+        }
+
+        if let (Self::X11(xid), true) = (self, can_attach) {
+            args.push("--modal".into());
+            args.push(format!("--attach={xid}").into());
+        }
+
+        args
+    }
 }
 
-impl From<std::io::Error> for ZenityError {
-    fn from(value: std::io::Error) -> Self {
-        Self::Io(value)
+/// Major version of zenity, checked once.
+///
+/// Only a zenity installed next to the app is checked: asking the host from inside of Flatpak
+/// would hold up the first dialog with `flatpak-spawn`, so dialogs of zenity running on the host
+/// are never attached.
+pub fn major_version() -> Option<u32> {
+    static VERSION: OnceLock<Option<u32>> = OnceLock::new();
+
+    *VERSION.get_or_init(|| {
+        if !helper::is_installed(ZENITY) {
+            return None;
+        }
+        let output = Command::new(ZENITY)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .split('.')
+            .next()?
+            .parse()
+            .ok()
+    })
+}
+
+/// Filters in the order they are passed to zenity, which selects the first one by default
+pub(super) fn ordered_filters(dialog: &FileDialog) -> impl Iterator<Item = &Filter> {
+    let current = dialog.current_filter.and_then(|id| dialog.filters.get(id));
+
+    current.into_iter().chain(
+        dialog
+            .filters
+            .iter()
+            .enumerate()
+            .filter(move |(id, _)| Some(*id) != dialog.current_filter)
+            .map(|(_, filter)| filter),
+    )
+}
+
+/// Glob patterns of the filter, with MIME types expanded through the shared-mime-info database
+pub(super) fn filter_globs(filter: &Filter) -> Vec<String> {
+    let mut globs: Vec<String> = filter
+        .extensions
+        .iter()
+        .map(|ext| match ext.as_str() {
+            // Any file, not only the ones with an extension
+            "*" | "" => "*".to_owned(),
+            ext => format!("*.{ext}"),
+        })
+        .chain(filter.patterns.iter().cloned())
+        .collect();
+
+    if !filter.mime_types.is_empty() {
+        globs.extend(MimeDatabase::get().globs(&filter.mime_types));
     }
+
+    globs
 }
 
-impl From<std::string::FromUtf8Error> for ZenityError {
-    fn from(value: std::string::FromUtf8Error) -> Self {
-        Self::FromUtf8Error(value)
+fn filter_matches(filter: &Filter, path: &Path) -> bool {
+    if filter.matches(path) {
+        return true;
     }
+
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+
+    !filter.mime_types.is_empty()
+        && MimeDatabase::get()
+            .globs(&filter.mime_types)
+            .iter()
+            .any(|glob| glob_matches(glob.as_bytes(), name.as_bytes()))
 }
 
-pub type ZenityResult<T> = Result<T, ZenityError>;
+/// Zenity does not report which filter was active, so it is inferred from the picked path,
+/// preferring the filter that was preselected with `FileDialog::set_current_filter`
+pub fn infer_filter(dialog: &FileDialog, path: &Path) -> Option<usize> {
+    dialog
+        .current_filter
+        .filter(|id| {
+            dialog
+                .filters
+                .get(*id)
+                .is_some_and(|filter| filter_matches(filter, path))
+        })
+        .or_else(|| {
+            dialog
+                .filters
+                .iter()
+                .position(|filter| filter_matches(filter, path))
+        })
+}
 
-fn command() -> Command {
-    let mut cmd = Command::new("zenity");
-    cmd.arg("--no-markup");
-    cmd
+/// Value of `--filename`, which opens a folder when it ends with `/` and preselects (or fills in
+/// the name of) a file otherwise.
+///
+/// Zenity running on the host gets it translated by [`helper`], through the folder for a file
+/// that doesn't exist yet.
+fn filename(dialog: &FileDialog) -> Option<OsString> {
+    if let Some(path) = dialog.existing_file.as_ref() {
+        return Some(path.into());
+    }
+
+    match (&dialog.starting_directory, &dialog.file_name) {
+        (Some(dir), Some(name)) => Some(dir.join(name).into()),
+        (Some(dir), None) => {
+            let mut dir = OsString::from(dir);
+            if !dir.as_bytes().ends_with(b"/") {
+                dir.push("/");
+            }
+            Some(dir)
+        }
+        (None, Some(name)) => Some(name.into()),
+        (None, None) => None,
+    }
 }
 
-fn file_dialog_command(dialog: &FileDialog) -> Command {
-    let mut command = command();
+/// Zenity has no options for `show_hidden_files`, `can_create_directories` and choices, hidden
+/// files can still be shown with Ctrl+H
+fn file_dialog_command(program: &OsStr, dialog: &FileDialog, can_attach: bool) -> Command {
+    let mut command = command(program);
+
+    let parent = Parent::new(dialog.parent.as_ref());
+    command.args(parent.args(dialog.title.as_deref(), can_attach));
 
-    for filter in &dialog.filters {
+    for filter in ordered_filters(dialog) {
         command.arg("--file-filter");
-        let extensions: Vec<_> = filter
-            .extensions
-            .iter()
-            .map(|ext| format!("*.{}", ext))
-            .collect();
-        command.arg(format!("{} | {}", filter.name, extensions.join(" ")));
+        command.arg(format!(
+            "{} | {}",
+            filter.name,
+            filter_globs(filter).join(" ")
+        ));
     }
 
-    if let Some(name) = dialog.file_name.as_ref() {
+    if let Some(filename) = filename(dialog) {
         command.arg("--filename");
-        command.arg(name);
+        command.arg(filename);
     }
 
-    if let Some(dir) = dialog.starting_directory.as_ref() {
-        command.current_dir(dir);
+    if let Some(label) = dialog.accept_label.as_ref() {
+        command.arg("--ok-label");
+        command.arg(label);
     }
 
     command
 }
 
-async fn run(mut command: Command) -> ZenityResult<Option<String>> {
-    let res = {
-        let (tx, rx) = crate::oneshot::channel();
-        std::thread::spawn(move || {
-            tx.send(command.output()).ok();
-        });
-        rx.await.map_err(std::io::Error::other)??
+async fn run(command: Command) -> ZenityResult<Option<helper::Output>> {
+    let output = helper::output(command).await?;
+
+    if output.status.success() || !output.stdout.is_empty() {
+        Ok(Some(output))
+    } else if output.status.code() == Some(1) {
+        // Cancelled, or closed
+        Ok(None)
+    } else {
+        Err(helper::failed("zenity", output.status))
+    }
+}
+
+/// The path printed by a file dialog, as the app sees it
+async fn picked_path(output: Option<helper::Output>, picked: Picked) -> Option<PathBuf> {
+    let output = output?;
+    let path = parse_path(&output.stdout);
+    Some(helper::picked_path(path, output.on_host, picked).await)
+}
+
+/// The paths printed by a file dialog with `--multiple`, as the app sees them
+async fn picked_paths(output: Option<helper::Output>, picked: Picked) -> Vec<PathBuf> {
+    let Some(output) = output else {
+        return Vec::new();
     };
-    let buffer = String::from_utf8(res.stdout)?;
+    let paths = parse_paths(&output.stdout);
+    helper::picked_paths(paths, output.on_host, picked).await
+}
 
-    Ok((res.status.success() || !buffer.is_empty()).then_some(buffer))
+/// Zenity ends its output with a newline, anything else is part of the path
+fn strip_newline(buffer: &[u8]) -> &[u8] {
+    buffer.strip_suffix(b"\n").unwrap_or(buffer)
 }
 
-pub async fn pick_file(dialog: &FileDialog) -> ZenityResult<Option<PathBuf>> {
-    let mut command = file_dialog_command(dialog);
-    command.arg("--file-selection");
+fn parse_path(buffer: &[u8]) -> PathBuf {
+    OsStr::from_bytes(strip_newline(buffer)).into()
+}
 
-    run(command).await.map(|res| {
-        res.map(|buffer| {
-            let trimed = buffer.trim();
-            trimed.into()
-        })
-    })
+/// Paths picked with `--multiple`, separated by newlines as no other byte can be told apart
+/// from the file names.
+///
+/// File names containing a newline are not supported when picking several files or folders.
+/// Picked paths are absolute, so a line that doesn't start with `/` is still joined to the path
+/// before it, but a newline followed by `/` splits the name in two.
+fn parse_paths(buffer: &[u8]) -> Vec<PathBuf> {
+    let mut paths: Vec<OsString> = Vec::new();
+
+    for line in strip_newline(buffer).split(|byte| *byte == b'\n') {
+        let line = OsStr::from_bytes(line);
+        match paths.last_mut() {
+            Some(path) if !line.as_bytes().starts_with(b"/") => {
+                path.push("\n");
+                path.push(line);
+            }
+            _ => paths.push(line.to_owned()),
+        }
+    }
+
+    paths
+        .into_iter()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect()
 }
 
-pub async fn pick_files(dialog: &FileDialog) -> ZenityResult<Vec<PathBuf>> {
-    let mut command = file_dialog_command(dialog);
-    command.args(["--file-selection", "--multiple"]);
+const PICK_FILE: &[&str] = &["--file-selection"];
+const PICK_FILES: &[&str] = &["--file-selection", "--multiple", "--separator", "\n"];
+const PICK_FOLDER: &[&str] = &["--file-selection", "--directory"];
+const PICK_FOLDERS: &[&str] = &[
+    "--file-selection",
+    "--directory",
+    "--multiple",
+    "--separator",
+    "\n",
+];
+const SAVE_FILE: &[&str] = &["--file-selection", "--save", "--confirm-overwrite"];
+
+/// Runs `program` as a zenity file dialog with the mode given by `args`
+async fn file_dialog(
+    program: &OsStr,
+    dialog: &FileDialog,
+    args: &[&str],
+) -> ZenityResult<Option<helper::Output>> {
+    let can_attach = Parent::new(dialog.parent.as_ref()).can_attach();
+    let mut command = file_dialog_command(program, dialog, can_attach);
+    command.args(args);
+
+    run(command).await
+}
 
-    run(command).await.map(|res| {
-        res.map(|buffer| {
-            let list = buffer.trim().split('|').map(PathBuf::from).collect();
-            list
-        })
-        .unwrap_or_default()
-    })
+pub async fn pick_file(dialog: &FileDialog) -> ZenityResult<Option<PathBuf>> {
+    let output = file_dialog(ZENITY.as_ref(), dialog, PICK_FILE).await?;
+    Ok(picked_path(output, Picked::Files).await)
 }
 
-pub async fn pick_folder(dialog: &FileDialog) -> ZenityResult<Option<PathBuf>> {
-    let mut command = file_dialog_command(dialog);
-    command.args(["--file-selection", "--directory"]);
+pub async fn pick_files(dialog: &FileDialog) -> ZenityResult<Vec<PathBuf>> {
+    let output = file_dialog(ZENITY.as_ref(), dialog, PICK_FILES).await?;
+    Ok(picked_paths(output, Picked::Files).await)
+}
 
-    run(command).await.map(|res| {
-        res.map(|buffer| {
-            let trimed = buffer.trim();
-            trimed.into()
-        })
-    })
+pub async fn pick_folder(dialog: &FileDialog) -> ZenityResult<Option<PathBuf>> {
+    let output = file_dialog(ZENITY.as_ref(), dialog, PICK_FOLDER).await?;
+    Ok(picked_path(output, Picked::Folders).await)
 }
 
 pub async fn pick_folders(dialog: &FileDialog) -> ZenityResult<Vec<PathBuf>> {
-    let mut command = file_dialog_command(dialog);
-    command.args(["--file-selection", "--directory", "--multiple"]);
-
-    run(command).await.map(|res| {
-        res.map(|buffer| {
-            let list = buffer.trim().split('|').map(PathBuf::from).collect();
-            list
-        })
-        .unwrap_or_default()
-    })
+    let output = file_dialog(ZENITY.as_ref(), dialog, PICK_FOLDERS).await?;
+    Ok(picked_paths(output, Picked::Folders).await)
 }
 
 pub async fn save_file(dialog: &FileDialog) -> ZenityResult<Option<PathBuf>> {
-    let mut command = file_dialog_command(dialog);
-    command.args(["--file-selection", "--save", "--confirm-overwrite"]);
-
-    run(command).await.map(|res| {
-        res.map(|buffer| {
-            let trimed = buffer.trim();
-            trimed.into()
-        })
-    })
+    let output = file_dialog(ZENITY.as_ref(), dialog, SAVE_FILE).await?;
+    Ok(picked_path(output, Picked::NewFile).await)
 }
 
 pub async fn message(
@@ -148,6 +351,7 @@ pub async fn message(
     btns: &MessageButtons,
     title: &str,
     description: &str,
+    parent: Parent,
 ) -> ZenityResult<MessageDialogResult> {
     let cmd = match level {
         MessageLevel::Info => "--info",
@@ -161,8 +365,10 @@ pub async fn message(
         _ => None,
     };
 
-    let mut command = command();
-    command.args([cmd, "--title", title, "--text", description]);
+    let mut command = command(ZENITY.as_ref());
+    command.arg(cmd);
+    command.args(parent.args(Some(title), parent.can_attach()));
+    command.args(["--text", description]);
 
     if let Some(ok) = ok_label {
         command.args(["--ok-label", ok]);
@@ -181,9 +387,12 @@ pub async fn question(
     btns: &MessageButtons,
     title: &str,
     description: &str,
+    parent: Parent,
 ) -> ZenityResult<MessageDialogResult> {
-    let mut command = command();
-    command.args(["--question", "--title", title, "--text", description]);
+    let mut command = command(ZENITY.as_ref());
+    command.arg("--question");
+    command.args(parent.args(Some(title), parent.can_attach()));
+    command.args(["--text", description]);
 
     match btns {
         MessageButtons::OkCancel => {
@@ -220,12 +429,12 @@ pub async fn question(
             None => MessageDialogResult::Custom(cancel.clone()),
         },
         MessageButtons::YesNoCancel => match res {
-            Some(output) if output.is_empty() => MessageDialogResult::Yes,
+            Some(output) if output.stdout.is_empty() => MessageDialogResult::Yes,
             Some(_) => MessageDialogResult::No,
             None => MessageDialogResult::Cancel,
         },
         MessageButtons::YesNoCancelCustom(yes, no, cancel) => match res {
-            Some(output) if output.is_empty() => MessageDialogResult::Custom(yes.clone()),
+            Some(output) if output.stdout.is_empty() => MessageDialogResult::Custom(yes.clone()),
             Some(_) => MessageDialogResult::Custom(no.clone()),
             None => MessageDialogResult::Custom(cancel.clone()),
         },
@@ -235,7 +444,238 @@ pub async fn question(
 
 #[cfg(test)]
 mod tests {
+    use super::Picked;
     use crate::FileDialog;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+
+    #[test]
+    fn infer_filter() {
+        let dialog = FileDialog::new()
+            .add_filter("Text", &["txt", "md"])
+            .add_filter("Archive", &["tar.gz"])
+            .add_filter("All", &["*"]);
+
+        assert_eq!(super::infer_filter(&dialog, "/a/b.MD".as_ref()), Some(0));
+        assert_eq!(
+            super::infer_filter(&dialog, "/a/b.tar.gz".as_ref()),
+            Some(1)
+        );
+        assert_eq!(super::infer_filter(&dialog, "/a/b.png".as_ref()), Some(2));
+
+        let dialog = dialog.set_current_filter(2);
+        assert_eq!(super::infer_filter(&dialog, "/a/b.txt".as_ref()), Some(2));
+    }
+
+    #[test]
+    fn current_filter_is_passed_first() {
+        let dialog = FileDialog::new()
+            .add_filter("Text", &["txt"])
+            .add_filter("Rust", &["rs"])
+            .set_current_filter(1);
+
+        let names: Vec<_> = super::ordered_filters(&dialog)
+            .map(|filter| filter.name.as_str())
+            .collect();
+        assert_eq!(names, ["Rust", "Text"]);
+    }
+
+    fn args(dialog: &FileDialog) -> Vec<String> {
+        let command = super::file_dialog_command("zenity".as_ref(), dialog, false);
+        assert_eq!(command.get_current_dir(), None);
+        command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn existing_file_is_passed_as_filename() {
+        let dialog = FileDialog::new().set_existing_file("/home/user/notes.txt");
+        assert_eq!(
+            args(&dialog),
+            ["--no-markup", "--filename", "/home/user/notes.txt"]
+        );
+    }
+
+    #[test]
+    fn directory_is_passed_as_filename() {
+        let dialog = FileDialog::new().set_directory("/home/user");
+        assert_eq!(args(&dialog), ["--no-markup", "--filename", "/home/user/"]);
+
+        let dialog = FileDialog::new().set_directory("/home/user/");
+        assert_eq!(args(&dialog), ["--no-markup", "--filename", "/home/user/"]);
+
+        let dialog = dialog.set_file_name("notes.txt");
+        assert_eq!(
+            args(&dialog),
+            ["--no-markup", "--filename", "/home/user/notes.txt"]
+        );
+
+        let dialog = FileDialog::new().set_file_name("notes.txt");
+        assert_eq!(args(&dialog), ["--no-markup", "--filename", "notes.txt"]);
+    }
+
+    #[test]
+    fn parent_args() {
+        use super::Parent;
+        use raw_window_handle::{RawWindowHandle, XlibWindowHandle};
+
+        let xlib = RawWindowHandle::Xlib(XlibWindowHandle::new(0x2a00005));
+        let parent = Parent::new(Some(&xlib));
+        assert_eq!(parent, Parent::X11(0x2a00005));
+
+        let args = |parent: Parent, title, can_attach| -> Vec<String> {
+            parent
+                .args(title, can_attach)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect()
+        };
+        assert_eq!(
+            args(parent, Some("Open"), true),
+            ["--title", "Open", "--modal", "--attach=44040197"]
+        );
+        assert_eq!(args(parent, None, true), ["--modal", "--attach=44040197"]);
+        assert_eq!(args(Parent::None, Some(""), false), [] as [&str; 0]);
+
+        // Not attached, so named after the app
+        let app_name = std::env::current_exe().unwrap();
+        let app_name = app_name.file_name().unwrap().to_str().unwrap();
+        assert_eq!(args(parent, None, false), ["--title", app_name]);
+        assert_eq!(args(Parent::Other, Some(""), false), ["--title", app_name]);
+        assert_eq!(
+            args(Parent::Other, Some("Open"), false),
+            ["--title", "Open"]
+        );
+    }
+
+    #[test]
+    fn file_dialog_args() {
+        let dialog = FileDialog::new()
+            .set_title("Open a document")
+            .set_accept_label("Import")
+            .add_filter("Text", &["txt", "md"])
+            .add_filter("All", &["*"]);
+
+        assert_eq!(
+            args(&dialog),
+            [
+                "--no-markup",
+                "--title",
+                "Open a document",
+                "--file-filter",
+                "Text | *.txt *.md",
+                "--file-filter",
+                "All | *",
+                "--ok-label",
+                "Import",
+            ]
+        );
+    }
+
+    /// A fake `zenity` printing the NUL separated paths of the file passed as `--title` joined
+    /// with `--separator`, like zenity prints the selection
+    fn fake_zenity() -> &'static Path {
+        static FAKE: OnceLock<PathBuf> = OnceLock::new();
+
+        FAKE.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("rfd-fake-zenity-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let script = dir.join("zenity");
+            std::fs::write(
+                &script,
+                r#"#!/bin/sh
+sep='|'
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --title) selection=$2; shift ;;
+        --separator) sep=$2; shift ;;
+    esac
+    shift
+done
+tr '\0' "$sep" < "$selection"
+echo
+"#,
+            )
+            .unwrap();
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+            script
+        })
+    }
+
+    /// Dialog the fake zenity answers with `paths`
+    fn answered_with(paths: &[PathBuf]) -> (FileDialog, PathBuf) {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let selection = std::env::temp_dir().join(format!(
+            "rfd-zenity-selection-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| path.as_os_str().as_bytes())
+            .collect();
+        std::fs::write(&selection, paths.join(&b'\0')).unwrap();
+
+        let dialog = FileDialog::new().set_title(selection.to_str().unwrap());
+        (dialog, selection)
+    }
+
+    fn picked_paths(dialog: &FileDialog, args: &[&str], picked: Picked) -> Vec<PathBuf> {
+        pollster::block_on(async {
+            let output = super::file_dialog(fake_zenity().as_ref(), dialog, args).await;
+            super::picked_paths(output.unwrap(), picked).await
+        })
+    }
+
+    fn picked_path(dialog: &FileDialog, args: &[&str], picked: Picked) -> Option<PathBuf> {
+        pollster::block_on(async {
+            let output = super::file_dialog(fake_zenity().as_ref(), dialog, args).await;
+            super::picked_path(output.unwrap(), picked).await
+        })
+    }
+
+    #[test]
+    fn parse_paths_round_trip() {
+        let paths = [
+            PathBuf::from("/tmp/a|b.txt"),
+            PathBuf::from("/tmp/ leading and trailing "),
+            PathBuf::from(OsStr::from_bytes(b"/tmp/not \xff utf-8")),
+            PathBuf::from("/tmp/new\nline.txt"),
+            PathBuf::from("/tmp/last"),
+        ];
+
+        let (dialog, selection) = answered_with(&paths);
+        let picked = picked_paths(&dialog, super::PICK_FILES, Picked::Files);
+        let picked_folders = picked_paths(&dialog, super::PICK_FOLDERS, Picked::Folders);
+        std::fs::remove_file(selection).unwrap();
+
+        assert_eq!(picked, paths);
+        assert_eq!(picked_folders, paths);
+    }
+
+    #[test]
+    fn parse_path_round_trip() {
+        for path in [
+            PathBuf::from("/tmp/ spaces \n"),
+            PathBuf::from("/tmp/new\n/line"),
+            PathBuf::from(OsStr::from_bytes(b"/tmp/\xfe|\xff")),
+        ] {
+            let (dialog, selection) = answered_with(std::slice::from_ref(&path));
+            let picked = picked_path(&dialog, super::PICK_FILE, Picked::Files);
+            let saved = picked_path(&dialog, super::SAVE_FILE, Picked::NewFile);
+            std::fs::remove_file(selection).unwrap();
+
+            assert_eq!(picked, Some(path.clone()));
+            assert_eq!(saved, Some(path));
+        }
+    }
 
     #[test]
     #[ignore]
@@ -245,6 +685,7 @@ mod tests {
             &crate::message_dialog::MessageButtons::Ok,
             "hi",
             "me",
+            super::Parent::None,
         ))
         .unwrap();
         pollster::block_on(super::message(
@@ -252,6 +693,7 @@ mod tests {
             &crate::message_dialog::MessageButtons::Ok,
             "hi",
             "me",
+            super::Parent::None,
         ))
         .unwrap();
         pollster::block_on(super::message(
@@ -259,6 +701,7 @@ mod tests {
             &crate::message_dialog::MessageButtons::Ok,
             "hi",
             "me",
+            super::Parent::None,
         ))
         .unwrap();
     }
@@ -270,12 +713,14 @@ mod tests {
             &crate::message_dialog::MessageButtons::OkCancel,
             "hi",
             "me",
+            super::Parent::None,
         ))
         .unwrap();
         pollster::block_on(super::question(
             &crate::message_dialog::MessageButtons::YesNo,
             "hi",
             "me",
+            super::Parent::None,
         ))
         .unwrap();
     }
@@ -283,28 +728,24 @@ mod tests {
     #[test]
     #[ignore]
     fn pick_file() {
-        let path = pollster::block_on(super::pick_file(&FileDialog::default())).unwrap();
-        dbg!(path);
+        pollster::block_on(super::pick_file(&FileDialog::default())).unwrap();
     }
 
     #[test]
     #[ignore]
     fn pick_files() {
-        let path = pollster::block_on(super::pick_files(&FileDialog::default())).unwrap();
-        dbg!(path);
+        pollster::block_on(super::pick_files(&FileDialog::default())).unwrap();
     }
 
     #[test]
     #[ignore]
     fn pick_folder() {
-        let path = pollster::block_on(super::pick_folder(&FileDialog::default())).unwrap();
-        dbg!(path);
+        pollster::block_on(super::pick_folder(&FileDialog::default())).unwrap();
     }
 
     #[test]
     #[ignore]
     fn save_file() {
-        let path = pollster::block_on(super::save_file(&FileDialog::default())).unwrap();
-        dbg!(path);
+        pollster::block_on(super::save_file(&FileDialog::default())).unwrap();
     }
 }
@@ -1,6 +1,11 @@
+#[cfg(not(feature = "testing"))]
 mod file_dialog;
+mod launcher;
+#[cfg(not(feature = "testing"))]
 mod message_dialog;
 
+#[cfg(not(feature = "testing"))]
 mod modal_future;
 
+#[cfg(not(feature = "testing"))]
 mod utils;
@@ -8,7 +8,30 @@ use self::panel_ffi::Panel;
 use super::modal_future::ModalFuture;
 use super::utils::{run_on_main, window_from_raw_window_handle};
 use crate::backend::DialogFutureType;
-use crate::{FileDialog, FileHandle};
+use crate::error::log_error;
+use crate::file_dialog::{filters_accept, Filter};
+use crate::{Error, FileDialog, FileHandle};
+
+/// Picked files that match the filters, as patterns can't always be expressed natively.
+///
+/// Picking only files that the filters reject is an error, so it can't be mistaken for a
+/// cancelled dialog.
+fn accepted(filters: &[Filter], mut paths: Vec<PathBuf>) -> Result<Option<Vec<PathBuf>>, Error> {
+    let picked = paths.len();
+    paths.retain(|path| filters_accept(filters, path));
+
+    if paths.is_empty() && picked > 0 {
+        Err(Error::InvalidOptions(
+            "none of the picked files match the filters".into(),
+        ))
+    } else {
+        Ok((!paths.is_empty()).then_some(paths))
+    }
+}
+
+fn wrap_paths(paths: Vec<PathBuf>) -> Vec<FileHandle> {
+    paths.into_iter().map(FileHandle::wrap).collect()
+}
 
 //
 // File Picker
@@ -17,28 +40,37 @@ use crate::{FileDialog, FileHandle};
 use crate::backend::FilePickerDialogImpl;
 impl FilePickerDialogImpl for FileDialog {
     fn pick_file(self) -> Option<PathBuf> {
+        log_error(FilePickerDialogImpl::try_pick_file(self))
+    }
+
+    fn pick_files(self) -> Option<Vec<PathBuf>> {
+        log_error(FilePickerDialogImpl::try_pick_files(self))
+    }
+
+    fn try_pick_file(self) -> Result<Option<PathBuf>, Error> {
         autoreleasepool(move |_| {
             run_on_main(move |mtm| {
                 let panel = Panel::build_pick_file(&self, mtm);
 
                 if panel.run_modal() == NSModalResponseOK {
-                    Some(panel.get_result())
+                    let paths = accepted(&self.filters, vec![panel.get_result()])?;
+                    Ok(paths.and_then(|paths| paths.into_iter().next()))
                 } else {
-                    None
+                    Ok(None)
                 }
             })
         })
     }
 
-    fn pick_files(self) -> Option<Vec<PathBuf>> {
+    fn try_pick_files(self) -> Result<Option<Vec<PathBuf>>, Error> {
         autoreleasepool(move |_| {
             run_on_main(move |mtm| {
                 let panel = Panel::build_pick_files(&self, mtm);
 
                 if panel.run_modal() == NSModalResponseOK {
-                    Some(panel.get_results())
+                    accepted(&self.filters, panel.get_results())
                 } else {
-                    None
+                    Ok(None)
                 }
             })
         })
@@ -48,45 +80,52 @@ impl FilePickerDialogImpl for FileDialog {
 use crate::backend::AsyncFilePickerDialogImpl;
 impl AsyncFilePickerDialogImpl for FileDialog {
     fn pick_file_async(self) -> DialogFutureType<Option<FileHandle>> {
+        let future = self.try_pick_file_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+        let future = self.try_pick_files_async();
+        Box::pin(async move { log_error(future.await) })
+    }
+
+    fn try_pick_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
         let win = self.parent.as_ref().map(window_from_raw_window_handle);
 
+        let filters = self.filters.clone();
+
         let future = ModalFuture::new(
             win,
             move |mtm| Panel::build_pick_file(&self, mtm),
-            |panel, res_id| {
-                if res_id == NSModalResponseOK {
-                    Some(panel.get_result().into())
-                } else {
-                    None
-                }
-            },
+            move |panel, res_id| (res_id == NSModalResponseOK).then(|| panel.get_result()),
         );
 
-        Box::pin(future)
+        Box::pin(async move {
+            let Some(path) = future.await else {
+                return Ok(None);
+            };
+            let paths = accepted(&filters, vec![path])?;
+            Ok(paths.and_then(|paths| paths.into_iter().next().map(FileHandle::wrap)))
+        })
     }
 
-    fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+    fn try_pick_files_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
         let win = self.parent.as_ref().map(window_from_raw_window_handle);
 
+        let filters = self.filters.clone();
+
         let future = ModalFuture::new(
             win,
             move |mtm| Panel::build_pick_files(&self, mtm),
-            |panel, res_id| {
-                if res_id == NSModalResponseOK {
-                    Some(
-                        panel
-                            .get_results()
-                            .into_iter()
-                            .map(FileHandle::wrap)
-                            .collect(),
-                    )
-                } else {
-                    None
-                }
-            },
+            move |panel, res_id| (res_id == NSModalResponseOK).then(|| panel.get_results()),
         );
 
-        Box::pin(future)
+        Box::pin(async move {
+            let Some(paths) = future.await else {
+                return Ok(None);
+            };
+            Ok(accepted(&filters, paths)?.map(wrap_paths))
+        })
     }
 }
 
@@ -286,3 +325,14 @@ impl AsyncFileSaveDialogImpl for FileDialog {
         Box::pin(future)
     }
 }
+
+//
+// Files Save
+//
+
+// Emulated with the folder picker
+use crate::backend::FilesSaveDialogImpl;
+impl FilesSaveDialogImpl for FileDialog {}
+
+use crate::backend::AsyncFilesSaveDialogImpl;
+impl AsyncFilesSaveDialogImpl for FileDialog {}
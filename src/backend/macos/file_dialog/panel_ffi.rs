@@ -113,7 +113,8 @@ fn utype_for_extension(ext: &str) -> Option<Retained<UTType>> {
 
 fn utypes_for_filter(filter: &Filter) -> Retained<NSArray<UTType>> {
     let types: Vec<_> = filter
-        .extensions
+        .approximate_extensions()
+        .unwrap_or_default()
         .iter()
         .filter_map(|ext| utype_for_extension(ext))
         .collect();
@@ -136,7 +137,12 @@ trait PanelExt {
         let mut exts: Vec<String> = Vec::new();
 
         for filter in opt.filters.iter() {
-            exts.append(&mut filter.extensions.to_vec());
+            match filter.approximate_extensions() {
+                Some(mut extensions) => exts.append(&mut extensions),
+                // The pattern can't be expressed as an extension, so every file is shown
+                // and the selection is checked afterwards
+                None => return,
+            }
         }
 
         let f_raw: Vec<_> = exts.iter().map(|ext| NSString::from_str(ext)).collect();
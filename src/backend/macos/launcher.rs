@@ -0,0 +1,45 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::backend::LauncherImpl;
+use crate::{Error, Launcher};
+
+impl LauncherImpl for Launcher {
+    fn open_uri(self, uri: &str) -> Result<(), Error> {
+        open(uri.as_ref())
+    }
+
+    fn open_file(self, path: &Path) -> Result<(), Error> {
+        open(path.as_os_str())
+    }
+
+    fn show_in_folder(self, paths: &[PathBuf]) -> Result<(), Error> {
+        if paths.is_empty() {
+            return Err(Error::InvalidOptions("No paths to show".into()));
+        }
+
+        // `-R` reveals the files in Finder instead of opening them
+        let mut command = Command::new("open");
+        command.arg("-R").args(paths);
+        run(command)
+    }
+}
+
+/// `open` picks the default application of files and URLs, like Finder does
+fn open(target: &OsStr) -> Result<(), Error> {
+    let mut command = Command::new("open");
+    command.arg(target);
+    run(command)
+}
+
+fn run(mut command: Command) -> Result<(), Error> {
+    let status = command
+        .status()
+        .map_err(|err| Error::Helper(format!("open: {err}")))?;
+
+    if !status.success() {
+        return Err(Error::Helper(format!("open: exited with {status}")));
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! Trait impls of the `testing` feature, answering every dialog from [`crate::testing`] instead
+//! of showing a native one.
+
+use std::path::PathBuf;
+
+use crate::backend::{paths_in_folder, DialogFutureType};
+use crate::error::log_error;
+use crate::message_dialog::MessageDialog;
+use crate::testing::{self, FileMethod};
+use crate::{Error, FileDialog, FileHandle, FileResponse, MessageDialogResult};
+
+fn pick_one(method: FileMethod, dialog: &FileDialog) -> Result<Option<PathBuf>, Error> {
+    let paths = testing::file_dialog(method, dialog, Vec::new())?;
+    Ok(paths.and_then(|paths| paths.into_iter().next()))
+}
+
+fn pick_many(method: FileMethod, dialog: &FileDialog) -> Result<Option<Vec<PathBuf>>, Error> {
+    testing::file_dialog(method, dialog, Vec::new())
+}
+
+fn save_files(dialog: &FileDialog, file_names: Vec<String>) -> Result<Option<Vec<PathBuf>>, Error> {
+    let folder = testing::file_dialog(FileMethod::SaveFiles, dialog, file_names.clone())?;
+    Ok(folder
+        .and_then(|paths| paths.into_iter().next())
+        .map(|folder| paths_in_folder(&folder, &file_names)))
+}
+
+/// Like the helper backends, the active filter is the first one matching the selection
+fn response<T>(dialog: &FileDialog, selection: T, path: Option<&PathBuf>) -> FileResponse<T> {
+    let filter = path.and_then(|path| dialog.filters.iter().position(|f| f.matches(path)));
+    FileResponse {
+        filter,
+        ..FileResponse::new(selection)
+    }
+}
+
+fn wrap_paths(paths: Vec<PathBuf>) -> Vec<FileHandle> {
+    paths.into_iter().map(FileHandle::wrap).collect()
+}
+
+fn ready<T: Send + 'static>(value: T) -> DialogFutureType<T> {
+    Box::pin(std::future::ready(value))
+}
+
+//
+// File Picker
+//
+
+use crate::backend::FilePickerDialogImpl;
+impl FilePickerDialogImpl for FileDialog {
+    fn pick_file(self) -> Option<PathBuf> {
+        log_error(FilePickerDialogImpl::try_pick_file(self))
+    }
+
+    fn pick_files(self) -> Option<Vec<PathBuf>> {
+        log_error(FilePickerDialogImpl::try_pick_files(self))
+    }
+
+    fn try_pick_file(self) -> Result<Option<PathBuf>, Error> {
+        pick_one(FileMethod::PickFile, &self)
+    }
+
+    fn try_pick_files(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        pick_many(FileMethod::PickFiles, &self)
+    }
+
+    fn try_pick_file_with_response(self) -> Result<Option<FileResponse<PathBuf>>, Error> {
+        let path = pick_one(FileMethod::PickFile, &self)?;
+        Ok(path.map(|path| response(&self, path.clone(), Some(&path))))
+    }
+
+    fn try_pick_files_with_response(self) -> Result<Option<FileResponse<Vec<PathBuf>>>, Error> {
+        let paths = pick_many(FileMethod::PickFiles, &self)?;
+        Ok(paths.map(|paths| {
+            let first = paths.first().cloned();
+            response(&self, paths, first.as_ref())
+        }))
+    }
+}
+
+use crate::backend::AsyncFilePickerDialogImpl;
+impl AsyncFilePickerDialogImpl for FileDialog {
+    fn pick_file_async(self) -> DialogFutureType<Option<FileHandle>> {
+        ready(FilePickerDialogImpl::pick_file(self).map(FileHandle::wrap))
+    }
+
+    fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+        ready(FilePickerDialogImpl::pick_files(self).map(wrap_paths))
+    }
+
+    fn try_pick_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        ready(FilePickerDialogImpl::try_pick_file(self).map(|path| path.map(FileHandle::wrap)))
+    }
+
+    fn try_pick_files_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        ready(FilePickerDialogImpl::try_pick_files(self).map(|paths| paths.map(wrap_paths)))
+    }
+
+    fn try_pick_file_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<FileHandle>>, Error>> {
+        let res = FilePickerDialogImpl::try_pick_file_with_response(self);
+        ready(res.map(|res| res.map(|res| res.map(FileHandle::wrap))))
+    }
+
+    fn try_pick_files_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<Vec<FileHandle>>>, Error>> {
+        let res = FilePickerDialogImpl::try_pick_files_with_response(self);
+        ready(res.map(|res| res.map(|res| res.map(wrap_paths))))
+    }
+}
+
+//
+// Folder Picker
+//
+
+use crate::backend::FolderPickerDialogImpl;
+impl FolderPickerDialogImpl for FileDialog {
+    fn pick_folder(self) -> Option<PathBuf> {
+        log_error(FolderPickerDialogImpl::try_pick_folder(self))
+    }
+
+    fn pick_folders(self) -> Option<Vec<PathBuf>> {
+        log_error(FolderPickerDialogImpl::try_pick_folders(self))
+    }
+
+    fn try_pick_folder(self) -> Result<Option<PathBuf>, Error> {
+        pick_one(FileMethod::PickFolder, &self)
+    }
+
+    fn try_pick_folders(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        pick_many(FileMethod::PickFolders, &self)
+    }
+}
+
+use crate::backend::AsyncFolderPickerDialogImpl;
+impl AsyncFolderPickerDialogImpl for FileDialog {
+    fn pick_folder_async(self) -> DialogFutureType<Option<FileHandle>> {
+        ready(FolderPickerDialogImpl::pick_folder(self).map(FileHandle::wrap))
+    }
+
+    fn pick_folders_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+        ready(FolderPickerDialogImpl::pick_folders(self).map(wrap_paths))
+    }
+
+    fn try_pick_folder_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        ready(FolderPickerDialogImpl::try_pick_folder(self).map(|path| path.map(FileHandle::wrap)))
+    }
+
+    fn try_pick_folders_async(self) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        ready(FolderPickerDialogImpl::try_pick_folders(self).map(|paths| paths.map(wrap_paths)))
+    }
+}
+
+//
+// File or Folder Picker
+//
+
+#[cfg(target_os = "macos")]
+use crate::backend::FileOrFolderPickerDialogImpl;
+#[cfg(target_os = "macos")]
+impl FileOrFolderPickerDialogImpl for FileDialog {
+    fn pick_file_or_folder(self) -> Option<PathBuf> {
+        log_error(pick_one(FileMethod::PickFileOrFolder, &self))
+    }
+
+    fn pick_files_or_folders(self) -> Option<Vec<PathBuf>> {
+        log_error(pick_many(FileMethod::PickFilesOrFolders, &self))
+    }
+}
+
+#[cfg(target_os = "macos")]
+use crate::backend::AsyncFileOrFolderPickerDialogImpl;
+#[cfg(target_os = "macos")]
+impl AsyncFileOrFolderPickerDialogImpl for FileDialog {
+    fn pick_file_or_folder_async(self) -> DialogFutureType<Option<FileHandle>> {
+        ready(FileOrFolderPickerDialogImpl::pick_file_or_folder(self).map(FileHandle::wrap))
+    }
+
+    fn pick_files_or_folders_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
+        ready(FileOrFolderPickerDialogImpl::pick_files_or_folders(self).map(wrap_paths))
+    }
+}
+
+//
+// File Save
+//
+
+use crate::backend::FileSaveDialogImpl;
+impl FileSaveDialogImpl for FileDialog {
+    fn save_file(self) -> Option<PathBuf> {
+        log_error(FileSaveDialogImpl::try_save_file(self))
+    }
+
+    fn try_save_file(self) -> Result<Option<PathBuf>, Error> {
+        pick_one(FileMethod::SaveFile, &self)
+    }
+
+    fn try_save_file_with_response(self) -> Result<Option<FileResponse<PathBuf>>, Error> {
+        let path = pick_one(FileMethod::SaveFile, &self)?;
+        Ok(path.map(|path| response(&self, path.clone(), Some(&path))))
+    }
+}
+
+use crate::backend::AsyncFileSaveDialogImpl;
+impl AsyncFileSaveDialogImpl for FileDialog {
+    fn save_file_async(self) -> DialogFutureType<Option<FileHandle>> {
+        ready(FileSaveDialogImpl::save_file(self).map(FileHandle::wrap))
+    }
+
+    fn try_save_file_async(self) -> DialogFutureType<Result<Option<FileHandle>, Error>> {
+        ready(FileSaveDialogImpl::try_save_file(self).map(|path| path.map(FileHandle::wrap)))
+    }
+
+    fn try_save_file_with_response_async(
+        self,
+    ) -> DialogFutureType<Result<Option<FileResponse<FileHandle>>, Error>> {
+        let res = FileSaveDialogImpl::try_save_file_with_response(self);
+        ready(res.map(|res| res.map(|res| res.map(FileHandle::wrap))))
+    }
+}
+
+//
+// Files Save
+//
+
+use crate::backend::FilesSaveDialogImpl;
+impl FilesSaveDialogImpl for FileDialog {
+    fn try_save_files(self, file_names: Vec<String>) -> Result<Option<Vec<PathBuf>>, Error> {
+        save_files(&self, file_names)
+    }
+}
+
+use crate::backend::AsyncFilesSaveDialogImpl;
+impl AsyncFilesSaveDialogImpl for FileDialog {
+    fn try_save_files_async(
+        self,
+        file_names: Vec<String>,
+    ) -> DialogFutureType<Result<Option<Vec<FileHandle>>, Error>> {
+        ready(save_files(&self, file_names).map(|paths| paths.map(wrap_paths)))
+    }
+}
+
+//
+// Message Dialog
+//
+
+use crate::backend::MessageDialogImpl;
+impl MessageDialogImpl for MessageDialog {
+    fn show(self) -> MessageDialogResult {
+        testing::message_dialog(&self)
+    }
+}
+
+use crate::backend::AsyncMessageDialogImpl;
+impl AsyncMessageDialogImpl for MessageDialog {
+    fn show_async(self) -> DialogFutureType<MessageDialogResult> {
+        ready(testing::message_dialog(&self))
+    }
+}
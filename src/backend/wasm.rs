@@ -1,8 +1,11 @@
 mod file_dialog;
 
 use crate::{
-    file_dialog::FileDialog, file_handle::WasmFileHandleKind, FileHandle, MessageDialogResult,
+    file_dialog::{filters_accept, FileDialog, Filter},
+    file_handle::WasmFileHandleKind,
+    FileHandle, MessageDialogResult,
 };
+use std::path::Path;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{Element, HtmlAnchorElement, HtmlButtonElement, HtmlElement, HtmlInputElement};
@@ -30,6 +33,7 @@ pub struct WasmDialog<'a> {
     io: HtmlIoElement<'a>,
     ok_button: HtmlButtonElement,
     cancel_button: HtmlButtonElement,
+    filters: Vec<Filter>,
 
     style: Element,
 }
@@ -75,13 +79,27 @@ impl<'a> WasmDialog<'a> {
 
                 let mut accept: Vec<String> = Vec::new();
 
-                for filter in dialog.filters.iter() {
-                    accept.append(&mut filter.extensions.to_vec());
-                }
+                // A pattern that can't be expressed as an extension leaves `accept` unset,
+                // the selection is checked in `get_results` instead
+                let extensions: Option<Vec<String>> = dialog
+                    .filters
+                    .iter()
+                    .map(|filter| filter.approximate_extensions())
+                    .collect::<Option<Vec<_>>>()
+                    .map(|extensions| extensions.concat());
+
+                if let Some(mut extensions) = extensions {
+                    accept.append(&mut extensions);
 
-                accept.iter_mut().for_each(|ext| ext.insert_str(0, "."));
+                    accept.iter_mut().for_each(|ext| ext.insert_str(0, "."));
+
+                    // `accept` understands MIME types (including `image/*`) natively
+                    for filter in dialog.filters.iter() {
+                        accept.append(&mut filter.mime_types.to_vec());
+                    }
 
-                input.set_accept(&accept.join(","));
+                    input.set_accept(&accept.join(","));
+                }
 
                 card.append_child(&input).unwrap();
                 HtmlIoElement::Input(input)
@@ -134,6 +152,10 @@ impl<'a> WasmDialog<'a> {
             title,
             ok_button,
             cancel_button,
+            filters: match opt {
+                FileKind::In(dialog) => dialog.filters.clone(),
+                FileKind::Out(..) => Vec::new(),
+            },
             io,
 
             style,
@@ -274,9 +296,11 @@ impl<'a> WasmDialog<'a> {
                 let mut file_handles = Vec::new();
                 for id in 0..len {
                     let file = files.get(id).unwrap();
-                    file_handles.push(FileHandle::wrap(file));
+                    if filters_accept(&self.filters, Path::new(&file.name())) {
+                        file_handles.push(FileHandle::wrap(file));
+                    }
                 }
-                Some(file_handles)
+                (!file_handles.is_empty()).then_some(file_handles)
             } else {
                 None
             }
@@ -359,6 +383,10 @@ use crate::message_dialog::{MessageButtons, MessageDialog};
 
 impl MessageDialogImpl for MessageDialog {
     fn show(self) -> MessageDialogResult {
+        if self.timeout.is_some() {
+            log::warn!("`set_timeout` is not supported by message dialogs on WASM32, ignoring it");
+        }
+
         let text = format!("{}\n{}", self.title, self.description);
         match self.buttons {
             MessageButtons::Ok | MessageButtons::OkCustom(_) => {
@@ -398,3 +426,26 @@ impl FileHandle {
         Ok(())
     }
 }
+
+use crate::backend::LauncherImpl;
+use crate::{Error, Launcher};
+
+impl LauncherImpl for Launcher {
+    fn open_uri(self, _uri: &str) -> Result<(), Error> {
+        Err(Error::BackendUnavailable(
+            "Launcher is not supported on WASM".into(),
+        ))
+    }
+
+    fn open_file(self, _path: &Path) -> Result<(), Error> {
+        Err(Error::BackendUnavailable(
+            "Launcher is not supported on WASM".into(),
+        ))
+    }
+
+    fn show_in_folder(self, _paths: &[std::path::PathBuf]) -> Result<(), Error> {
+        Err(Error::BackendUnavailable(
+            "Launcher is not supported on WASM".into(),
+        ))
+    }
+}
@@ -1,7 +1,11 @@
 //! Windows Common Item Dialog
 //! Win32 Vista
 
+#[cfg(not(feature = "testing"))]
 mod utils;
 
+#[cfg(not(feature = "testing"))]
 mod file_dialog;
+mod launcher;
+#[cfg(not(feature = "testing"))]
 mod message_dialog;
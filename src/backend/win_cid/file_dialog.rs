@@ -138,3 +138,14 @@ impl AsyncFileSaveDialogImpl for FileDialog {
         async_thread(move || Self::save_file(self).map(FileHandle::wrap))
     }
 }
+
+//
+// Files Save
+//
+
+// Emulated with the folder picker
+use crate::backend::FilesSaveDialogImpl;
+impl FilesSaveDialogImpl for FileDialog {}
+
+use crate::backend::AsyncFilesSaveDialogImpl;
+impl AsyncFilesSaveDialogImpl for FileDialog {}
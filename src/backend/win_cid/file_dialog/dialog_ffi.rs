@@ -198,7 +198,11 @@ impl IDialog {
             let mut f_list = Vec::new();
             let mut ext_string = String::new();
 
-            for f in filters.iter() {
+            // MIME type only filters have no Windows equivalent
+            for f in filters
+                .iter()
+                .filter(|f| !f.extensions.is_empty() || !f.patterns.is_empty())
+            {
                 let name = str_to_vec_u16(&f.name);
                 ext_string.clear();
 
@@ -208,6 +212,12 @@ impl IDialog {
                     let _ = write!(&mut ext_string, "*.{ext};");
                 }
 
+                // Windows filter specs take arbitrary wildcard patterns
+                for pattern in &f.patterns {
+                    ext_string.push_str(pattern);
+                    ext_string.push(';');
+                }
+
                 // pop trailing ;
                 ext_string.pop();
 
@@ -0,0 +1,75 @@
+use std::ffi::OsStr;
+use std::iter::once;
+use std::os::windows::{ffi::OsStrExt, process::CommandExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use windows_sys::Win32::{
+    Foundation::HWND,
+    UI::{Shell::ShellExecuteW, WindowsAndMessaging::SW_SHOWNORMAL},
+};
+
+use raw_window_handle::RawWindowHandle;
+
+use crate::backend::LauncherImpl;
+use crate::{Error, Launcher};
+
+impl LauncherImpl for Launcher {
+    fn open_uri(self, uri: &str) -> Result<(), Error> {
+        shell_execute(parent(&self), "open", uri.as_ref())
+    }
+
+    fn open_file(self, path: &Path) -> Result<(), Error> {
+        // `openas` shows the "Open with" dialog
+        let verb = if self.ask { "openas" } else { "open" };
+        shell_execute(parent(&self), verb, path.as_os_str())
+    }
+
+    fn show_in_folder(self, paths: &[PathBuf]) -> Result<(), Error> {
+        let Some(path) = paths.first() else {
+            return Err(Error::InvalidOptions("No paths to show".into()));
+        };
+
+        // Explorer wants the path quoted right after the comma, which `arg` can't express.
+        // Its exit code means nothing, so only failing to start it is an error.
+        Command::new("explorer")
+            .raw_arg(format!("/select,\"{}\"", path.display()))
+            .spawn()
+            .map_err(|err| Error::Helper(format!("explorer: {err}")))?;
+        Ok(())
+    }
+}
+
+fn parent(launcher: &Launcher) -> HWND {
+    match launcher.parent {
+        Some(RawWindowHandle::Win32(handle)) => handle.hwnd.get() as _,
+        _ => std::ptr::null_mut(),
+    }
+}
+
+fn to_wide(value: &OsStr) -> Vec<u16> {
+    value.encode_wide().chain(once(0)).collect()
+}
+
+fn shell_execute(parent: HWND, verb: &str, target: &OsStr) -> Result<(), Error> {
+    let verb = to_wide(verb.as_ref());
+    let target = to_wide(target);
+
+    let res = unsafe {
+        ShellExecuteW(
+            parent,
+            verb.as_ptr(),
+            target.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+
+    // Values above 32 mean success, the others are error codes
+    let code = res as usize;
+    if code <= 32 {
+        return Err(Error::Helper(format!("ShellExecuteW failed with {code}")));
+    }
+    Ok(())
+}
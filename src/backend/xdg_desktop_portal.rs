@@ -1,350 +1,232 @@
+use std::ffi::OsStr;
+use std::future::Future;
+use std::os::fd::AsFd;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::Path;
+use std::sync::OnceLock;
 use std::{ffi::CString, os::unix::ffi::OsStrExt, path::PathBuf};
 
+// Not built with the `testing` feature, which answers file dialogs itself
+#[cfg(not(feature = "testing"))]
+mod file_dialog;
+#[cfg(not(feature = "testing"))]
+pub(crate) use file_dialog::{
+    pick_file, pick_files, pick_folder, pick_folders, save_file, save_files,
+};
+
 mod portal;
 
 mod window_identifier;
 use window_identifier::WindowIdentifier;
 
-use super::linux::zenity;
-use crate::backend::DialogFutureType;
-use crate::file_dialog::Filter;
-use crate::message_dialog::MessageDialog;
-use crate::{FileDialog, FileHandle, MessageButtons, MessageDialogResult};
+use crate::{Error, Launcher};
 
-use log::{error, warn};
-use pollster::block_on;
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
-fn async_thread<T, F>(f: F) -> DialogFutureType<Option<T>>
-where
-    F: FnOnce() -> Option<T>,
-    F: Send + 'static,
-    T: Send + 'static,
-{
-    Box::pin(async move {
-        let (tx, rx) = crate::oneshot::channel();
-
-        std::thread::spawn(move || {
-            tx.send(f()).ok();
-        });
-
-        rx.await.ok()?
-    })
-}
-
-fn to_window_identifier(
-    window: Option<RawWindowHandle>,
-    display: Option<RawDisplayHandle>,
-) -> Option<WindowIdentifier> {
-    window.map(|window| {
-        block_on(Box::pin(async move {
-            WindowIdentifier::from_raw_handle(&window, display.as_ref()).await
-        }))
-    })?
-}
+static VERSION: OnceLock<u32> = OnceLock::new();
 
-impl From<&Filter> for portal::FileFilter {
-    fn from(filter: &Filter) -> Self {
-        let name = CString::new(filter.name.as_str()).unwrap();
-
-        let globs = filter
-            .extensions
-            .iter()
-            .map(|file_extension| {
-                if file_extension == "*" || file_extension.is_empty() {
-                    c"*".to_owned()
-                } else {
-                    CString::new(format!("*.{file_extension}")).unwrap()
-                }
-            })
-            .collect();
-
-        (name, globs)
+/// Version of the FileChooser portal, fails if no portal is running.
+///
+/// Read once, older portals silently ignore options they don't know.
+pub(crate) async fn version() -> Result<u32, Error> {
+    if let Some(version) = VERSION.get() {
+        return Ok(*version);
     }
-}
 
-fn str_to_cstring(value: Option<&str>) -> Option<CString> {
-    value.and_then(|title| CString::new(title).ok())
+    let version = portal::file_chooser_version().await?;
+    log::debug!("FileChooser portal version {version}");
+    Ok(*VERSION.get_or_init(|| version))
 }
 
-fn path_to_cstring(value: Option<&Path>) -> Option<portal::FilePath> {
-    value
-        .and_then(|f| CString::new(f.as_os_str().as_bytes()).ok())
-        .map(portal::FilePath)
+/// Keeps the exported window alive for as long as the dialog is open
+fn parent_window(
+    parent: Option<RawWindowHandle>,
+    display: Option<&RawDisplayHandle>,
+) -> impl Future<Output = (Option<WindowIdentifier>, CString)> + Send {
+    // The handles are not `Send`, so they are only used before the first await
+    let identifier = parent.map(|window| WindowIdentifier::from_raw_handle(&window, display));
+
+    async move {
+        let identifier = match identifier {
+            Some(identifier) => identifier.await,
+            None => None,
+        };
+        let parent_window = identifier
+            .as_ref()
+            .and_then(|w| CString::new(w.to_string()).ok())
+            .unwrap_or_default();
+        (identifier, parent_window)
+    }
 }
 
 //
-// File Picker
+// OpenURI
 //
 
-use crate::backend::FilePickerDialogImpl;
-impl FilePickerDialogImpl for FileDialog {
-    fn pick_file(self) -> Option<PathBuf> {
-        let window_identifier = to_window_identifier(self.parent, self.parent_display);
-        let res = portal::open_file(portal::OpenFileOptions {
-            parent_window: window_identifier
-                .as_ref()
-                .and_then(|w| CString::new(w.to_string()).ok())
-                .unwrap_or_default(),
-            title: str_to_cstring(self.title.as_deref()).unwrap_or_default(),
-            multiple: Some(false),
-            filters: self.filters.iter().map(Into::into).collect(),
-            current_folder: path_to_cstring(self.starting_directory.as_deref()),
-            ..Default::default()
-        })
-        .map(portal::uris_to_paths);
-
-        if let Some(mut res) = res {
-            if res.is_empty() {
-                None
-            } else {
-                Some(res.remove(0))
-            }
-        } else {
-            warn!("Using zenity fallback");
-            match block_on(zenity::pick_file(&self)) {
-                Ok(res) => res,
-                Err(err) => {
-                    error!("Failed to pick file with zenity: {err}");
-                    None
-                }
-            }
-        }
-    }
+pub(crate) async fn open_uri(launcher: Launcher, uri: &str) -> Result<(), Error> {
+    let uri = CString::new(uri)
+        .map_err(|_| Error::InvalidOptions(format!("URI {uri:?} contains a NUL byte")))?;
+    let (_window_identifier, parent_window) =
+        parent_window(launcher.parent, launcher.parent_display.as_ref()).await;
+    let opts = portal::OpenUriOptions {
+        parent_window,
+        ask: Some(launcher.ask),
+        ..Default::default()
+    };
+
+    // `None` if the user closed the application chooser of `ask`, which is not an error
+    portal::open_uri(&uri, opts).await?;
+    Ok(())
+}
 
-    fn pick_files(self) -> Option<Vec<PathBuf>> {
-        let window_identifier = to_window_identifier(self.parent, self.parent_display);
-        let res = portal::open_file(portal::OpenFileOptions {
-            parent_window: window_identifier
-                .as_ref()
-                .and_then(|w| CString::new(w.to_string()).ok())
-                .unwrap_or_default(),
-            title: str_to_cstring(self.title.as_deref()).unwrap_or_default(),
-            multiple: Some(true),
-            filters: self.filters.iter().map(Into::into).collect(),
-            current_folder: path_to_cstring(self.starting_directory.as_deref()),
-            ..Default::default()
-        })
-        .map(portal::uris_to_paths);
-
-        if let Some(res) = res {
-            if res.is_empty() {
-                None
-            } else {
-                Some(res)
-            }
-        } else {
-            warn!("Using zenity fallback");
-            match block_on(zenity::pick_files(&self)) {
-                Ok(res) => Some(res),
-                Err(err) => {
-                    error!("Failed to pick files with zenity: {err}");
-                    None
-                }
-            }
-        }
-    }
+/// The portal gets files as a file descriptor, so it works from inside a sandbox too
+fn open_for_portal(path: &Path) -> Result<std::fs::File, Error> {
+    std::fs::File::open(path)
+        .map_err(|err| Error::InvalidOptions(format!("{}: {err}", path.display())))
 }
 
-use crate::backend::AsyncFilePickerDialogImpl;
-impl AsyncFilePickerDialogImpl for FileDialog {
-    fn pick_file_async(self) -> DialogFutureType<Option<FileHandle>> {
-        async_thread(move || Self::pick_file(self).map(FileHandle::wrap))
-    }
+pub(crate) async fn open_file(launcher: Launcher, path: &Path) -> Result<(), Error> {
+    let file = open_for_portal(path)?;
+    let (_window_identifier, parent_window) =
+        parent_window(launcher.parent, launcher.parent_display.as_ref()).await;
+    let opts = portal::OpenUriOptions {
+        parent_window,
+        ask: Some(launcher.ask),
+        ..Default::default()
+    };
+
+    // `None` if the user closed the application chooser of `ask`, which is not an error
+    portal::open_uri_file(file.as_fd(), opts).await?;
+    Ok(())
+}
 
-    fn pick_files_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
-        async_thread(move || {
-            Self::pick_files(self).map(|res| res.into_iter().map(FileHandle::wrap).collect())
-        })
-    }
+/// Show the folder containing `path` in the file manager, with `path` selected
+pub(crate) async fn open_directory(launcher: &Launcher, path: &Path) -> Result<(), Error> {
+    let file = open_for_portal(path)?;
+    let (_window_identifier, parent_window) =
+        parent_window(launcher.parent, launcher.parent_display.as_ref()).await;
+    let opts = portal::OpenUriOptions {
+        parent_window,
+        ..Default::default()
+    };
+
+    portal::open_directory(file.as_fd(), opts).await?;
+    Ok(())
 }
 
 //
-// Folder Picker
+// FileManager1
 //
 
-use crate::backend::FolderPickerDialogImpl;
-impl FolderPickerDialogImpl for FileDialog {
-    fn pick_folder(self) -> Option<PathBuf> {
-        let window_identifier = to_window_identifier(self.parent, self.parent_display);
-        let res = portal::open_file(portal::OpenFileOptions {
-            parent_window: window_identifier
-                .as_ref()
-                .and_then(|w| CString::new(w.to_string()).ok())
-                .unwrap_or_default(),
-            title: str_to_cstring(self.title.as_deref()).unwrap_or_default(),
-            multiple: Some(false),
-            directory: Some(true),
-            filters: self.filters.iter().map(Into::into).collect(),
-            current_folder: path_to_cstring(self.starting_directory.as_deref()),
-            ..Default::default()
-        })
-        .map(portal::uris_to_paths);
-
-        if let Some(mut res) = res {
-            if res.is_empty() {
-                None
-            } else {
-                Some(res.remove(0))
-            }
-        } else {
-            warn!("Using zenity fallback");
-            match block_on(zenity::pick_folder(&self)) {
-                Ok(res) => res,
-                Err(err) => {
-                    error!("Failed to pick folder with zenity: {err}");
-                    None
-                }
-            }
-        }
+pub(crate) async fn show_items(paths: &[PathBuf]) -> Result<(), Error> {
+    let uris: Vec<_> = paths.iter().map(|path| portal::path_to_uri(path)).collect();
+    portal::show_items(&uris).await
+}
+
+//
+// Documents
+//
+
+static DOCUMENT_MOUNT_POINT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Mount point of the document store, `None` without a document portal.
+///
+/// Read once, so a missing portal isn't asked for again for every path.
+async fn document_mount_point() -> Result<Option<&'static Path>, Error> {
+    if let Some(mount_point) = DOCUMENT_MOUNT_POINT.get() {
+        return Ok(mount_point.as_deref());
     }
 
-    fn pick_folders(self) -> Option<Vec<PathBuf>> {
-        let window_identifier = to_window_identifier(self.parent, self.parent_display);
-        let res = portal::open_file(portal::OpenFileOptions {
-            parent_window: window_identifier
-                .as_ref()
-                .and_then(|w| CString::new(w.to_string()).ok())
-                .unwrap_or_default(),
-            title: str_to_cstring(self.title.as_deref()).unwrap_or_default(),
-            multiple: Some(true),
-            directory: Some(true),
-            filters: self.filters.iter().map(Into::into).collect(),
-            current_folder: path_to_cstring(self.starting_directory.as_deref()),
-            ..Default::default()
-        })
-        .map(portal::uris_to_paths);
-
-        if let Some(res) = res {
-            if res.is_empty() {
-                None
-            } else {
-                Some(res)
-            }
-        } else {
-            warn!("Using zenity fallback");
-            match block_on(zenity::pick_folders(&self)) {
-                Ok(res) => Some(res),
-                Err(err) => {
-                    error!("Failed to pick folders with zenity: {err}");
-                    None
-                }
-            }
+    let mount_point = match portal::document_mount_point().await {
+        Ok(mount_point) => Some(mount_point),
+        Err(Error::BackendUnavailable(reason)) => {
+            log::debug!("Documents portal is not available: {reason}");
+            None
         }
-    }
+        Err(err) => return Err(err),
+    };
+    Ok(DOCUMENT_MOUNT_POINT.get_or_init(|| mount_point).as_deref())
 }
 
-use crate::backend::AsyncFolderPickerDialogImpl;
-impl AsyncFolderPickerDialogImpl for FileDialog {
-    fn pick_folder_async(self) -> DialogFutureType<Option<FileHandle>> {
-        async_thread(move || Self::pick_folder(self).map(FileHandle::wrap))
-    }
-
-    fn pick_folders_async(self) -> DialogFutureType<Option<Vec<FileHandle>>> {
-        async_thread(move || {
-            Self::pick_folders(self).map(|res| res.into_iter().map(FileHandle::wrap).collect())
-        })
+/// Id of the document `path` is in, along with the path inside of an exported directory
+fn document_id<'a>(mount_point: &Path, path: &'a Path) -> Option<(&'a OsStr, &'a Path)> {
+    let mut components = path.strip_prefix(mount_point).ok()?.components();
+    let mut id = components.next()?;
+    // `by-app/<app id>/<document id>` views of the store
+    if id.as_os_str() == "by-app" {
+        components.next()?;
+        id = components.next()?;
     }
+    // The document itself, a file or a directory
+    components.next()?;
+    Some((id.as_os_str(), components.as_path()))
 }
 
-//
-// File Save
-//
-
-use crate::backend::FileSaveDialogImpl;
-impl FileSaveDialogImpl for FileDialog {
-    fn save_file(self) -> Option<PathBuf> {
-        let window_identifier = to_window_identifier(self.parent, self.parent_display);
-        let res = portal::save_file(portal::SaveFileOptions {
-            parent_window: window_identifier
-                .as_ref()
-                .and_then(|w| CString::new(w.to_string()).ok())
-                .unwrap_or_default(),
-            title: str_to_cstring(self.title.as_deref()).unwrap_or_default(),
-            filters: self.filters.iter().map(Into::into).collect(),
-            current_folder: path_to_cstring(self.starting_directory.as_deref()),
-            current_name: str_to_cstring(self.file_name.as_deref()),
-            ..Default::default()
-        })
-        .map(portal::uris_to_paths);
-
-        if let Some(mut res) = res {
-            if res.is_empty() {
-                None
-            } else {
-                Some(res.remove(0))
-            }
-        } else {
-            warn!("Using zenity fallback");
-            match block_on(zenity::save_file(&self)) {
-                Ok(res) => res,
-                Err(err) => {
-                    error!("Failed to save file with zenity: {err}");
-                    None
-                }
-            }
-        }
-    }
+/// [`document_id`] of `path`, `None` for other paths
+async fn find_document(path: &Path) -> Result<Option<(&OsStr, &Path)>, Error> {
+    // Without a document portal there are no documents either
+    let mount_point = document_mount_point().await?;
+    Ok(mount_point.and_then(|mount_point| document_id(mount_point, path)))
 }
 
-use crate::backend::AsyncFileSaveDialogImpl;
-impl AsyncFileSaveDialogImpl for FileDialog {
-    fn save_file_async(self) -> DialogFutureType<Option<FileHandle>> {
-        async_thread(move || Self::save_file(self).map(FileHandle::wrap))
-    }
+/// Path on the host of a file in the document store, `None` for other paths
+pub(crate) async fn document_host_path(path: &Path) -> Result<Option<PathBuf>, Error> {
+    let Some((id, inner)) = find_document(path).await? else {
+        return Ok(None);
+    };
+
+    let id = CString::new(id.as_bytes()).unwrap();
+    let host_paths = portal::document_host_paths(std::slice::from_ref(&id)).await?;
+
+    Ok(host_paths
+        .into_iter()
+        .find(|(doc, _)| *doc == id)
+        .map(|(_, host_path)| match inner.as_os_str().is_empty() {
+            true => host_path,
+            false => host_path.join(inner),
+        }))
 }
 
-use crate::backend::MessageDialogImpl;
-impl MessageDialogImpl for MessageDialog {
-    fn show(self) -> MessageDialogResult {
-        block_on(self.show_async())
+/// Keep the document of `path` in the document store across sessions, other paths are left alone
+pub(crate) async fn make_document_persistent(path: &Path) -> Result<(), Error> {
+    if find_document(path).await?.is_none() {
+        return Ok(());
     }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.read(true);
+    // `Add` takes an `O_PATH` descriptor, there is no need to be able to read the file
+    #[cfg(target_os = "linux")]
+    options.custom_flags(libc::O_PATH);
+
+    let file = options
+        .open(path)
+        .map_err(|err| Error::InvalidOptions(format!("{}: {err}", path.display())))?;
+
+    portal::add_document(file.as_fd(), true).await?;
+    Ok(())
 }
 
-use crate::backend::AsyncMessageDialogImpl;
-impl AsyncMessageDialogImpl for MessageDialog {
-    fn show_async(self) -> DialogFutureType<MessageDialogResult> {
-        Box::pin(async move {
-            match &self.buttons {
-                MessageButtons::Ok | MessageButtons::OkCustom(_) => {
-                    let res = crate::backend::linux::zenity::message(
-                        &self.level,
-                        &self.buttons,
-                        &self.title,
-                        &self.description,
-                    )
-                    .await;
-
-                    match res {
-                        Ok(res) => res,
-                        Err(err) => {
-                            error!("Failed to open zenity dialog: {err}");
-                            MessageDialogResult::Cancel
-                        }
-                    }
-                }
-                MessageButtons::OkCancel
-                | MessageButtons::YesNo
-                | MessageButtons::OkCancelCustom(..)
-                | MessageButtons::YesNoCancel
-                | MessageButtons::YesNoCancelCustom(..) => {
-                    let res = crate::backend::linux::zenity::question(
-                        &self.buttons,
-                        &self.title,
-                        &self.description,
-                    )
-                    .await;
-
-                    match res {
-                        Ok(res) => res,
-                        Err(err) => {
-                            error!("Failed to open zenity dialog: {err}");
-                            MessageDialogResult::Cancel
-                        }
-                    }
-                }
-            }
-        })
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::path::Path;
+
+    #[test]
+    fn document_id() {
+        let mount_point = Path::new("/run/user/1000/doc");
+        let id = |path| super::document_id(mount_point, Path::new(path));
+
+        assert_eq!(
+            id("/run/user/1000/doc/a1b2c3/notes.txt"),
+            Some((OsStr::new("a1b2c3"), Path::new("")))
+        );
+        assert_eq!(
+            id("/run/user/1000/doc/by-app/org.example.App/a1b2c3/photos/2024/cat.png"),
+            Some((OsStr::new("a1b2c3"), Path::new("2024/cat.png")))
+        );
+        assert_eq!(id("/run/user/1000/doc/a1b2c3"), None);
+        assert_eq!(id("/home/user/notes.txt"), None);
     }
 }
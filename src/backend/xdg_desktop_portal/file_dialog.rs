@@ -0,0 +1,439 @@
+//! File dialogs of the FileChooser portal.
+//!
+//! Every dialog is implemented once as a future, the sync API just blocks on it. A missing portal
+//! is reported as `Error::BackendUnavailable`, so the next backend gets a chance.
+
+use std::path::{Path, PathBuf};
+use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+use super::{parent_window, portal, version};
+use crate::file_dialog::{Choice, Filter};
+use crate::{Error, FileDialog, FileResponse};
+
+use log::warn;
+
+impl From<&Filter> for portal::FileFilter {
+    fn from(filter: &Filter) -> Self {
+        // NUL bytes are rejected by `FileDialog::validate`
+        let cstring = |value: String| CString::new(value).expect("filter contains a NUL byte");
+
+        let name = cstring(filter.name.clone());
+
+        let globs = filter.extensions.iter().map(|file_extension| {
+            let glob = if file_extension == "*" || file_extension.is_empty() {
+                c"*".to_owned()
+            } else {
+                cstring(format!("*.{file_extension}"))
+            };
+            (portal::FilterKind::Glob, glob)
+        });
+
+        let patterns = filter
+            .patterns
+            .iter()
+            .map(|pattern| (portal::FilterKind::Glob, cstring(pattern.clone())));
+
+        let mime_types = filter
+            .mime_types
+            .iter()
+            .map(|mime_type| (portal::FilterKind::MimeType, cstring(mime_type.clone())));
+
+        (name, globs.chain(patterns).chain(mime_types).collect())
+    }
+}
+
+impl From<&Choice> for portal::Choice {
+    fn from(choice: &Choice) -> Self {
+        // NUL bytes are rejected by `FileDialog::validate`
+        let cstring = |value: &str| CString::new(value).expect("choice contains a NUL byte");
+
+        (
+            cstring(&choice.id),
+            cstring(&choice.label),
+            choice
+                .options
+                .iter()
+                .map(|(id, label)| (cstring(id), cstring(label)))
+                .collect(),
+            cstring(&choice.default),
+        )
+    }
+}
+
+fn response_choices(choices: Vec<(CString, CString)>) -> Vec<(String, String)> {
+    choices
+        .into_iter()
+        .map(|(id, value)| {
+            (
+                id.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+fn current_filter(dialog: &FileDialog) -> Option<portal::FileFilter> {
+    dialog
+        .current_filter
+        .and_then(|id| dialog.filters.get(id))
+        .map(Into::into)
+}
+
+/// Map the filter reported by the portal back to its index in `FileDialog::filters`
+fn response_filter(dialog: &FileDialog, filter: Option<portal::FileFilter>) -> Option<usize> {
+    let filter = filter?;
+    let filters: Vec<portal::FileFilter> = dialog.filters.iter().map(Into::into).collect();
+
+    filters
+        .iter()
+        .position(|f| *f == filter)
+        // Some portal implementations don't echo the globs back verbatim
+        .or_else(|| filters.iter().position(|(name, _)| *name == filter.0))
+}
+
+fn str_to_cstring(value: Option<&str>) -> Option<CString> {
+    value.and_then(|title| CString::new(title).ok())
+}
+
+fn path_to_cstring(value: Option<&Path>) -> Option<portal::FilePath> {
+    value
+        .and_then(|f| CString::new(f.as_os_str().as_bytes()).ok())
+        .map(portal::FilePath)
+}
+
+/// FileChooser version that added the `directory` option of `OpenFile`
+const DIRECTORY_VERSION: u32 = 3;
+/// FileChooser version that added the `current_folder` option of `OpenFile`
+const OPEN_CURRENT_FOLDER_VERSION: u32 = 4;
+
+/// Portals without `directory` would let the user pick a file, so another backend has to do it
+async fn folder_picker_version() -> Result<u32, Error> {
+    let version = version().await?;
+    if version < DIRECTORY_VERSION {
+        return Err(Error::BackendUnavailable(format!(
+            "FileChooser portal version {version} can't pick folders"
+        )));
+    }
+    Ok(version)
+}
+
+/// `current_folder` of `OpenFile`, skipped if the portal is too old to know it
+fn open_current_folder(dialog: &FileDialog, version: u32) -> Option<portal::FilePath> {
+    let folder = path_to_cstring(dialog.starting_directory.as_deref())?;
+    if version < OPEN_CURRENT_FOLDER_VERSION {
+        warn!("FileChooser portal version {version} ignores the starting directory");
+        return None;
+    }
+    Some(folder)
+}
+
+pub(crate) async fn pick_file(dialog: FileDialog) -> Result<Option<FileResponse<PathBuf>>, Error> {
+    let version = version().await?;
+    let (_window_identifier, parent_window) =
+        parent_window(dialog.parent, dialog.parent_display.as_ref()).await;
+    let res = portal::open_file(portal::OpenFileOptions {
+        parent_window,
+        title: str_to_cstring(dialog.title.as_deref()).unwrap_or_default(),
+        accept_label: str_to_cstring(dialog.accept_label.as_deref()),
+        multiple: Some(false),
+        filters: dialog.filters.iter().map(Into::into).collect(),
+        current_filter: current_filter(&dialog),
+        choices: dialog.choices.iter().map(Into::into).collect(),
+        current_folder: open_current_folder(&dialog, version),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(res.and_then(|res| {
+        let filter = response_filter(&dialog, res.current_filter);
+        let choices = response_choices(res.choices);
+        let path = portal::uris_to_paths(res.uris).into_iter().next()?;
+        Some(FileResponse {
+            selection: path,
+            filter,
+            choices,
+        })
+    }))
+}
+
+pub(crate) async fn pick_files(
+    dialog: FileDialog,
+) -> Result<Option<FileResponse<Vec<PathBuf>>>, Error> {
+    let version = version().await?;
+    let (_window_identifier, parent_window) =
+        parent_window(dialog.parent, dialog.parent_display.as_ref()).await;
+    let res = portal::open_file(portal::OpenFileOptions {
+        parent_window,
+        title: str_to_cstring(dialog.title.as_deref()).unwrap_or_default(),
+        accept_label: str_to_cstring(dialog.accept_label.as_deref()),
+        multiple: Some(true),
+        filters: dialog.filters.iter().map(Into::into).collect(),
+        current_filter: current_filter(&dialog),
+        choices: dialog.choices.iter().map(Into::into).collect(),
+        current_folder: open_current_folder(&dialog, version),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(res.and_then(|res| {
+        let filter = response_filter(&dialog, res.current_filter);
+        let choices = response_choices(res.choices);
+        let paths = portal::uris_to_paths(res.uris);
+        (!paths.is_empty()).then_some(FileResponse {
+            selection: paths,
+            filter,
+            choices,
+        })
+    }))
+}
+
+pub(crate) async fn pick_folder(dialog: FileDialog) -> Result<Option<PathBuf>, Error> {
+    let version = folder_picker_version().await?;
+    let (_window_identifier, parent_window) =
+        parent_window(dialog.parent, dialog.parent_display.as_ref()).await;
+    let res = portal::open_file(portal::OpenFileOptions {
+        parent_window,
+        title: str_to_cstring(dialog.title.as_deref()).unwrap_or_default(),
+        accept_label: str_to_cstring(dialog.accept_label.as_deref()),
+        multiple: Some(false),
+        directory: Some(true),
+        filters: dialog.filters.iter().map(Into::into).collect(),
+        current_folder: open_current_folder(&dialog, version),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(res.and_then(|res| portal::uris_to_paths(res.uris).into_iter().next()))
+}
+
+pub(crate) async fn pick_folders(dialog: FileDialog) -> Result<Option<Vec<PathBuf>>, Error> {
+    let version = folder_picker_version().await?;
+    let (_window_identifier, parent_window) =
+        parent_window(dialog.parent, dialog.parent_display.as_ref()).await;
+    let res = portal::open_file(portal::OpenFileOptions {
+        parent_window,
+        title: str_to_cstring(dialog.title.as_deref()).unwrap_or_default(),
+        accept_label: str_to_cstring(dialog.accept_label.as_deref()),
+        multiple: Some(true),
+        directory: Some(true),
+        filters: dialog.filters.iter().map(Into::into).collect(),
+        current_folder: open_current_folder(&dialog, version),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(res
+        .map(|res| portal::uris_to_paths(res.uris))
+        .filter(|paths| !paths.is_empty()))
+}
+
+pub(crate) async fn save_file(dialog: FileDialog) -> Result<Option<FileResponse<PathBuf>>, Error> {
+    let (_window_identifier, parent_window) =
+        parent_window(dialog.parent, dialog.parent_display.as_ref()).await;
+    let res = portal::save_file(portal::SaveFileOptions {
+        parent_window,
+        title: str_to_cstring(dialog.title.as_deref()).unwrap_or_default(),
+        accept_label: str_to_cstring(dialog.accept_label.as_deref()),
+        filters: dialog.filters.iter().map(Into::into).collect(),
+        current_filter: current_filter(&dialog),
+        choices: dialog.choices.iter().map(Into::into).collect(),
+        // `current_file` already covers the folder and the name
+        current_folder: path_to_cstring(dialog.starting_directory.as_deref())
+            .filter(|_| dialog.existing_file.is_none()),
+        current_name: str_to_cstring(dialog.file_name.as_deref())
+            .filter(|_| dialog.existing_file.is_none()),
+        current_file: path_to_cstring(dialog.existing_file.as_deref()),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(res.and_then(|res| {
+        let filter = response_filter(&dialog, res.current_filter);
+        let choices = response_choices(res.choices);
+        let path = portal::uris_to_paths(res.uris).into_iter().next()?;
+        Some(FileResponse {
+            selection: path,
+            filter,
+            choices,
+        })
+    }))
+}
+
+pub(crate) async fn save_files(
+    dialog: FileDialog,
+    file_names: Vec<String>,
+) -> Result<Option<Vec<PathBuf>>, Error> {
+    let (_window_identifier, parent_window) =
+        parent_window(dialog.parent, dialog.parent_display.as_ref()).await;
+    let res = portal::save_files(portal::SaveFilesOptions {
+        parent_window,
+        title: str_to_cstring(dialog.title.as_deref()).unwrap_or_default(),
+        accept_label: str_to_cstring(dialog.accept_label.as_deref()),
+        choices: dialog.choices.iter().map(Into::into).collect(),
+        current_folder: path_to_cstring(dialog.starting_directory.as_deref()),
+        files: file_names
+            .iter()
+            .filter_map(|name| path_to_cstring(Some(Path::new(name))))
+            .collect(),
+        ..Default::default()
+    })
+    .await;
+
+    match res {
+        Ok(res) => Ok(res
+            .map(|res| portal::uris_to_paths(res.uris))
+            .filter(|paths| !paths.is_empty())),
+        Err(Error::BackendUnavailable(err)) => {
+            // Portal is missing, or too old to know `SaveFiles`
+            warn!("Using folder picker fallback: {err}");
+            let folder = pick_folder(dialog).await?;
+            Ok(folder.map(|folder| crate::backend::paths_in_folder(&folder, &file_names)))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::portal::mock::{MockPortal, Reply, Value};
+    use crate::{Error, FileDialog};
+
+    fn bytes(path: &str) -> Value {
+        Value::Bytes([path.as_bytes(), b"\0"].concat())
+    }
+
+    #[test]
+    fn open_current_folder() {
+        let dialog = FileDialog::new().set_directory("/home/user");
+        assert!(super::open_current_folder(&dialog, 3).is_none());
+        assert!(super::open_current_folder(&dialog, 4).is_some());
+        assert!(super::open_current_folder(&FileDialog::new(), 4).is_none());
+    }
+
+    #[test]
+    fn pick_file_options() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+        portal.reply(Reply::Response {
+            code: 0,
+            uris: vec!["file:///tmp/b.rs".into()],
+            current_filter: Some(("Rust".into(), "*.rs".into())),
+        });
+
+        let dialog = FileDialog::new()
+            .set_title("Pick one")
+            .set_accept_label("Choose")
+            .set_directory("/tmp")
+            .add_filter("Text", &["txt"])
+            .add_filter("Rust", &["rs"]);
+        let res = pollster::block_on(super::pick_file(dialog))
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.selection, Path::new("/tmp/b.rs"));
+        assert_eq!(res.filter, Some(1));
+
+        let call = &portal.calls()[0];
+        assert_eq!(call.method, "FileChooser.OpenFile");
+        assert_eq!(call.args[1], Value::Str("Pick one".into()));
+        assert_eq!(
+            call.option("accept_label"),
+            Some(&Value::Str("Choose".into()))
+        );
+        assert_eq!(call.option("multiple"), Some(&Value::Bool(false)));
+        assert_eq!(call.option("current_folder"), Some(&bytes("/tmp")));
+
+        let filter = |name: &str, glob: &str| {
+            Value::Struct(vec![
+                Value::Str(name.into()),
+                Value::Array(vec![Value::Struct(vec![
+                    Value::U32(0),
+                    Value::Str(glob.into()),
+                ])]),
+            ])
+        };
+        assert_eq!(
+            call.option("filters"),
+            Some(&Value::Array(vec![
+                filter("Text", "*.txt"),
+                filter("Rust", "*.rs")
+            ]))
+        );
+    }
+
+    #[test]
+    fn save_file_options() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+        portal.reply(Reply::uris(&["file:///tmp/a.txt"]));
+        portal.reply(Reply::cancelled());
+
+        let dialog = FileDialog::new()
+            .set_directory("/tmp")
+            .set_file_name("a.txt");
+        let res = pollster::block_on(super::save_file(dialog))
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.selection, Path::new("/tmp/a.txt"));
+
+        let dialog = FileDialog::new().set_existing_file("/tmp/b.txt");
+        assert!(pollster::block_on(super::save_file(dialog))
+            .unwrap()
+            .is_none());
+
+        let calls = portal.calls();
+        assert_eq!(calls[0].method, "FileChooser.SaveFile");
+        assert_eq!(calls[0].option("current_folder"), Some(&bytes("/tmp")));
+        assert_eq!(
+            calls[0].option("current_name"),
+            Some(&Value::Str("a.txt".into()))
+        );
+        assert_eq!(calls[0].option("current_file"), None);
+
+        // `current_file` covers the folder and the name
+        assert_eq!(calls[1].option("current_file"), Some(&bytes("/tmp/b.txt")));
+        assert_eq!(calls[1].option("current_folder"), None);
+        assert_eq!(calls[1].option("current_name"), None);
+    }
+
+    #[test]
+    fn save_files_fallback() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+        let save_files = || {
+            pollster::block_on(super::save_files(
+                FileDialog::new(),
+                vec!["a.txt".into(), "b.txt".into()],
+            ))
+        };
+
+        portal.reply(Reply::Error(
+            "org.freedesktop.portal.Error.NotAllowed".into(),
+        ));
+        assert!(matches!(
+            save_files(),
+            Err(Error::Portal { name, .. }) if name == "org.freedesktop.portal.Error.NotAllowed"
+        ));
+        assert_eq!(portal.calls().len(), 1);
+
+        // Too old to know `SaveFiles`, a folder is picked instead
+        portal.reply(Reply::Error(
+            "org.freedesktop.DBus.Error.UnknownMethod".into(),
+        ));
+        portal.reply(Reply::uris(&["file:///tmp"]));
+        assert_eq!(
+            save_files().unwrap(),
+            Some(vec!["/tmp/a.txt".into(), "/tmp/b.txt".into()])
+        );
+
+        let calls = portal.calls();
+        assert_eq!(calls[0].method, "FileChooser.SaveFiles");
+        assert_eq!(calls[1].method, "FileChooser.SaveFiles");
+        assert_eq!(calls[2].method, "FileChooser.OpenFile");
+        assert_eq!(calls[2].option("directory"), Some(&Value::Bool(true)));
+    }
+}
@@ -0,0 +1,49 @@
+use std::{
+    ffi::CString,
+    os::fd::{AsRawFd, BorrowedFd},
+};
+
+use super::{ffi, Message, MessageIter};
+
+fn documents_call(method: &std::ffi::CStr) -> Message {
+    Message::new_method_call(
+        c"org.freedesktop.portal.Documents",
+        c"/org/freedesktop/portal/documents",
+        c"org.freedesktop.portal.Documents",
+        method,
+    )
+    .unwrap()
+}
+
+impl Message {
+    /// `Documents.GetMountPoint`, where the document store is mounted
+    pub fn document_mount_point() -> Self {
+        documents_call(c"GetMountPoint")
+    }
+
+    /// `Documents.GetHostPaths`, added in version 5
+    pub fn document_host_paths(doc_ids: &[CString]) -> Self {
+        let mut msg = documents_call(c"GetHostPaths");
+
+        let mut iter = MessageIter::init_append(&mut msg);
+        iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"s"), |array| {
+            for id in doc_ids {
+                array.append_string(id);
+            }
+        });
+
+        msg
+    }
+
+    /// `Documents.Add`, returns the id of the existing document if `reuse_existing` is set
+    pub fn add_document(file: BorrowedFd, reuse_existing: bool, persistent: bool) -> Self {
+        let mut msg = documents_call(c"Add");
+
+        let mut iter = MessageIter::init_append(&mut msg);
+        iter.append_fd(file.as_raw_fd());
+        iter.append_bool(reuse_existing);
+        iter.append_bool(persistent);
+
+        msg
+    }
+}
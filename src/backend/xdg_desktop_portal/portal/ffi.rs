@@ -38,6 +38,12 @@ pub const DBUS_TYPE_STRUCT: c_int = 'r' as c_int;
 pub const DBUS_TYPE_OBJECT_PATH: c_int = 'o' as c_int;
 pub const DBUS_TYPE_SIGNATURE: c_int = 'g' as c_int;
 
+pub const DBUS_MESSAGE_TYPE_INVALID: c_int = 0;
+pub const DBUS_MESSAGE_TYPE_METHOD_CALL: c_int = 1;
+pub const DBUS_MESSAGE_TYPE_METHOD_RETURN: c_int = 2;
+pub const DBUS_MESSAGE_TYPE_ERROR: c_int = 3;
+pub const DBUS_MESSAGE_TYPE_SIGNAL: c_int = 4;
+
 #[repr(C)]
 pub struct DBusError {
     pub name: *const c_char,
@@ -76,11 +82,14 @@ pub struct Libdbus {
     pub dbus_bus_get_private:
         unsafe extern "C" fn(DBusBusType, *mut DBusError) -> *mut DBusConnection,
     pub dbus_bus_get_unique_name: unsafe extern "C" fn(*mut DBusConnection) -> *const c_char,
+    pub dbus_bus_register: unsafe extern "C" fn(*mut DBusConnection, *mut DBusError) -> u32,
 
     pub dbus_bus_add_match:
         unsafe extern "C" fn(*mut DBusConnection, *const c_char, *mut DBusError),
 
     // Connection Management
+    pub dbus_connection_open_private:
+        unsafe extern "C" fn(*const c_char, *mut DBusError) -> *mut DBusConnection,
     pub dbus_connection_close: unsafe extern "C" fn(*mut DBusConnection),
     pub dbus_connection_flush: unsafe extern "C" fn(*mut DBusConnection),
     pub dbus_connection_send_with_reply_and_block: unsafe extern "C" fn(
@@ -91,6 +100,9 @@ pub struct Libdbus {
     ) -> *mut DBusMessage,
     pub dbus_connection_read_write: unsafe extern "C" fn(*mut DBusConnection, c_int) -> u32,
     pub dbus_connection_pop_message: unsafe extern "C" fn(*mut DBusConnection) -> *mut DBusMessage,
+    pub dbus_connection_send:
+        unsafe extern "C" fn(*mut DBusConnection, *mut DBusMessage, *mut u32) -> u32,
+    pub dbus_connection_get_unix_fd: unsafe extern "C" fn(*mut DBusConnection, *mut c_int) -> u32,
 
     // Errors
     pub dbus_error_init: unsafe extern "C" fn(*mut DBusError),
@@ -107,6 +119,9 @@ pub struct Libdbus {
     pub dbus_message_is_signal:
         unsafe extern "C" fn(*mut DBusMessage, *const c_char, *const c_char) -> u32,
     pub dbus_message_get_path: unsafe extern "C" fn(*mut DBusMessage) -> *const c_char,
+    pub dbus_message_get_type: unsafe extern "C" fn(*mut DBusMessage) -> c_int,
+    pub dbus_message_get_reply_serial: unsafe extern "C" fn(*mut DBusMessage) -> u32,
+    pub dbus_message_get_error_name: unsafe extern "C" fn(*mut DBusMessage) -> *const c_char,
 
     // Message Iterators
     pub dbus_message_iter_append_basic:
@@ -162,13 +177,17 @@ impl Libdbus {
                 // Bus Connections
                 dbus_bus_get_private,
                 dbus_bus_get_unique_name,
+                dbus_bus_register,
                 dbus_bus_add_match,
                 // Connection Management
+                dbus_connection_open_private,
                 dbus_connection_close,
                 dbus_connection_flush,
                 dbus_connection_send_with_reply_and_block,
                 dbus_connection_read_write,
                 dbus_connection_pop_message,
+                dbus_connection_send,
+                dbus_connection_get_unix_fd,
                 // Errors
                 dbus_error_init,
                 dbus_error_free,
@@ -177,6 +196,9 @@ impl Libdbus {
                 dbus_message_unref,
                 dbus_message_is_signal,
                 dbus_message_get_path,
+                dbus_message_get_type,
+                dbus_message_get_reply_serial,
+                dbus_message_get_error_name,
                 // Message Iterators
                 dbus_message_iter_append_basic,
                 dbus_message_iter_init,
@@ -1,28 +1,39 @@
+//! FileChooser portal, not built with the `testing` feature which answers file dialogs itself
+
 use std::{
-    ffi::{CStr, CString},
-    time::{SystemTime, UNIX_EPOCH},
+    ffi::{CStr, CString, OsStr},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
 };
 
 use super::{
-    ffi,
-    libdbus::{Message, MessageIter},
+    failed, ffi, generate_response_path, request, response_code, Connection, HandleToken, Message,
+    MessageIter,
 };
+use crate::Error;
 
-#[derive(Debug)]
-pub struct HandleToken(pub CString);
+/// Kind of a filter entry, as defined by the FileChooser portal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    Glob = 0,
+    MimeType = 1,
+}
 
-impl Default for HandleToken {
-    fn default() -> Self {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        let token = format!("rfd_{now}");
-        Self(CString::new(token).unwrap())
+impl FilterKind {
+    pub fn from_u32(kind: u32) -> Option<Self> {
+        match kind {
+            0 => Some(Self::Glob),
+            1 => Some(Self::MimeType),
+            _ => None,
+        }
     }
 }
 
-pub type FileFilter = (CString, Vec<CString>);
+/// `(label, [(kind, glob or mime type)])`
+pub type FileFilter = (CString, Vec<(FilterKind, CString)>);
+
+/// `(id, label, [(option id, option label)], default option id)`
+pub type Choice = (CString, CString, Vec<(CString, CString)>, CString);
 
 #[derive(Debug)]
 pub struct FilePath(pub CString);
@@ -37,32 +48,68 @@ pub struct OpenFileOptions {
     pub multiple: Option<bool>,
     pub directory: Option<bool>,
     pub filters: Vec<FileFilter>,
-    // TODO: Serialize this if needed
-    #[allow(unused)]
     pub current_filter: Option<FileFilter>,
     pub current_folder: Option<FilePath>,
+    pub choices: Vec<Choice>,
+}
+
+fn append_filter(iter: &mut MessageIter, (label, entries): &FileFilter) {
+    iter.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+        s.append_string(label);
+        s.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(us)"), |array| {
+            for (kind, entry) in entries {
+                array.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+                    s.append_u32(*kind as u32);
+                    s.append_string(entry);
+                })
+            }
+        })
+    });
 }
 
 fn append_filters(dict: &mut MessageIter, filters: Vec<FileFilter>) {
     let filters: Vec<_> = filters
         .into_iter()
-        .filter(|(_, globs)| !globs.is_empty())
+        .filter(|(_, entries)| !entries.is_empty())
         .collect();
 
     if !filters.is_empty() {
         dict.with_dict_entry(c"filters", c"a(sa(us))", |variant| {
             variant.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(sa(us))"), |array| {
-                for (label, globs) in filters.iter() {
+                for filter in filters.iter() {
+                    append_filter(array, filter);
+                }
+            });
+        });
+    }
+}
+
+fn append_current_filter(dict: &mut MessageIter, filter: &FileFilter) {
+    // An empty filter is not a valid value, portal would reject the whole request
+    if !filter.1.is_empty() {
+        dict.with_dict_entry(c"current_filter", c"(sa(us))", |variant| {
+            append_filter(variant, filter);
+        });
+    }
+}
+
+fn append_choices(dict: &mut MessageIter, choices: Vec<Choice>) {
+    if !choices.is_empty() {
+        dict.with_dict_entry(c"choices", c"a(ssa(ss)s)", |variant| {
+            variant.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(ssa(ss)s)"), |array| {
+                for (id, label, options, default) in choices.iter() {
                     array.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+                        s.append_string(id);
                         s.append_string(label);
-                        s.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(us)"), |array| {
-                            for glob in globs {
+                        s.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(ss)"), |array| {
+                            for (id, label) in options {
                                 array.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
-                                    s.append_u32(0); // Glob type
-                                    s.append_string(glob);
+                                    s.append_string(id);
+                                    s.append_string(label);
                                 })
                             }
-                        })
+                        });
+                        s.append_string(default);
                     });
                 }
             });
@@ -127,6 +174,12 @@ impl Message {
             }
 
             append_filters(dict, opts.filters);
+
+            if let Some(current_filter) = opts.current_filter.as_ref() {
+                append_current_filter(dict, current_filter);
+            }
+
+            append_choices(dict, opts.choices);
         });
 
         msg
@@ -144,9 +197,8 @@ pub struct SaveFileOptions {
     pub current_folder: Option<FilePath>,
     pub current_file: Option<FilePath>,
     pub filters: Vec<FileFilter>,
-    // TODO: Serialize this if needed
-    #[allow(unused)]
     pub current_filter: Option<FileFilter>,
+    pub choices: Vec<Choice>,
 }
 
 impl Message {
@@ -196,8 +248,239 @@ impl Message {
             }
 
             append_filters(dict, opts.filters);
+
+            if let Some(current_filter) = opts.current_filter.as_ref() {
+                append_current_filter(dict, current_filter);
+            }
+
+            append_choices(dict, opts.choices);
+        });
+
+        msg
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SaveFilesOptions {
+    pub parent_window: CString,
+    pub title: CString,
+    pub handle_token: HandleToken,
+    pub accept_label: Option<CString>,
+    pub modal: Option<bool>,
+    pub current_folder: Option<FilePath>,
+    pub files: Vec<FilePath>,
+    pub choices: Vec<Choice>,
+}
+
+impl Message {
+    pub fn save_files(opts: SaveFilesOptions) -> Self {
+        let mut msg = Message::new_method_call(
+            c"org.freedesktop.portal.Desktop",
+            c"/org/freedesktop/portal/desktop",
+            c"org.freedesktop.portal.FileChooser",
+            c"SaveFiles",
+        )
+        .unwrap();
+
+        let mut iter = MessageIter::init_append(&mut msg);
+
+        iter.append_string(&opts.parent_window);
+        iter.append_string(&opts.title);
+
+        iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"{sv}"), |dict| {
+            dict.with_dict_entry(c"handle_token", c"s", |variant| {
+                variant.append_string(&opts.handle_token.0);
+            });
+
+            if let Some(accept_label) = opts.accept_label.as_ref() {
+                dict.with_dict_entry(c"accept_label", c"s", |variant| {
+                    variant.append_string(accept_label);
+                });
+            }
+
+            if let Some(modal) = opts.modal {
+                dict.with_dict_entry(c"modal", c"b", |variant| {
+                    variant.append_bool(modal);
+                });
+            }
+
+            if let Some(current_folder) = opts.current_folder {
+                append_path(dict, c"current_folder", &current_folder);
+            }
+
+            dict.with_dict_entry(c"files", c"aay", |variant| {
+                variant.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"ay"), |files| {
+                    for file in opts.files.iter() {
+                        files.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"y"), |array| {
+                            for byte in file.0.as_bytes_with_nul() {
+                                array.append_byte(*byte);
+                            }
+                        });
+                    }
+                });
+            });
+
+            append_choices(dict, opts.choices);
         });
 
         msg
     }
 }
+
+/// Results of a `org.freedesktop.portal.Request::Response` signal
+#[derive(Debug, Default)]
+pub struct Response {
+    pub uris: Vec<CString>,
+    pub current_filter: Option<FileFilter>,
+    /// `(choice id, selected option id)`
+    pub choices: Vec<(CString, CString)>,
+}
+
+pub fn uris_to_paths(uris: Vec<CString>) -> Vec<PathBuf> {
+    uris.into_iter()
+        .filter_map(|uri| {
+            let bytes: Vec<u8> = percent_encoding::percent_decode(uri.as_bytes()).collect();
+            let Some(path) = bytes.strip_prefix(b"file://") else {
+                log::error!("Ignoring uri: {bytes:?} lacks `file://`");
+                return None;
+            };
+            Some(PathBuf::from(OsStr::from_bytes(path)))
+        })
+        .collect()
+}
+
+pub async fn open_file(opts: OpenFileOptions) -> Result<Option<Response>, Error> {
+    let mut conn = Connection::new().await?;
+    let handle_path = generate_response_path(&mut conn, &opts.handle_token);
+    request(conn, handle_path, Message::open_file(opts), parse_response).await
+}
+
+pub async fn save_file(opts: SaveFileOptions) -> Result<Option<Response>, Error> {
+    let mut conn = Connection::new().await?;
+    let handle_path = generate_response_path(&mut conn, &opts.handle_token);
+    request(conn, handle_path, Message::save_file(opts), parse_response).await
+}
+
+pub async fn save_files(opts: SaveFilesOptions) -> Result<Option<Response>, Error> {
+    let mut conn = Connection::new().await?;
+    let handle_path = generate_response_path(&mut conn, &opts.handle_token);
+    request(conn, handle_path, Message::save_files(opts), parse_response).await
+}
+
+fn parse_response(msg: &Message) -> Result<Option<Response>, Error> {
+    let mut iter = MessageIter::from_msg(msg);
+
+    if !response_code(&mut iter)? {
+        return Ok(None);
+    }
+
+    if !iter.next() {
+        return Err(failed("Body of the response is empty"));
+    }
+
+    if iter.get_arg_type() != ffi::DBUS_TYPE_ARRAY {
+        return Err(failed("Body of the response is not an array"));
+    }
+
+    let mut dict_iter = iter.iter_recurse();
+
+    let mut uris = None;
+    let mut current_filter = None;
+    let mut choices = Vec::new();
+
+    while dict_iter.get_arg_type() == ffi::DBUS_TYPE_DICT_ENTRY {
+        let mut entry_iter = dict_iter.iter_recurse();
+
+        let Some(key) = entry_iter.get_string() else {
+            log::error!("Wrong type of a dict key");
+            dict_iter.next();
+            continue;
+        };
+
+        entry_iter.next();
+        if entry_iter.get_arg_type() != ffi::DBUS_TYPE_VARIANT {
+            log::error!(
+                "Response.{key:?} type {} != VARIANT",
+                entry_iter.get_arg_type()
+            );
+            dict_iter.next();
+            continue;
+        }
+
+        let mut var_iter = entry_iter.iter_recurse();
+        match key.as_bytes() {
+            b"uris" => uris = Some(var_iter.get_string_array()),
+            b"current_filter" => current_filter = parse_filter(&mut var_iter),
+            b"choices" => choices = parse_choices(&mut var_iter),
+            _ => {}
+        }
+
+        dict_iter.next();
+    }
+
+    let Some(uris) = uris else {
+        return Err(failed("Response.uris was not found"));
+    };
+
+    Ok(Some(Response {
+        uris,
+        current_filter,
+        choices,
+    }))
+}
+
+fn parse_choices(iter: &mut MessageIter) -> Vec<(CString, CString)> {
+    let mut out = Vec::new();
+
+    if iter.get_arg_type() != ffi::DBUS_TYPE_ARRAY {
+        log::error!("Response.choices is not an array");
+        return out;
+    }
+
+    let mut array_iter = iter.iter_recurse();
+    while array_iter.get_arg_type() == ffi::DBUS_TYPE_STRUCT {
+        let mut choice_iter = array_iter.iter_recurse();
+        let id = choice_iter.get_string();
+        choice_iter.next();
+        let value = choice_iter.get_string();
+
+        if let (Some(id), Some(value)) = (id, value) {
+            out.push((id, value));
+        } else {
+            log::error!("Wrong type in Response.choices");
+        }
+        array_iter.next();
+    }
+
+    out
+}
+
+fn parse_filter(iter: &mut MessageIter) -> Option<FileFilter> {
+    if iter.get_arg_type() != ffi::DBUS_TYPE_STRUCT {
+        log::error!("Response.current_filter is not a struct");
+        return None;
+    }
+
+    let mut struct_iter = iter.iter_recurse();
+    let name = struct_iter.get_string()?;
+    struct_iter.next();
+
+    if struct_iter.get_arg_type() != ffi::DBUS_TYPE_ARRAY {
+        log::error!("Response.current_filter globs are not an array");
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut array_iter = struct_iter.iter_recurse();
+    while array_iter.get_arg_type() == ffi::DBUS_TYPE_STRUCT {
+        let mut entry_iter = array_iter.iter_recurse();
+        let kind = entry_iter.get_u32().and_then(FilterKind::from_u32);
+        entry_iter.next();
+        if let (Some(kind), Some(entry)) = (kind, entry_iter.get_string()) {
+            entries.push((kind, entry));
+        }
+        array_iter.next();
+    }
+
+    Some((name, entries))
+}
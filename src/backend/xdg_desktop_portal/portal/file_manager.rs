@@ -0,0 +1,28 @@
+use std::ffi::CString;
+
+use super::{ffi, Message, MessageIter};
+
+impl Message {
+    /// `org.freedesktop.FileManager1.ShowItems`, implemented by most file managers
+    pub fn show_items(uris: &[CString]) -> Self {
+        let mut msg = Message::new_method_call(
+            c"org.freedesktop.FileManager1",
+            c"/org/freedesktop/FileManager1",
+            c"org.freedesktop.FileManager1",
+            c"ShowItems",
+        )
+        .unwrap();
+
+        let mut iter = MessageIter::init_append(&mut msg);
+
+        iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"s"), |array| {
+            for uri in uris {
+                array.append_string(uri);
+            }
+        });
+        // Startup notification id, none
+        iter.append_string(c"");
+
+        msg
+    }
+}
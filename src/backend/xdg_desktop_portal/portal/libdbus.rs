@@ -5,8 +5,10 @@ use std::{
     ptr::NonNull,
 };
 
-use super::ffi;
+use super::{bus_address, disconnected, ffi, reactor, reply_error};
+use crate::Error;
 use libc::c_int;
+use std::os::fd::RawFd;
 
 macro_rules! f {
     ($f: ident) => {
@@ -68,40 +70,46 @@ impl Drop for DbusError {
 
 pub struct Connection {
     conn: NonNull<ffi::DBusConnection>,
-    err: DbusError,
 }
 
 impl Connection {
-    pub fn new() -> Option<Self> {
+    /// `async` like the pure Rust client, but the setup is synchronous: libdbus blocks the
+    /// executor while it connects and waits for the bus to answer `Hello`. Only the requests made
+    /// afterwards wait for the socket through the reactor.
+    pub async fn new() -> Result<Self, Error> {
         if ffi::Libdbus::open_libdbus().is_none() {
-            log::error!("Can't connect to a portal: libdbus-1.so not found");
-            return None;
+            return Err(Error::BackendUnavailable(
+                "libdbus-1.so not found".to_owned(),
+            ));
         }
 
         let mut err = DbusError::new();
         unsafe {
-            let ptr = f!(dbus_bus_get_private)(ffi::DBusBusType::Session, err.as_ptr());
+            let ptr = match bus_address() {
+                Some(address) => {
+                    let address = CString::new(address).unwrap();
+                    let ptr = f!(dbus_connection_open_private)(address.as_ptr(), err.as_ptr());
+                    if !ptr.is_null() && f!(dbus_bus_register)(ptr, err.as_ptr()) == 0 {
+                        f!(dbus_connection_close)(ptr);
+                    }
+                    ptr
+                }
+                None => f!(dbus_bus_get_private)(ffi::DBusBusType::Session, err.as_ptr()),
+            };
 
             if err.is_err() {
-                log::error!("Failed to connect to session bus: {err}");
-                return None;
+                let message = err.message().map(CStr::to_string_lossy).unwrap_or_default();
+                return Err(Error::BackendUnavailable(format!(
+                    "Failed to connect to the session bus: {message}"
+                )));
             }
 
-            Some(Self {
-                conn: NonNull::new(ptr)?,
-                err,
-            })
+            NonNull::new(ptr)
+                .map(|conn| Self { conn })
+                .ok_or_else(disconnected)
         }
     }
 
-    pub fn err(&mut self) -> &mut DbusError {
-        &mut self.err
-    }
-
-    pub fn err_ptr(&mut self) -> &mut ffi::DBusError {
-        self.err.as_ptr()
-    }
-
     pub fn as_ptr(&self) -> *mut ffi::DBusConnection {
         self.conn.as_ptr()
     }
@@ -110,40 +118,98 @@ impl Connection {
         unsafe { CStr::from_ptr(f!(dbus_bus_get_unique_name)(self.as_ptr())).to_owned() }
     }
 
+    /// Add a match rule without waiting for the bus to confirm it
     pub fn add_match(&mut self, match_rule: &CStr) {
         unsafe {
-            f!(dbus_bus_add_match)(self.as_ptr(), match_rule.as_ptr(), self.err_ptr());
+            f!(dbus_bus_add_match)(self.as_ptr(), match_rule.as_ptr(), std::ptr::null_mut());
         }
     }
 
-    pub fn flush(&self) {
+    fn flush(&self) {
         unsafe {
             f!(dbus_connection_flush)(self.as_ptr());
         }
     }
 
-    pub fn read_write(&self, timeout_milliseconds: c_int) {
-        unsafe {
-            f!(dbus_connection_read_write)(self.as_ptr(), timeout_milliseconds);
-        }
+    /// Returns `false` once the connection is closed
+    pub fn read_write(&self, timeout_milliseconds: c_int) -> bool {
+        unsafe { f!(dbus_connection_read_write)(self.as_ptr(), timeout_milliseconds) != 0 }
     }
 
     pub fn pop_message(&self) -> Option<Message> {
         unsafe { Message::new(f!(dbus_connection_pop_message)(self.as_ptr())) }
     }
 
-    pub fn send_and_block(&mut self, msg: &Message) -> Option<Message> {
+    /// Queue a message and flush it, returns its serial
+    pub fn send(&self, msg: &Message) -> Option<u32> {
+        let mut serial = 0;
         unsafe {
-            Message::new(f!(dbus_connection_send_with_reply_and_block)(
-                self.as_ptr(),
-                msg.as_ptr(),
-                -1,
-                self.err_ptr(),
-            ))
+            if f!(dbus_connection_send)(self.as_ptr(), msg.as_ptr(), &mut serial) == 0 {
+                return None;
+            }
         }
+        self.flush();
+        Some(serial)
+    }
+
+    pub fn unix_fd(&self) -> Option<RawFd> {
+        let mut fd = -1;
+        unsafe { (f!(dbus_connection_get_unix_fd)(self.as_ptr(), &mut fd) != 0).then_some(fd) }
+    }
+
+    /// Wait for the first incoming message `f` returns `Some` for, dropping the other ones.
+    ///
+    /// The socket is watched by the portal reactor, so no thread is blocked while waiting.
+    pub async fn next_message<T>(&mut self, mut f: impl FnMut(Message) -> Option<T>) -> Option<T> {
+        let fd = self.unix_fd()?;
+
+        loop {
+            if !self.read_write(0) {
+                log::error!("D-Bus connection closed");
+                return None;
+            }
+
+            while let Some(msg) = self.pop_message() {
+                if let Some(res) = f(msg) {
+                    return Some(res);
+                }
+            }
+
+            reactor::readable(fd).await;
+        }
+    }
+
+    /// Send a method call and wait for its reply
+    pub async fn call(&mut self, msg: Message) -> Result<Message, Error> {
+        let serial = self.send(&msg).ok_or_else(disconnected)?;
+        drop(msg);
+
+        let reply = self
+            .next_message(|reply| (reply.get_reply_serial() == serial).then_some(reply))
+            .await
+            .ok_or_else(disconnected)?;
+
+        if reply.get_type() == ffi::DBUS_MESSAGE_TYPE_ERROR {
+            let name = reply
+                .get_error_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let message = reply
+                .get_error_message()
+                .map(|message| message.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            return Err(reply_error(name, message));
+        }
+
+        Ok(reply)
     }
 }
 
+// libdbus connections and messages are internally locked, and each one is only used by
+// one future at a time
+unsafe impl Send for Connection {}
+unsafe impl Send for Message {}
+
 impl Drop for Connection {
     fn drop(&mut self) {
         unsafe {
@@ -185,6 +251,37 @@ impl Message {
         }
     }
 
+    pub fn get_type(&self) -> c_int {
+        unsafe { f!(dbus_message_get_type)(self.as_ptr()) }
+    }
+
+    /// Serial of the message this one replies to, `0` if none
+    pub fn get_reply_serial(&self) -> u32 {
+        unsafe { f!(dbus_message_get_reply_serial)(self.as_ptr()) }
+    }
+
+    pub fn get_error_name(&self) -> Option<&CStr> {
+        unsafe {
+            let name = f!(dbus_message_get_error_name)(self.as_ptr());
+            if name.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(name))
+            }
+        }
+    }
+
+    /// Human readable message of an error reply, its first argument
+    pub fn get_error_message(&self) -> Option<CString> {
+        let mut iter = MessageIter::new();
+        let has_args = unsafe { f!(dbus_message_iter_init)(self.as_ptr(), iter.as_ptr()) != 0 };
+        if has_args {
+            iter.get_string()
+        } else {
+            None
+        }
+    }
+
     pub fn get_path(&self) -> Option<&CStr> {
         unsafe {
             let path = f!(dbus_message_get_path)(self.as_ptr());
@@ -227,8 +324,10 @@ impl<'a> MessageIter<'a> {
     }
 
     pub fn init(&mut self, msg: &'a Message) {
+        // Returns false for messages without arguments, the iterator then yields
+        // `DBUS_TYPE_INVALID`, so a malformed reply is an error instead of a panic
         unsafe {
-            assert_eq!(f!(dbus_message_iter_init)(msg.as_ptr(), self.as_ptr()), 1);
+            f!(dbus_message_iter_init)(msg.as_ptr(), self.as_ptr());
         }
     }
 
@@ -275,6 +374,32 @@ impl<'a> MessageIter<'a> {
         unsafe { self.get_basic_str() }
     }
 
+    pub fn get_byte(&mut self) -> Option<u8> {
+        if self.get_arg_type() != ffi::DBUS_TYPE_BYTE {
+            return None;
+        }
+        let mut out = 0u8;
+        unsafe {
+            self.get_basic(&mut out as *mut u8 as *mut _);
+        }
+        Some(out)
+    }
+
+    pub fn get_byte_array(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        if self.get_arg_type() == ffi::DBUS_TYPE_ARRAY {
+            let mut array_iter = self.iter_recurse();
+            while let Some(byte) = array_iter.get_byte() {
+                out.push(byte);
+                array_iter.next();
+            }
+        }
+
+        out
+    }
+
+    #[cfg(not(feature = "testing"))]
     pub fn get_string_array(&mut self) -> Vec<CString> {
         let mut out = Vec::new();
 
@@ -328,14 +453,21 @@ impl<'a> MessageIter<'a> {
         );
     }
 
+    #[cfg(not(feature = "testing"))]
     pub fn append_byte(&mut self, value: u8) {
         self.append_basic(ffi::DBUS_TYPE_BYTE, &value as *const u8 as *const _);
     }
 
+    #[cfg(not(feature = "testing"))]
     pub fn append_u32(&mut self, value: u32) {
         self.append_basic(ffi::DBUS_TYPE_UINT32, &value as *const _ as *const _);
     }
 
+    /// libdbus duplicates `fd`, it can be closed once the message is built
+    pub fn append_fd(&mut self, fd: RawFd) {
+        self.append_basic(ffi::DBUS_TYPE_UNIX_FD, &fd as *const RawFd as *const _);
+    }
+
     pub fn append_bool(&mut self, value: bool) {
         let value: u32 = if value { 1 } else { 0 };
         self.append_basic(ffi::DBUS_TYPE_BOOLEAN, &value as *const _ as *const _);
@@ -0,0 +1,415 @@
+//! Fake `org.freedesktop.portal.Desktop` on a private session bus, for the tests.
+//!
+//! The bus is a `dbus-daemon` started on first use, and shared by every test of the process. The
+//! portal clients connect to it instead of the session bus. The fake portal is built on the pure
+//! Rust client, so with the default features it also checks the libdbus bindings against it.
+
+use std::{
+    collections::VecDeque,
+    ffi::{CStr, CString},
+    io::{BufRead, BufReader},
+    process::{ChildStdin, Command, Stdio},
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+
+use super::{
+    ffi,
+    wire::{Connection, Message, MessageIter},
+};
+
+/// A value of a recorded call
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U32(u32),
+    Str(String),
+    Bytes(Vec<u8>),
+    Fd,
+    Array(Vec<Value>),
+    Struct(Vec<Value>),
+    Dict(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn read(iter: &mut MessageIter) -> Option<Self> {
+        let value = match iter.get_arg_type() as u8 {
+            b's' => Self::Str(iter.get_string()?.into_string().ok()?),
+            b'o' => Self::Str(iter.get_object_path()?.into_string().ok()?),
+            b'u' => Self::U32(iter.get_u32()?),
+            b'b' => Self::Bool(iter.get_bool()?),
+            b'h' => Self::Fd,
+            b'v' => Self::read(&mut iter.iter_recurse())?,
+            b'r' => Self::Struct(Self::read_all(&mut iter.iter_recurse())),
+            b'a' => match iter.iter_recurse().get_arg_type() as u8 {
+                b'y' => Self::Bytes(iter.get_byte_array()),
+                b'e' => {
+                    let mut dict_iter = iter.iter_recurse();
+                    let mut entries = Vec::new();
+                    while dict_iter.get_arg_type() == ffi::DBUS_TYPE_DICT_ENTRY {
+                        let mut entry_iter = dict_iter.iter_recurse();
+                        let key = entry_iter.get_string()?.into_string().ok()?;
+                        entry_iter.next();
+                        entries.push((key, Self::read(&mut entry_iter)?));
+                        dict_iter.next();
+                    }
+                    Self::Dict(entries)
+                }
+                _ => Self::Array(Self::read_all(&mut iter.iter_recurse())),
+            },
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn read_all(iter: &mut MessageIter) -> Vec<Self> {
+        let mut values = Vec::new();
+        while let Some(value) = Self::read(iter) {
+            values.push(value);
+            if !iter.next() {
+                break;
+            }
+        }
+        values
+    }
+
+    pub fn str(&self) -> &str {
+        match self {
+            Self::Str(value) => value,
+            _ => panic!("{self:?} is not a string"),
+        }
+    }
+}
+
+/// A method call the fake portal received
+#[derive(Debug, Clone)]
+pub struct Call {
+    /// Eg. `FileChooser.OpenFile`
+    pub method: String,
+    pub args: Vec<Value>,
+}
+
+impl Call {
+    /// Entry of the `a{sv}` options, the last argument of portal methods
+    pub fn option(&self, key: &str) -> Option<&Value> {
+        let Some(Value::Dict(options)) = self.args.last() else {
+            return None;
+        };
+        options.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+/// How the fake portal answers the next request
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// `Response` signal, the code is `0` for success, `1` if the user cancelled
+    Response {
+        code: u32,
+        uris: Vec<String>,
+        /// `(name, glob)`
+        current_filter: Option<(String, String)>,
+    },
+    /// Error reply to the method call itself, eg. `org.freedesktop.DBus.Error.UnknownMethod`
+    Error(String),
+}
+
+impl Reply {
+    pub fn uris(uris: &[&str]) -> Self {
+        Self::Response {
+            code: 0,
+            uris: uris.iter().map(|uri| uri.to_string()).collect(),
+            current_filter: None,
+        }
+    }
+
+    pub fn cancelled() -> Self {
+        Self::Response {
+            code: 1,
+            uris: Vec::new(),
+            current_filter: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    replies: VecDeque<Reply>,
+    calls: Vec<Call>,
+    /// Ignore `handle_token`, like portals older than 0.9
+    ancient: bool,
+    requests: u32,
+}
+
+const FILE_CHOOSER_VERSION: u32 = 4;
+
+static BUS: OnceLock<Option<(String, ChildStdin)>> = OnceLock::new();
+
+/// Address of the private session bus, once a test started it
+pub fn bus_address() -> Option<&'static str> {
+    BUS.get()?.as_ref().map(|(address, _)| address.as_str())
+}
+
+/// Start the private session bus, `None` if `dbus-daemon` is not installed
+fn session_bus() -> Option<&'static str> {
+    let bus = BUS.get_or_init(|| {
+        let config = std::env::temp_dir().join(format!("rfd-test-bus-{}.conf", std::process::id()));
+        std::fs::write(
+            &config,
+            r#"<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#,
+        )
+        .ok()?;
+
+        // The shell kills the bus once stdin is closed, when the tests exit
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                r#"dbus-daemon --nofork --print-address --config-file="$1" & read -r _; kill $!"#,
+                "sh",
+            ])
+            .arg(&config)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        let mut address = String::new();
+        BufReader::new(child.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        std::fs::remove_file(&config).ok();
+
+        let address = address.trim().to_owned();
+        if address.is_empty() {
+            return None;
+        }
+        Some((address, child.stdin.take()?))
+    });
+
+    bus.as_ref().map(|(address, _)| address.as_str())
+}
+
+fn portal() -> Option<&'static Arc<Mutex<State>>> {
+    static PORTAL: OnceLock<Option<Arc<Mutex<State>>>> = OnceLock::new();
+
+    PORTAL
+        .get_or_init(|| {
+            session_bus()?;
+            let mut conn = pollster::block_on(Connection::new()).ok()?;
+
+            let mut msg = Message::new_method_call(
+                c"org.freedesktop.DBus",
+                c"/org/freedesktop/DBus",
+                c"org.freedesktop.DBus",
+                c"RequestName",
+            )?;
+            {
+                let mut iter = MessageIter::init_append(&mut msg);
+                iter.append_string(c"org.freedesktop.portal.Desktop");
+                // DBUS_NAME_FLAG_DO_NOT_QUEUE
+                iter.append_u32(4);
+            }
+            pollster::block_on(conn.call(msg)).ok()?;
+
+            // Portals older than 0.9 answer requests before the client subscribes to them
+            conn.add_match(c"type='method_call',interface='org.freedesktop.DBus',member='AddMatch',eavesdrop='true'");
+
+            let state = Arc::new(Mutex::new(State::default()));
+            let server_state = state.clone();
+            std::thread::spawn(move || serve(conn, server_state));
+            Some(state)
+        })
+        .as_ref()
+}
+
+fn serve(mut conn: Connection, state: Arc<Mutex<State>>) {
+    // `Response` signals waiting for the client to subscribe to their request, by path
+    let mut pending: Vec<(CString, Message)> = Vec::new();
+
+    loop {
+        let Some(call) =
+            pollster::block_on(conn.next_message(|msg| msg.is_method_call().then_some(msg)))
+        else {
+            return;
+        };
+
+        if call.get_interface() == Some(c"org.freedesktop.DBus") {
+            let rule = MessageIter::from_msg(&call)
+                .get_string()
+                .unwrap_or_default();
+            let rule = rule.to_string_lossy();
+            pending.retain(|(path, signal)| {
+                let subscribed = rule.contains(&format!("path='{}'", path.to_string_lossy()));
+                if subscribed {
+                    conn.send(signal);
+                }
+                !subscribed
+            });
+            continue;
+        }
+
+        if let Some(signal) = handle(&conn, &call, &state) {
+            pending.push(signal);
+        }
+    }
+}
+
+/// Returns the `Response` signal if it has to wait for the client to subscribe to it
+fn handle(conn: &Connection, call: &Message, state: &Mutex<State>) -> Option<(CString, Message)> {
+    let name = |name: Option<&CStr>| {
+        let name = name.map(CStr::to_string_lossy).unwrap_or_default();
+        name.rsplit('.').next().unwrap().to_owned()
+    };
+    let method = format!("{}.{}", name(call.get_interface()), name(call.get_member()));
+
+    if method == "Properties.Get" {
+        let mut reply = Message::new_method_return(call);
+        MessageIter::init_append(&mut reply).with_container(
+            ffi::DBUS_TYPE_VARIANT,
+            Some(c"u"),
+            |variant| variant.append_u32(FILE_CHOOSER_VERSION),
+        );
+        conn.send(&reply);
+        return None;
+    }
+
+    let call_record = Call {
+        method: method.clone(),
+        args: Value::read_all(&mut MessageIter::from_msg(call)),
+    };
+    let handle_token = call_record
+        .option("handle_token")
+        .map(|token| token.str().to_owned());
+
+    let mut state = state.lock().unwrap();
+    state.calls.push(call_record);
+
+    if method == "Request.Close" {
+        conn.send(&Message::new_method_return(call));
+        return None;
+    }
+
+    let reply = state.replies.pop_front().unwrap_or_else(Reply::cancelled);
+    let (code, uris, current_filter) = match reply {
+        Reply::Error(name) => {
+            let name = CString::new(name).unwrap();
+            conn.send(&Message::new_error(call, &name, c"Mock portal error"));
+            return None;
+        }
+        Reply::Response {
+            code,
+            uris,
+            current_filter,
+        } => (code, uris, current_filter),
+    };
+
+    state.requests += 1;
+    let sender = call.get_sender().unwrap().to_str().unwrap();
+    let handle_path = match handle_token {
+        Some(token) if !state.ancient => format!(
+            "/org/freedesktop/portal/desktop/request/{}/{token}",
+            sender.trim_start_matches(':').replace('.', "_")
+        ),
+        _ => format!(
+            "/org/freedesktop/portal/desktop/request/ancient/{}",
+            state.requests
+        ),
+    };
+    let handle_path = CString::new(handle_path).unwrap();
+    let ancient = state.ancient;
+    drop(state);
+
+    let mut reply = Message::new_method_return(call);
+    MessageIter::init_append(&mut reply).append_object_path(&handle_path);
+    conn.send(&reply);
+
+    let mut signal =
+        Message::new_signal(&handle_path, c"org.freedesktop.portal.Request", c"Response");
+    {
+        let mut iter = MessageIter::init_append(&mut signal);
+        iter.append_u32(code);
+        iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"{sv}"), |dict| {
+            dict.with_dict_entry(c"uris", c"as", |variant| {
+                variant.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"s"), |array| {
+                    for uri in &uris {
+                        array.append_string(&CString::new(uri.as_str()).unwrap());
+                    }
+                });
+            });
+
+            if let Some((name, glob)) = &current_filter {
+                dict.with_dict_entry(c"current_filter", c"(sa(us))", |variant| {
+                    variant.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+                        s.append_string(&CString::new(name.as_str()).unwrap());
+                        s.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(us)"), |array| {
+                            array.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+                                s.append_u32(0);
+                                s.append_string(&CString::new(glob.as_str()).unwrap());
+                            });
+                        });
+                    });
+                });
+            }
+        });
+    }
+    if ancient {
+        // The client only subscribes to the returned path once it got the reply
+        return Some((handle_path, signal));
+    }
+    conn.send(&signal);
+    None
+}
+
+/// The fake portal, tests using it run one after another
+pub struct MockPortal {
+    state: &'static Mutex<State>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl MockPortal {
+    /// `None` if `dbus-daemon` is not installed, the test should then be skipped.
+    ///
+    /// Panics instead if `CI` is set, so the tests can't silently stop running there. Elsewhere
+    /// the skipped test is reported, as it would otherwise pass without checking anything.
+    pub fn start() -> Option<Self> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        let lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        let Some(state) = portal() else {
+            if std::env::var_os("CI").is_some() {
+                panic!("dbus-daemon is not available");
+            }
+            // Straight to stderr, the test harness captures `eprintln!` of tests that pass
+            let test = std::thread::current().name().unwrap_or("test").to_owned();
+            let message = format!("{test}: skipped, dbus-daemon is not available\n");
+            std::io::Write::write_all(&mut std::io::stderr(), message.as_bytes()).ok();
+            return None;
+        };
+        *state.lock().unwrap() = State::default();
+
+        Some(Self { state, _lock: lock })
+    }
+
+    /// Answer the next request with `reply`, requests are cancelled if there is none left
+    pub fn reply(&self, reply: Reply) {
+        self.state.lock().unwrap().replies.push_back(reply);
+    }
+
+    /// Ignore `handle_token` and create the request object somewhere else, like portals older
+    /// than 0.9 did
+    pub fn set_ancient(&self, ancient: bool) {
+        self.state.lock().unwrap().ancient = ancient;
+    }
+
+    /// Portal calls received so far, the version is read by `Properties.Get` which isn't recorded
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
@@ -1,89 +1,290 @@
 use std::{
-    ffi::{CStr, CString, OsStr},
-    os::unix::ffi::OsStrExt,
-    path::PathBuf,
+    ffi::{CStr, CString, OsString},
+    os::{
+        fd::BorrowedFd,
+        unix::ffi::{OsStrExt, OsStringExt},
+    },
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
 mod ffi;
 
+mod documents;
+
+#[cfg(not(feature = "testing"))]
 mod file_dialog;
 
+mod file_manager;
+
+mod open_uri;
+
+#[cfg(not(feature = "pure-dbus"))]
 mod libdbus;
+#[cfg(not(feature = "pure-dbus"))]
 use libdbus::*;
 
-pub use file_dialog::{FileFilter, FilePath, HandleToken, OpenFileOptions, SaveFileOptions};
+// The mock portal of the tests is built on it too
+#[cfg(any(test, feature = "pure-dbus"))]
+#[cfg_attr(not(feature = "pure-dbus"), allow(dead_code))]
+mod wire;
+#[cfg(feature = "pure-dbus")]
+use wire::*;
+
+// The tests using the mock portal are left out by the `testing` feature, with the file dialogs
+#[cfg(all(test, not(feature = "testing")))]
+pub mod mock;
+
+mod reactor;
+
+use crate::Error;
 
-pub fn uris_to_paths(uris: Vec<CString>) -> Vec<PathBuf> {
-    uris.into_iter()
-        .filter_map(|uri| {
-            let bytes: Vec<u8> = percent_encoding::percent_decode(uri.as_bytes()).collect();
-            let Some(path) = bytes.strip_prefix(b"file://") else {
-                log::error!("Ignoring uri: {bytes:?} lacks `file://`");
-                return None;
-            };
-            Some(PathBuf::from(OsStr::from_bytes(path)))
-        })
-        .collect()
+#[cfg(not(feature = "testing"))]
+pub use file_dialog::{
+    open_file, save_file, save_files, uris_to_paths, Choice, FileFilter, FilePath, FilterKind,
+    OpenFileOptions, SaveFileOptions, SaveFilesOptions,
+};
+pub use open_uri::OpenUriOptions;
+
+/// Characters that are kept as they are in `file://` URIs
+const URI_PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// `file://` URI of an absolute path
+pub fn path_to_uri(path: &Path) -> CString {
+    let path = percent_encode(path.as_os_str().as_bytes(), URI_PATH);
+    CString::new(format!("file://{path}")).unwrap()
 }
 
-pub fn open_file(opts: OpenFileOptions) -> Option<Vec<CString>> {
-    let mut conn = Connection::new()?;
+/// Open `uri` with the default application, or the one chosen by the user if `ask` is set
+///
+/// `Ok(None)` if the user cancelled choosing the application.
+pub async fn open_uri(uri: &CStr, opts: OpenUriOptions) -> Result<Option<()>, Error> {
+    let mut conn = Connection::new().await?;
+    let handle_path = generate_response_path(&mut conn, &opts.handle_token);
+    let msg = Message::open_uri(uri, &opts);
+    request(conn, handle_path, msg, parse_empty_response).await
+}
 
+/// Same as [`open_uri`], for a local file
+pub async fn open_uri_file(
+    file: BorrowedFd<'_>,
+    opts: OpenUriOptions,
+) -> Result<Option<()>, Error> {
+    let mut conn = Connection::new().await?;
     let handle_path = generate_response_path(&mut conn, &opts.handle_token);
-    register_response_listener(&mut conn, &handle_path);
+    let msg = Message::open_uri_file(file, &opts);
+    request(conn, handle_path, msg, parse_empty_response).await
+}
 
-    let reply = conn.send_and_block(&Message::open_file(opts));
+/// Show the folder of `file` in the file manager, `Ok(None)` if the user cancelled
+pub async fn open_directory(
+    file: BorrowedFd<'_>,
+    opts: OpenUriOptions,
+) -> Result<Option<()>, Error> {
+    let mut conn = Connection::new().await?;
+    let handle_path = generate_response_path(&mut conn, &opts.handle_token);
+    let msg = Message::open_directory(file, &opts);
+    request(conn, handle_path, msg, parse_empty_response).await
+}
 
-    if conn.err().is_err() {
-        log::error!("OpenFile failed: {}", conn.err());
-        return None;
+/// Show `uris` selected in the file manager, through `org.freedesktop.FileManager1`
+pub async fn show_items(uris: &[CString]) -> Result<(), Error> {
+    let mut conn = Connection::new().await?;
+    conn.call(Message::show_items(uris)).await?;
+    Ok(())
+}
+
+/// Where the document store is mounted, eg. `/run/user/1000/doc`
+pub async fn document_mount_point() -> Result<PathBuf, Error> {
+    let mut conn = Connection::new().await?;
+    let reply = conn.call(Message::document_mount_point()).await?;
+    let path = MessageIter::from_msg(&reply).get_byte_array();
+    if path.is_empty() {
+        return Err(failed("Document store mount point is missing"));
     }
+    Ok(byte_path(path))
+}
 
-    let reply = reply?;
+/// `(document id, path on the host)` of each of `doc_ids` known to the document store
+pub async fn document_host_paths(doc_ids: &[CString]) -> Result<Vec<(CString, PathBuf)>, Error> {
+    let mut conn = Connection::new().await?;
+    let reply = conn.call(Message::document_host_paths(doc_ids)).await?;
 
     let mut iter = MessageIter::from_msg(&reply);
+    if iter.get_arg_type() != ffi::DBUS_TYPE_ARRAY {
+        return Err(failed("Host paths are not a dict"));
+    }
 
-    let Some(got_handle_path) = iter.get_object_path() else {
-        log::error!("Response object path is missing");
-        return None;
-    };
+    let mut out = Vec::new();
+    let mut dict_iter = iter.iter_recurse();
+    while dict_iter.get_arg_type() == ffi::DBUS_TYPE_DICT_ENTRY {
+        let mut entry_iter = dict_iter.iter_recurse();
+        let id = entry_iter.get_string();
+        entry_iter.next();
+        let path = entry_iter.get_byte_array();
 
-    if handle_path != got_handle_path {
-        log::debug!("Detected ancient version of xdg portal, attempting fallback");
-        register_response_listener(&mut conn, &got_handle_path);
+        match id {
+            Some(id) if !path.is_empty() => out.push((id, byte_path(path))),
+            _ => log::error!("Wrong type in host paths"),
+        }
+        dict_iter.next();
     }
 
-    wait_for_response(&mut conn, &handle_path)
+    Ok(out)
 }
 
-pub fn save_file(opts: SaveFileOptions) -> Option<Vec<CString>> {
-    let mut conn = Connection::new()?;
-
-    let handle_path = generate_response_path(&mut conn, &opts.handle_token);
-    register_response_listener(&mut conn, &handle_path);
+/// Add `file` to the document store, or update the document it already is, returning its id
+pub async fn add_document(file: BorrowedFd<'_>, persistent: bool) -> Result<CString, Error> {
+    let mut conn = Connection::new().await?;
+    let reply = conn
+        .call(Message::add_document(file, true, persistent))
+        .await?;
+    MessageIter::from_msg(&reply)
+        .get_string()
+        .ok_or_else(|| failed("Document id is missing"))
+}
 
-    let reply = conn.send_and_block(&Message::save_file(opts));
+/// Paths are sent as NUL terminated byte arrays
+fn byte_path(mut bytes: Vec<u8>) -> PathBuf {
+    if bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    PathBuf::from(OsString::from_vec(bytes))
+}
 
-    if conn.err().is_err() {
-        log::error!("OpenFile failed: {}", conn.err());
-        return None;
+/// `version` property of `org.freedesktop.portal.FileChooser`, fails if no portal is running
+pub async fn file_chooser_version() -> Result<u32, Error> {
+    let mut conn = Connection::new().await?;
+
+    let mut msg = Message::new_method_call(
+        c"org.freedesktop.portal.Desktop",
+        c"/org/freedesktop/portal/desktop",
+        c"org.freedesktop.DBus.Properties",
+        c"Get",
+    )
+    .ok_or_else(|| failed("Failed to create the Properties.Get call"))?;
+
+    {
+        let mut iter = MessageIter::init_append(&mut msg);
+        iter.append_string(c"org.freedesktop.portal.FileChooser");
+        iter.append_string(c"version");
     }
 
-    let reply = reply?;
+    let reply = match conn.call(msg).await {
+        // The portal is running, but without a FileChooser implementation
+        Err(Error::Portal { name, message })
+            if name == "org.freedesktop.DBus.Error.InvalidArgs" =>
+        {
+            return Err(Error::BackendUnavailable(format!(
+                "XDG portal has no FileChooser: {message}"
+            )));
+        }
+        res => res?,
+    };
 
     let mut iter = MessageIter::from_msg(&reply);
+    if iter.get_arg_type() != ffi::DBUS_TYPE_VARIANT {
+        return Err(failed("FileChooser version is not a variant"));
+    }
+    iter.iter_recurse()
+        .get_u32()
+        .ok_or_else(|| failed("FileChooser version is not a u32"))
+}
 
-    let Some(got_handle_path) = iter.get_object_path() else {
-        log::error!("Response object path is missing");
-        return None;
+/// Call a portal method and wait for the `Response` signal of the request it created
+///
+/// `Ok(None)` if the user cancelled the dialog.
+async fn request<T>(
+    conn: Connection,
+    handle_path: CString,
+    msg: Message,
+    parse: fn(&Message) -> Result<Option<T>, Error>,
+) -> Result<Option<T>, Error> {
+    let mut request = PendingRequest {
+        conn,
+        handle_path,
+        finished: false,
     };
+    let res = request.run(msg, parse).await;
+    request.finished = true;
+    res
+}
+
+/// A portal request, closed with `org.freedesktop.portal.Request.Close` if it gets dropped before
+/// it finished, eg. when the future of the dialog is dropped
+struct PendingRequest {
+    conn: Connection,
+    handle_path: CString,
+    finished: bool,
+}
+
+impl PendingRequest {
+    async fn run<T>(
+        &mut self,
+        msg: Message,
+        parse: fn(&Message) -> Result<Option<T>, Error>,
+    ) -> Result<Option<T>, Error> {
+        // Subscribe before the call, so the response can't be missed
+        register_response_listener(&mut self.conn, &self.handle_path);
 
-    if handle_path != got_handle_path {
-        log::debug!("Detected ancient version of xdg portal, attempting fallback");
-        register_response_listener(&mut conn, &got_handle_path);
+        let reply = self.conn.call(msg).await?;
+
+        let Some(got_handle_path) = MessageIter::from_msg(&reply).get_object_path() else {
+            return Err(failed("Response object path is missing"));
+        };
+
+        if self.handle_path != got_handle_path {
+            log::debug!("Detected ancient version of xdg portal, attempting fallback");
+            register_response_listener(&mut self.conn, &got_handle_path);
+            self.handle_path = got_handle_path;
+        }
+
+        wait_for_response(&mut self.conn, &self.handle_path, parse).await
     }
+}
 
-    wait_for_response(&mut conn, &handle_path)
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let Some(msg) = Message::new_method_call(
+            c"org.freedesktop.portal.Desktop",
+            &self.handle_path,
+            c"org.freedesktop.portal.Request",
+            c"Close",
+        ) else {
+            return;
+        };
+
+        // Nobody is left to wait for the reply
+        if self.conn.send(&msg).is_none() {
+            log::error!("Failed to close portal request {:?}", self.handle_path);
+        }
+    }
+}
+
+/// Last part of the path of the request object
+#[derive(Debug)]
+pub struct HandleToken(pub CString);
+
+impl Default for HandleToken {
+    fn default() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let token = format!("rfd_{now}");
+        Self(CString::new(token).unwrap())
+    }
 }
 
 fn generate_response_path(conn: &mut Connection, handle_token: &HandleToken) -> CString {
@@ -97,6 +298,7 @@ fn generate_response_path(conn: &mut Connection, handle_token: &HandleToken) ->
     .unwrap()
 }
 
+/// Only queues the match rule, it goes out ahead of the call or the wait for the response after it
 fn register_response_listener(conn: &mut Connection, handle_path: &CStr) {
     conn.add_match(
         &CString::new(
@@ -111,87 +313,236 @@ fn register_response_listener(conn: &mut Connection, handle_path: &CStr) {
         )
         .unwrap(),
     );
-
-    if conn.err().is_err() {
-        log::error!("Failed to add match rule: {}", conn.err());
-    }
-
-    conn.flush();
 }
 
 #[derive(Debug)]
 enum ResponseCode {
     Success = 0,
-    // Cancelled = 1,
-    // Other = 2,
-}
-
-fn wait_for_response(conn: &mut Connection, handle_path: &CStr) -> Option<Vec<CString>> {
-    loop {
-        conn.read_write(-1);
-        while let Some(signal) = conn.pop_message() {
-            if signal.is_signal(c"org.freedesktop.portal.Request", c"Response") {
-                let Some(path) = signal.get_path() else {
-                    log::error!("Response signal is missing a path");
-                    return None;
-                };
-
-                if path == handle_path {
-                    return parse_response(&signal);
-                }
-            }
-        }
+    Cancelled = 1,
+    /// The interaction ended in some other way, eg. the dialog was closed by the system
+    Other = 2,
+}
+
+/// Bus to connect to instead of the session bus, the private one of the mock portal in the tests
+fn bus_address() -> Option<&'static str> {
+    #[cfg(all(test, not(feature = "testing")))]
+    return mock::bus_address();
+    #[cfg(not(all(test, not(feature = "testing"))))]
+    None
+}
+
+pub fn disconnected() -> Error {
+    Error::Portal {
+        name: "org.freedesktop.DBus.Error.Disconnected".to_owned(),
+        message: "D-Bus connection closed".to_owned(),
+    }
+}
+
+/// Error replies meaning that nobody implements the called method, the portal is missing or
+/// too old
+const UNAVAILABLE_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.NameHasNoOwner",
+    "org.freedesktop.DBus.Error.UnknownObject",
+    "org.freedesktop.DBus.Error.UnknownInterface",
+    "org.freedesktop.DBus.Error.UnknownMethod",
+];
+
+pub fn reply_error(name: String, message: String) -> Error {
+    if UNAVAILABLE_ERRORS.contains(&name.as_str()) {
+        Error::BackendUnavailable(format!("D-Bus service is not available: {name}: {message}"))
+    } else {
+        Error::Portal { name, message }
+    }
+}
+
+/// Error for replies of the portal that don't make sense
+fn failed(message: &str) -> Error {
+    Error::Portal {
+        name: "org.freedesktop.portal.Error.Failed".to_owned(),
+        message: message.to_owned(),
     }
 }
 
-fn parse_response(msg: &Message) -> Option<Vec<CString>> {
-    let mut iter = MessageIter::from_msg(msg);
+async fn wait_for_response<T>(
+    conn: &mut Connection,
+    handle_path: &CStr,
+    parse: fn(&Message) -> Result<Option<T>, Error>,
+) -> Result<Option<T>, Error> {
+    conn.next_message(|signal| {
+        if !signal.is_signal(c"org.freedesktop.portal.Request", c"Response") {
+            return None;
+        }
+
+        let Some(path) = signal.get_path() else {
+            log::error!("Response signal is missing a path");
+            return None;
+        };
+
+        (path == handle_path).then(|| parse(&signal))
+    })
+    .await
+    .ok_or_else(disconnected)?
+}
 
+/// `Ok(false)` if the dialog was closed without an answer of the user.
+///
+/// The portal did show a dialog either way, so none of them is an error that would let another
+/// backend show a second one.
+fn response_code(iter: &mut MessageIter) -> Result<bool, Error> {
     let Some(response_code) = iter.get_u32() else {
-        log::error!("Response code missing");
-        return None;
+        return Err(failed("Response code missing"));
     };
-    if response_code != ResponseCode::Success as u32 {
-        return Some(vec![]);
+    match response_code {
+        code if code == ResponseCode::Success as u32 => Ok(true),
+        code if code == ResponseCode::Cancelled as u32 => Ok(false),
+        code if code == ResponseCode::Other as u32 => {
+            log::debug!("The dialog was closed without an answer of the user");
+            Ok(false)
+        }
+        code => Err(failed(&format!("Unknown response code {code}"))),
     }
+}
+
+/// Response of requests that don't return anything, like `OpenURI`
+fn parse_empty_response(msg: &Message) -> Result<Option<()>, Error> {
+    Ok(response_code(&mut MessageIter::from_msg(msg))?.then_some(()))
+}
 
-    if !iter.next() {
-        log::error!("Body of the response is empty");
-        return None;
+// Requests are tested through `OpenFile`, which the `testing` feature leaves out
+#[cfg(all(test, not(feature = "testing")))]
+mod tests {
+    use std::future::Future;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    use super::mock::{MockPortal, Reply, Value};
+    use super::OpenFileOptions;
+    use crate::Error;
+
+    fn open_file() -> Result<Option<super::file_dialog::Response>, Error> {
+        pollster::block_on(super::open_file(OpenFileOptions {
+            title: c"Open".into(),
+            ..Default::default()
+        }))
     }
 
-    if iter.get_arg_type() != ffi::DBUS_TYPE_ARRAY {
-        log::error!("Body of the response is not an array");
-        return None;
+    #[test]
+    fn path_to_uri() {
+        let path = Path::new("/home/user/Ünïcode dir/a#b.txt");
+        let uri = super::path_to_uri(path);
+        assert_eq!(
+            uri.to_str().unwrap(),
+            "file:///home/user/%C3%9Cn%C3%AFcode%20dir/a%23b.txt"
+        );
+        assert_eq!(super::uris_to_paths(vec![uri]), [PathBuf::from(path)]);
     }
 
-    let mut dict_iter = iter.iter_recurse();
+    #[test]
+    fn response() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+        portal.reply(Reply::Response {
+            code: 0,
+            uris: vec!["file:///tmp/a%20b.txt".into()],
+            current_filter: Some(("Text".into(), "*.txt".into())),
+        });
+
+        let res = open_file().unwrap().unwrap();
+        assert_eq!(super::uris_to_paths(res.uris), [Path::new("/tmp/a b.txt")]);
+        let (name, globs) = res.current_filter.unwrap();
+        assert_eq!(name.to_str(), Ok("Text"));
+        assert_eq!(globs[0].1.to_str(), Ok("*.txt"));
+
+        let calls = portal.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].method, "FileChooser.OpenFile");
+        assert_eq!(calls[0].args[1], Value::Str("Open".into()));
+        assert!(calls[0]
+            .option("handle_token")
+            .unwrap()
+            .str()
+            .starts_with("rfd_"));
+    }
 
-    while dict_iter.get_arg_type() == ffi::DBUS_TYPE_DICT_ENTRY {
-        let mut entry_iter = dict_iter.iter_recurse();
+    #[test]
+    fn cancelled() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+        portal.reply(Reply::cancelled());
+        assert!(open_file().unwrap().is_none());
+
+        portal.reply(Reply::Response {
+            code: 2,
+            uris: Vec::new(),
+            current_filter: None,
+        });
+        assert!(open_file().unwrap().is_none());
+    }
 
-        let Some(key) = entry_iter.get_string() else {
-            log::error!("Wrong type of a dict key");
-            dict_iter.next();
-            continue;
+    #[test]
+    fn error_reply() {
+        let Some(portal) = MockPortal::start() else {
+            return;
         };
 
-        entry_iter.next();
-        if key.as_c_str() == c"uris" {
-            if entry_iter.get_arg_type() == ffi::DBUS_TYPE_VARIANT {
-                let mut var_iter = entry_iter.iter_recurse();
-                return Some(var_iter.get_string_array());
-            } else {
-                log::error!(
-                    "Response.uris type {} != VARIANT",
-                    entry_iter.get_arg_type()
-                );
+        portal.reply(Reply::Error(
+            "org.freedesktop.portal.Error.NotAllowed".into(),
+        ));
+        assert!(matches!(
+            open_file(),
+            Err(Error::Portal { name, .. }) if name == "org.freedesktop.portal.Error.NotAllowed"
+        ));
+
+        // Lets the next backend try
+        portal.reply(Reply::Error(
+            "org.freedesktop.DBus.Error.UnknownMethod".into(),
+        ));
+        assert!(matches!(open_file(), Err(Error::BackendUnavailable(_))));
+    }
+
+    #[test]
+    fn ancient_portal() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+        portal.set_ancient(true);
+        portal.reply(Reply::uris(&["file:///tmp/a.txt"]));
+
+        let res = open_file().unwrap().unwrap();
+        assert_eq!(
+            super::uris_to_paths(res.uris),
+            [PathBuf::from("/tmp/a.txt")]
+        );
+    }
+
+    #[test]
+    fn dropped_request_is_closed() {
+        let Some(portal) = MockPortal::start() else {
+            return;
+        };
+
+        // Dropped before the user answered, eg. the future of a dialog that timed out
+        let start = Instant::now();
+        {
+            let mut request = std::pin::pin!(super::open_file(OpenFileOptions::default()));
+            let waker = std::task::Waker::noop();
+            // Connecting doesn't block either, so polled until the request reached the portal
+            while portal.calls().is_empty() && start.elapsed() < Duration::from_secs(5) {
+                let poll = request
+                    .as_mut()
+                    .poll(&mut std::task::Context::from_waker(waker));
+                assert!(poll.is_pending());
+                std::thread::sleep(Duration::from_millis(10));
             }
         }
 
-        dict_iter.next();
+        while portal.calls().len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let methods: Vec<_> = portal.calls().into_iter().map(|call| call.method).collect();
+        assert_eq!(methods, ["FileChooser.OpenFile", "Request.Close"]);
     }
-
-    log::error!("Response.uris was not found");
-    None
 }
@@ -0,0 +1,85 @@
+use std::{
+    ffi::{CStr, CString},
+    os::fd::{AsRawFd, BorrowedFd},
+};
+
+use super::{ffi, HandleToken, Message, MessageIter};
+
+#[derive(Debug, Default)]
+pub struct OpenUriOptions {
+    pub parent_window: CString,
+    pub handle_token: HandleToken,
+    pub ask: Option<bool>,
+}
+
+fn append_options(iter: &mut MessageIter, opts: &OpenUriOptions) {
+    iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"{sv}"), |dict| {
+        dict.with_dict_entry(c"handle_token", c"s", |variant| {
+            variant.append_string(&opts.handle_token.0);
+        });
+
+        if let Some(ask) = opts.ask {
+            dict.with_dict_entry(c"ask", c"b", |variant| {
+                variant.append_bool(ask);
+            });
+        }
+    });
+}
+
+impl Message {
+    pub fn open_uri(uri: &CStr, opts: &OpenUriOptions) -> Self {
+        let mut msg = Message::new_method_call(
+            c"org.freedesktop.portal.Desktop",
+            c"/org/freedesktop/portal/desktop",
+            c"org.freedesktop.portal.OpenURI",
+            c"OpenURI",
+        )
+        .unwrap();
+
+        let mut iter = MessageIter::init_append(&mut msg);
+
+        iter.append_string(&opts.parent_window);
+        iter.append_string(uri);
+        append_options(&mut iter, opts);
+
+        msg
+    }
+
+    /// `OpenURI.OpenFile`, the portal gets the file itself instead of its path
+    pub fn open_uri_file(file: BorrowedFd, opts: &OpenUriOptions) -> Self {
+        let mut msg = Message::new_method_call(
+            c"org.freedesktop.portal.Desktop",
+            c"/org/freedesktop/portal/desktop",
+            c"org.freedesktop.portal.OpenURI",
+            c"OpenFile",
+        )
+        .unwrap();
+
+        let mut iter = MessageIter::init_append(&mut msg);
+
+        iter.append_string(&opts.parent_window);
+        iter.append_fd(file.as_raw_fd());
+        append_options(&mut iter, opts);
+
+        msg
+    }
+
+    /// `OpenURI.OpenDirectory`, shows the folder of `file` in the file manager
+    pub fn open_directory(file: BorrowedFd, opts: &OpenUriOptions) -> Self {
+        let mut msg = Message::new_method_call(
+            c"org.freedesktop.portal.Desktop",
+            c"/org/freedesktop/portal/desktop",
+            c"org.freedesktop.portal.OpenURI",
+            c"OpenDirectory",
+        )
+        .unwrap();
+
+        let mut iter = MessageIter::init_append(&mut msg);
+
+        iter.append_string(&opts.parent_window);
+        iter.append_fd(file.as_raw_fd());
+        append_options(&mut iter, opts);
+
+        msg
+    }
+}
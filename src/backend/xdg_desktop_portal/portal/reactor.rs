@@ -0,0 +1,217 @@
+//! Wakes up portal futures once their D-Bus socket becomes readable, or writable again.
+//!
+//! A single thread, spawned on first use, `poll`s the sockets of every pending dialog, so an
+//! unanswered dialog costs one file descriptor instead of a parked thread.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    os::fd::RawFd,
+    pin::Pin,
+    sync::{Arc, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+};
+
+struct Source {
+    fd: RawFd,
+    events: libc::c_short,
+    waker: Waker,
+    ready: bool,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    sources: HashMap<u64, Source>,
+}
+
+struct Reactor {
+    state: Arc<Mutex<State>>,
+    /// Write end of the pipe interrupting `poll` when a source is added
+    notify: RawFd,
+}
+
+static REACTOR: OnceLock<Option<Reactor>> = OnceLock::new();
+
+impl Reactor {
+    fn get() -> Option<&'static Reactor> {
+        REACTOR.get_or_init(Self::spawn).as_ref()
+    }
+
+    fn spawn() -> Option<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+            log::error!(
+                "Failed to create the portal reactor pipe: {}",
+                std::io::Error::last_os_error()
+            );
+            return None;
+        }
+        let [wakeup, notify] = fds;
+
+        let state = Arc::new(Mutex::default());
+        let res = std::thread::Builder::new()
+            .name("rfd-portal-reactor".into())
+            .spawn({
+                let state = state.clone();
+                move || run(&state, wakeup)
+            });
+
+        if let Err(err) = res {
+            log::error!("Failed to spawn the portal reactor thread: {err}");
+            return None;
+        }
+
+        Some(Self { state, notify })
+    }
+
+    fn notify(&self) {
+        unsafe { libc::write(self.notify, [0u8].as_ptr().cast(), 1) };
+    }
+}
+
+fn run(state: &Mutex<State>, wakeup: RawFd) -> ! {
+    let mut pollfds = Vec::new();
+    let mut ids = Vec::new();
+    let mut wakers = Vec::new();
+
+    loop {
+        pollfds.clear();
+        ids.clear();
+
+        pollfds.push(libc::pollfd {
+            fd: wakeup,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+
+        {
+            let state = state.lock().unwrap();
+            for (id, source) in state.sources.iter().filter(|(_, s)| !s.ready) {
+                pollfds.push(libc::pollfd {
+                    fd: source.fd,
+                    events: source.events,
+                    revents: 0,
+                });
+                ids.push(*id);
+            }
+        }
+
+        let res = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as _, -1) };
+        if res < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                log::error!("Portal reactor poll failed: {err}");
+            }
+            continue;
+        }
+
+        if pollfds[0].revents != 0 {
+            let mut buf = [0u8; 64];
+            while unsafe { libc::read(wakeup, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+        }
+
+        {
+            let mut state = state.lock().unwrap();
+            for (pollfd, id) in pollfds[1..].iter().zip(ids.iter()) {
+                if pollfd.revents == 0 {
+                    continue;
+                }
+                if let Some(source) = state.sources.get_mut(id) {
+                    source.ready = true;
+                    wakers.push(source.waker.clone());
+                }
+            }
+        }
+
+        // Wake outside of the lock, an executor might poll the future right away
+        for waker in wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// Resolves once `fd` is ready for `events`, or has hung up
+pub struct Watch {
+    fd: RawFd,
+    events: libc::c_short,
+    id: Option<u64>,
+}
+
+pub fn readable(fd: RawFd) -> Watch {
+    Watch {
+        fd,
+        events: libc::POLLIN,
+        id: None,
+    }
+}
+
+/// Only the pure Rust D-Bus client writes through the reactor
+#[cfg(any(test, feature = "pure-dbus"))]
+pub fn writable(fd: RawFd) -> Watch {
+    Watch {
+        fd,
+        events: libc::POLLOUT,
+        id: None,
+    }
+}
+
+impl Future for Watch {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(reactor) = Reactor::get() else {
+            // Without a reactor, fall back to blocking the executor until the fd is ready
+            let mut pollfd = libc::pollfd {
+                fd: self.fd,
+                events: self.events,
+                revents: 0,
+            };
+            unsafe { libc::poll(&mut pollfd, 1, -1) };
+            return Poll::Ready(());
+        };
+
+        let mut state = reactor.state.lock().unwrap();
+
+        let Some(id) = self.id else {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.sources.insert(
+                id,
+                Source {
+                    fd: self.fd,
+                    events: self.events,
+                    waker: cx.waker().clone(),
+                    ready: false,
+                },
+            );
+            self.id = Some(id);
+            drop(state);
+
+            reactor.notify();
+            return Poll::Pending;
+        };
+
+        match state.sources.get_mut(&id) {
+            Some(source) if !source.ready => {
+                source.waker.clone_from(cx.waker());
+                Poll::Pending
+            }
+            _ => {
+                state.sources.remove(&id);
+                self.id = None;
+                Poll::Ready(())
+            }
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        // The fd is about to be closed, it must not end up in `poll` anymore
+        if let (Some(id), Some(reactor)) = (self.id, REACTOR.get().and_then(Option::as_ref)) {
+            reactor.state.lock().unwrap().sources.remove(&id);
+            reactor.notify();
+        }
+    }
+}
@@ -0,0 +1,1392 @@
+//! Pure Rust D-Bus client, used instead of `libdbus` with the `pure-dbus` feature.
+//!
+//! Only what the portals need is implemented: the session bus over a Unix socket, `EXTERNAL`
+//! authentication, passing file descriptors, and the same `Connection`, `Message` and
+//! `MessageIter` surface as the `libdbus` bindings.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    ffi::{CStr, CString, OsStr},
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::{ffi::OsStrExt, net::UnixStream},
+    },
+    path::Path,
+};
+
+use super::{bus_address, disconnected, ffi, reactor, reply_error};
+use crate::Error;
+use libc::c_int;
+
+const MESSAGE_TYPE_METHOD_CALL: u8 = 1;
+#[cfg(all(test, not(feature = "testing")))]
+const MESSAGE_TYPE_METHOD_RETURN: u8 = 2;
+const MESSAGE_TYPE_ERROR: u8 = 3;
+const MESSAGE_TYPE_SIGNAL: u8 = 4;
+
+const FLAG_NO_REPLY_EXPECTED: u8 = 0x1;
+
+const FIELD_PATH: u8 = 1;
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_ERROR_NAME: u8 = 4;
+const FIELD_REPLY_SERIAL: u8 = 5;
+const FIELD_DESTINATION: u8 = 6;
+const FIELD_SENDER: u8 = 7;
+const FIELD_SIGNATURE: u8 = 8;
+const FIELD_UNIX_FDS: u8 = 9;
+
+/// Largest message the bus accepts
+const MAX_MESSAGE_SIZE: usize = 1 << 27;
+/// Room for the file descriptors of one received message, the bus allows 16 by default
+const MAX_UNIX_FDS: usize = 64;
+
+/// Socket of the session bus, from `DBUS_SESSION_BUS_ADDRESS` or `$XDG_RUNTIME_DIR/bus`
+fn connect_session_bus() -> Result<UnixStream, String> {
+    let addresses = bus_address()
+        .map(str::to_owned)
+        .or_else(|| std::env::var("DBUS_SESSION_BUS_ADDRESS").ok());
+    let Some(addresses) = addresses else {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .ok_or("neither DBUS_SESSION_BUS_ADDRESS nor XDG_RUNTIME_DIR is set")?;
+        let path = Path::new(&runtime_dir).join("bus");
+        return UnixStream::connect(&path).map_err(|err| format!("{}: {err}", path.display()));
+    };
+
+    let mut error = format!("no supported address in {addresses:?}");
+    for address in addresses.split(';') {
+        match connect_address(address) {
+            Some(Ok(stream)) => return Ok(stream),
+            Some(Err(err)) => error = format!("{address}: {err}"),
+            None => {}
+        }
+    }
+    Err(error)
+}
+
+/// `None` if `address` is not a Unix socket, eg. `tcp:` or `unixexec:`
+fn connect_address(address: &str) -> Option<io::Result<UnixStream>> {
+    let params = address.strip_prefix("unix:")?;
+
+    for param in params.split(',') {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+        let value: Vec<u8> = percent_encoding::percent_decode_str(value).collect();
+
+        match key {
+            "path" => return Some(UnixStream::connect(OsStr::from_bytes(&value))),
+            #[cfg(target_os = "linux")]
+            "abstract" => {
+                use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+                return Some(
+                    SocketAddr::from_abstract_name(&value)
+                        .and_then(|addr| UnixStream::connect_addr(&addr)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+pub struct Connection {
+    stream: UnixStream,
+    unique_name: CString,
+    next_serial: Cell<u32>,
+    /// Messages, or what is left of them, that did not fit into the socket yet
+    outgoing: RefCell<VecDeque<Outgoing>>,
+    /// Bytes read from the socket that are not a whole message yet
+    buffer: Vec<u8>,
+    /// File descriptors received along with `buffer`
+    fds: VecDeque<OwnedFd>,
+    incoming: VecDeque<Message>,
+}
+
+struct Outgoing {
+    bytes: Vec<u8>,
+    /// Sent along with the first byte of the message
+    fds: Vec<OwnedFd>,
+}
+
+impl Connection {
+    /// Connect to the session bus.
+    ///
+    /// The socket is non-blocking from the start, waiting for the bus is left to the portal
+    /// reactor, like for every other message.
+    pub async fn new() -> Result<Self, Error> {
+        let unavailable = |err: String| {
+            Error::BackendUnavailable(format!("Failed to connect to the session bus: {err}"))
+        };
+
+        let stream = connect_session_bus().map_err(unavailable)?;
+        stream
+            .set_nonblocking(true)
+            .map_err(|err| unavailable(err.to_string()))?;
+
+        let mut conn = Self {
+            stream,
+            unique_name: CString::default(),
+            next_serial: Cell::new(1),
+            outgoing: RefCell::default(),
+            buffer: Vec::new(),
+            fds: VecDeque::new(),
+            incoming: VecDeque::new(),
+        };
+        conn.authenticate()
+            .await
+            .map_err(|err| unavailable(err.to_string()))?;
+
+        let hello = Message::new_method_call(
+            c"org.freedesktop.DBus",
+            c"/org/freedesktop/DBus",
+            c"org.freedesktop.DBus",
+            c"Hello",
+        )
+        .ok_or_else(disconnected)?;
+        let reply = conn.call(hello).await?;
+
+        conn.unique_name = MessageIter::from_msg(&reply)
+            .get_string()
+            .ok_or_else(|| unavailable("Hello was not answered with a unique name".into()))?;
+
+        Ok(conn)
+    }
+
+    /// SASL `EXTERNAL` authentication as the uid of the process, the bus checks it through the
+    /// credentials of the socket
+    async fn authenticate(&mut self) -> io::Result<()> {
+        let uid = unsafe { libc::geteuid() }.to_string();
+        let uid: String = uid.bytes().map(|byte| format!("{byte:02x}")).collect();
+
+        self.write_line(&format!("\0AUTH EXTERNAL {uid}")).await?;
+        let reply = self.read_line().await?;
+        if !reply.starts_with("OK ") {
+            return Err(io::Error::other(format!(
+                "authentication rejected: {reply}"
+            )));
+        }
+
+        self.write_line("NEGOTIATE_UNIX_FD").await?;
+        let reply = self.read_line().await?;
+        if reply != "AGREE_UNIX_FD" {
+            log::debug!("D-Bus does not support passing file descriptors: {reply}");
+        }
+
+        self.write_line("BEGIN").await
+    }
+
+    async fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.queue(format!("{line}\r\n").into_bytes(), Vec::new());
+        self.write_queued().await
+    }
+
+    /// Byte by byte, so nothing after the line is consumed
+    async fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        let mut byte = [0];
+
+        while !line.ends_with(b"\r\n") {
+            match self.stream.read(&mut byte) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(_) => line.push(byte[0]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    reactor::readable(self.stream.as_raw_fd()).await
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        line.truncate(line.len() - 2);
+
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    pub fn get_unique_name(&mut self) -> CString {
+        self.unique_name.clone()
+    }
+
+    /// Add a match rule without waiting for the bus to confirm it
+    pub fn add_match(&mut self, match_rule: &CStr) {
+        let Some(mut msg) = Message::new_method_call(
+            c"org.freedesktop.DBus",
+            c"/org/freedesktop/DBus",
+            c"org.freedesktop.DBus",
+            c"AddMatch",
+        ) else {
+            return;
+        };
+        msg.flags |= FLAG_NO_REPLY_EXPECTED;
+        MessageIter::init_append(&mut msg).append_string(match_rule);
+
+        self.send(&msg);
+    }
+
+    /// Write the messages that did not fit into the socket when they were sent, blocking for at
+    /// most a second.
+    ///
+    /// Only for `Drop`, async code leaves waiting for the bus to `next_message`.
+    fn flush(&self) {
+        loop {
+            match self.write_available() {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(err) => {
+                    log::error!("Failed to send a D-Bus message: {err}");
+                    return;
+                }
+            }
+
+            let mut pollfd = libc::pollfd {
+                fd: self.stream.as_raw_fd(),
+                events: libc::POLLOUT,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut pollfd, 1, 1000) } == 0 {
+                log::error!("Timed out sending D-Bus messages");
+                return;
+            }
+        }
+    }
+
+    /// Returns its serial.
+    ///
+    /// Never blocks, what doesn't fit into the socket is written by `next_message`, or once the
+    /// connection is dropped.
+    pub fn send(&self, msg: &Message) -> Option<u32> {
+        let serial = self.next_serial.get();
+        self.next_serial.set(serial.checked_add(1).unwrap_or(1));
+
+        let fds = msg
+            .fds
+            .iter()
+            .map(OwnedFd::try_clone)
+            .collect::<io::Result<Vec<_>>>();
+        let res = fds.and_then(|fds| {
+            self.queue(msg.serialize(serial), fds);
+            self.write_available()
+        });
+
+        match res {
+            Ok(_) => Some(serial),
+            Err(err) => {
+                log::error!("Failed to send a D-Bus message: {err}");
+                None
+            }
+        }
+    }
+
+    fn queue(&self, bytes: Vec<u8>, fds: Vec<OwnedFd>) {
+        self.outgoing
+            .borrow_mut()
+            .push_back(Outgoing { bytes, fds });
+    }
+
+    /// Write as much of the queued messages as the socket takes, `Ok(true)` once all of them are
+    /// written
+    fn write_available(&self) -> io::Result<bool> {
+        let mut outgoing = self.outgoing.borrow_mut();
+
+        while let Some(msg) = outgoing.front_mut() {
+            match send_with_fds(&self.stream, &msg.bytes, &msg.fds) {
+                Ok(written) => {
+                    msg.bytes.drain(..written);
+                    msg.fds.clear();
+                    if msg.bytes.is_empty() {
+                        outgoing.pop_front();
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Write all queued messages, waiting for the socket through the portal reactor
+    async fn write_queued(&mut self) -> io::Result<()> {
+        while !self.write_available()? {
+            reactor::writable(self.stream.as_raw_fd()).await;
+        }
+        Ok(())
+    }
+
+    /// Read what is available on the socket, returns `Ok(0)` once it is closed
+    fn read(&mut self) -> io::Result<usize> {
+        let mut chunk = [0; 4096];
+        let read = recv_with_fds(&self.stream, &mut chunk, &mut self.fds)?;
+        self.buffer.extend_from_slice(&chunk[..read]);
+
+        while let Some(len) = message_len(&self.buffer)? {
+            if self.buffer.len() < len {
+                break;
+            }
+            let bytes: Vec<u8> = self.buffer.drain(..len).collect();
+            let (mut msg, unix_fds) = Message::parse(&bytes)?;
+            if unix_fds > self.fds.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "D-Bus message is missing file descriptors",
+                ));
+            }
+            msg.fds = self.fds.drain(..unix_fds).collect();
+            self.incoming.push_back(msg);
+        }
+
+        Ok(read)
+    }
+
+    /// Wait for the first incoming message `f` returns `Some` for, dropping the other ones.
+    ///
+    /// The socket is watched by the portal reactor, so no thread is blocked while waiting.
+    pub async fn next_message<T>(&mut self, mut f: impl FnMut(Message) -> Option<T>) -> Option<T> {
+        if let Err(err) = self.write_queued().await {
+            log::error!("Failed to send a D-Bus message: {err}");
+            return None;
+        }
+
+        loop {
+            while let Some(msg) = self.incoming.pop_front() {
+                if let Some(res) = f(msg) {
+                    return Some(res);
+                }
+            }
+
+            match self.read() {
+                Ok(0) => {
+                    log::error!("D-Bus connection closed");
+                    return None;
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    reactor::readable(self.stream.as_raw_fd()).await
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    log::error!("D-Bus connection failed: {err}");
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Send a method call and wait for its reply
+    pub async fn call(&mut self, msg: Message) -> Result<Message, Error> {
+        let serial = self.send(&msg).ok_or_else(disconnected)?;
+        drop(msg);
+
+        let reply = self
+            .next_message(|reply| (reply.reply_serial == serial).then_some(reply))
+            .await
+            .ok_or_else(disconnected)?;
+
+        if reply.kind == MESSAGE_TYPE_ERROR {
+            let name = reply
+                .error_name
+                .as_deref()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let message = MessageIter::from_msg(&reply)
+                .get_string()
+                .map(|message| message.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            return Err(reply_error(name, message));
+        }
+
+        Ok(reply)
+    }
+}
+
+impl Drop for Connection {
+    /// Messages that don't expect a reply, like closing a request, may still be queued
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+fn send_with_fds(stream: &UnixStream, bytes: &[u8], fds: &[OwnedFd]) -> io::Result<usize> {
+    if fds.is_empty() {
+        return (&*stream).write(bytes);
+    }
+
+    let fds: Vec<RawFd> = fds.iter().map(AsRawFd::as_raw_fd).collect();
+    let payload = std::mem::size_of_val(fds.as_slice()) as u32;
+
+    // `u64`s keep the control messages aligned
+    let space = unsafe { libc::CMSG_SPACE(payload) } as usize;
+    let mut control = vec![0u64; space.div_ceil(8)];
+
+    let mut iov = libc::iovec {
+        iov_base: bytes.as_ptr() as *mut _,
+        iov_len: bytes.len(),
+    };
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr().cast();
+    header.msg_controllen = space as _;
+
+    let written = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&header);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(payload) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg).cast(), fds.len());
+
+        libc::sendmsg(stream.as_raw_fd(), &header, libc::MSG_NOSIGNAL)
+    };
+
+    if written < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(written as usize)
+    }
+}
+
+/// Like `read`, the file descriptors that come along are appended to `fds`
+fn recv_with_fds(
+    stream: &UnixStream,
+    buf: &mut [u8],
+    fds: &mut VecDeque<OwnedFd>,
+) -> io::Result<usize> {
+    let space = unsafe { libc::CMSG_SPACE((MAX_UNIX_FDS * std::mem::size_of::<RawFd>()) as u32) };
+    let mut control = vec![0u64; (space as usize).div_ceil(8)];
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr().cast();
+    header.msg_controllen = space as _;
+
+    let read = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut header, libc::MSG_CMSG_CLOEXEC) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                for id in 0..len / std::mem::size_of::<RawFd>() {
+                    let fd = std::ptr::read_unaligned(data.cast::<RawFd>().add(id));
+                    fds.push_back(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+        }
+    }
+
+    if header.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "received too many file descriptors",
+        ));
+    }
+    Ok(read as usize)
+}
+
+/// Length of the message at the start of `buffer`, `None` until its fixed header was read
+fn message_len(buffer: &[u8]) -> io::Result<Option<usize>> {
+    let Some(header) = buffer.get(..16) else {
+        return Ok(None);
+    };
+
+    let big_endian = match header[0] {
+        b'l' => false,
+        b'B' => true,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "bad endianness")),
+    };
+    let u32_at = |pos: usize| {
+        let bytes = header[pos..pos + 4].try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+
+    let len = 16 + align(u32_at(12) as usize, 8) + u32_at(4) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too long",
+        ));
+    }
+    Ok(Some(len))
+}
+
+fn align(pos: usize, alignment: usize) -> usize {
+    pos.next_multiple_of(alignment)
+}
+
+fn alignment(code: u8) -> usize {
+    match code {
+        b'n' | b'q' => 2,
+        b'b' | b'i' | b'u' | b'h' | b's' | b'o' | b'a' => 4,
+        b'x' | b't' | b'd' | b'(' | b'{' => 8,
+        _ => 1,
+    }
+}
+
+/// Length of the first complete type of `signature`
+fn type_len(signature: &[u8]) -> Option<usize> {
+    match *signature.first()? {
+        b'a' => Some(1 + type_len(&signature[1..])?),
+        open @ (b'(' | b'{') => {
+            let close = if open == b'(' { b')' } else { b'}' };
+            let mut len = 1;
+            while *signature.get(len)? != close {
+                len += type_len(&signature[len..])?;
+            }
+            Some(len + 1)
+        }
+        _ => Some(1),
+    }
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.resize(align(out.len(), 4), 0);
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, value: &[u8]) {
+    put_u32(out, value.len() as u32);
+    out.extend_from_slice(value);
+    out.push(0);
+}
+
+fn put_signature(out: &mut Vec<u8>, value: &[u8]) {
+    out.push(value.len() as u8);
+    out.extend_from_slice(value);
+    out.push(0);
+}
+
+pub struct Message {
+    kind: u8,
+    flags: u8,
+    big_endian: bool,
+    path: Option<CString>,
+    interface: Option<CString>,
+    member: Option<CString>,
+    error_name: Option<CString>,
+    destination: Option<CString>,
+    reply_serial: u32,
+    /// Serial and sender of received messages, only the mock portal of the tests replies to them
+    #[cfg_attr(any(not(test), feature = "testing"), allow(dead_code))]
+    serial: u32,
+    #[cfg_attr(any(not(test), feature = "testing"), allow(dead_code))]
+    sender: Option<CString>,
+    signature: Vec<u8>,
+    body: Vec<u8>,
+    fds: Vec<OwnedFd>,
+}
+
+impl Message {
+    fn new(kind: u8) -> Self {
+        Self {
+            kind,
+            flags: 0,
+            big_endian: false,
+            path: None,
+            interface: None,
+            member: None,
+            error_name: None,
+            destination: None,
+            reply_serial: 0,
+            serial: 0,
+            sender: None,
+            signature: Vec::new(),
+            body: Vec::new(),
+            fds: Vec::new(),
+        }
+    }
+
+    pub fn new_method_call(
+        destination: &CStr,
+        path: &CStr,
+        iface: &CStr,
+        method: &CStr,
+    ) -> Option<Self> {
+        Some(Self {
+            path: Some(path.to_owned()),
+            interface: Some(iface.to_owned()),
+            member: Some(method.to_owned()),
+            destination: Some(destination.to_owned()),
+            ..Self::new(MESSAGE_TYPE_METHOD_CALL)
+        })
+    }
+
+    pub fn is_signal(&self, iface: &CStr, signal_name: &CStr) -> bool {
+        self.kind == MESSAGE_TYPE_SIGNAL
+            && self.interface.as_deref() == Some(iface)
+            && self.member.as_deref() == Some(signal_name)
+    }
+
+    pub fn get_path(&self) -> Option<&CStr> {
+        self.path.as_deref()
+    }
+
+    /// Outgoing messages are always little endian
+    fn serialize(&self, serial: u32) -> Vec<u8> {
+        let mut out = vec![b'l', self.kind, self.flags, 1];
+        put_u32(&mut out, self.body.len() as u32);
+        put_u32(&mut out, serial);
+
+        // Header fields, an `a(yv)` array
+        put_u32(&mut out, 0);
+        let start = out.len();
+
+        let field = |out: &mut Vec<u8>, code: u8, signature: &[u8]| {
+            out.resize(align(out.len(), 8), 0);
+            out.push(code);
+            put_signature(out, signature);
+        };
+
+        let strings = [
+            (FIELD_PATH, b"o", &self.path),
+            (FIELD_INTERFACE, b"s", &self.interface),
+            (FIELD_MEMBER, b"s", &self.member),
+            (FIELD_ERROR_NAME, b"s", &self.error_name),
+            (FIELD_DESTINATION, b"s", &self.destination),
+        ];
+        for (code, signature, value) in strings {
+            if let Some(value) = value {
+                field(&mut out, code, signature);
+                put_string(&mut out, value.as_bytes());
+            }
+        }
+        if self.reply_serial != 0 {
+            field(&mut out, FIELD_REPLY_SERIAL, b"u");
+            put_u32(&mut out, self.reply_serial);
+        }
+        if !self.signature.is_empty() {
+            field(&mut out, FIELD_SIGNATURE, b"g");
+            put_signature(&mut out, &self.signature);
+        }
+        if !self.fds.is_empty() {
+            field(&mut out, FIELD_UNIX_FDS, b"u");
+            put_u32(&mut out, self.fds.len() as u32);
+        }
+
+        let fields_len = (out.len() - start) as u32;
+        out[start - 4..start].copy_from_slice(&fields_len.to_le_bytes());
+
+        out.resize(align(out.len(), 8), 0);
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    /// `bytes` is one whole message, as measured by [`message_len`]. Also returns how many of the
+    /// file descriptors received with it belong to the message.
+    fn parse(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed D-Bus message");
+
+        let big_endian = bytes[0] == b'B';
+        let mut msg = Self {
+            flags: bytes[2],
+            big_endian,
+            ..Self::new(bytes[1])
+        };
+
+        let header = Reader {
+            data: bytes,
+            big_endian,
+            signature: b"a(yv)",
+            pos: 12,
+            array: None,
+        };
+
+        msg.serial = header.u32_at(8).ok_or_else(invalid)?;
+        let mut unix_fds = 0;
+
+        let mut fields = header.recurse();
+        while fields.arg_type() == ffi::DBUS_TYPE_STRUCT {
+            let mut field = fields.recurse();
+            let code = field.byte();
+            field.next();
+            let value = field.recurse();
+
+            match code {
+                Some(FIELD_PATH) => msg.path = value.string(),
+                Some(FIELD_INTERFACE) => msg.interface = value.string(),
+                Some(FIELD_MEMBER) => msg.member = value.string(),
+                Some(FIELD_ERROR_NAME) => msg.error_name = value.string(),
+                Some(FIELD_DESTINATION) => msg.destination = value.string(),
+                Some(FIELD_SENDER) => msg.sender = value.string(),
+                Some(FIELD_REPLY_SERIAL) => msg.reply_serial = value.u32().unwrap_or_default(),
+                Some(FIELD_UNIX_FDS) => unix_fds = value.u32().unwrap_or_default() as usize,
+                Some(FIELD_SIGNATURE) => {
+                    let (signature, _) = value.signature_at(value.pos).ok_or_else(invalid)?;
+                    msg.signature = signature.to_vec();
+                }
+                _ => {}
+            }
+
+            fields.next();
+        }
+
+        let fields_end = 16 + header.u32_at(12).ok_or_else(invalid)? as usize;
+        msg.body = bytes
+            .get(align(fields_end, 8)..)
+            .ok_or_else(invalid)?
+            .to_vec();
+
+        Ok((msg, unix_fds))
+    }
+}
+
+/// Replying to calls and emitting signals, for the mock portal of the tests
+#[cfg(all(test, not(feature = "testing")))]
+impl Message {
+    pub fn is_method_call(&self) -> bool {
+        self.kind == MESSAGE_TYPE_METHOD_CALL
+    }
+
+    pub fn get_interface(&self) -> Option<&CStr> {
+        self.interface.as_deref()
+    }
+
+    pub fn get_member(&self) -> Option<&CStr> {
+        self.member.as_deref()
+    }
+
+    pub fn get_sender(&self) -> Option<&CStr> {
+        self.sender.as_deref()
+    }
+
+    pub fn new_method_return(call: &Message) -> Self {
+        Self {
+            destination: call.sender.clone(),
+            reply_serial: call.serial,
+            ..Self::new(MESSAGE_TYPE_METHOD_RETURN)
+        }
+    }
+
+    pub fn new_error(call: &Message, name: &CStr, message: &CStr) -> Self {
+        let mut msg = Self {
+            error_name: Some(name.to_owned()),
+            destination: call.sender.clone(),
+            reply_serial: call.serial,
+            ..Self::new(MESSAGE_TYPE_ERROR)
+        };
+        MessageIter::init_append(&mut msg).append_string(message);
+        msg
+    }
+
+    pub fn new_signal(path: &CStr, iface: &CStr, name: &CStr) -> Self {
+        Self {
+            path: Some(path.to_owned()),
+            interface: Some(iface.to_owned()),
+            member: Some(name.to_owned()),
+            ..Self::new(MESSAGE_TYPE_SIGNAL)
+        }
+    }
+}
+
+/// Cursor over the values of a message body, or of one of its containers
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+    /// Signature of the current value and the ones after it
+    signature: &'a [u8],
+    pos: usize,
+    /// Element signature and end of the data, when iterating an array
+    array: Option<(&'a [u8], usize)>,
+}
+
+impl<'a> Reader<'a> {
+    fn arg_type(&self) -> c_int {
+        if self.array.is_some_and(|(_, end)| self.pos >= end) {
+            return ffi::DBUS_TYPE_INVALID;
+        }
+
+        match self.signature.first() {
+            None => ffi::DBUS_TYPE_INVALID,
+            Some(b'(') => ffi::DBUS_TYPE_STRUCT,
+            Some(b'{') => ffi::DBUS_TYPE_DICT_ENTRY,
+            Some(code) => *code as c_int,
+        }
+    }
+
+    /// Move to the next value, `false` if there is none
+    fn next(&mut self) -> bool {
+        if self.arg_type() == ffi::DBUS_TYPE_INVALID {
+            return false;
+        }
+
+        match self.skip(self.pos, self.signature) {
+            Some((pos, len)) if pos <= self.data.len() => {
+                self.pos = pos;
+                self.signature = match self.array {
+                    Some((element, _)) => element,
+                    None => &self.signature[len..],
+                };
+            }
+            // Malformed, nothing after it can be read
+            _ => self.signature = b"",
+        }
+
+        self.arg_type() != ffi::DBUS_TYPE_INVALID
+    }
+
+    /// End of the value at `pos` with the type `signature` starts with, and the length of
+    /// that type
+    fn skip(&self, pos: usize, signature: &'a [u8]) -> Option<(usize, usize)> {
+        let code = *signature.first()?;
+        let pos = align(pos, alignment(code));
+
+        let end = match code {
+            b'y' => pos + 1,
+            b'n' | b'q' => pos + 2,
+            b'b' | b'i' | b'u' | b'h' => pos + 4,
+            b'x' | b't' | b'd' => pos + 8,
+            b's' | b'o' => (pos + 5).checked_add(self.u32_at(pos)? as usize)?,
+            b'g' => self.signature_at(pos)?.1,
+            b'a' => {
+                let element = *signature.get(1)?;
+                let start = align(pos + 4, alignment(element));
+                let end = start.checked_add(self.u32_at(pos)? as usize)?;
+                return Some((end, 1 + type_len(&signature[1..])?));
+            }
+            b'(' | b'{' => {
+                let len = type_len(signature)?;
+                let mut pos = pos;
+                let mut field = 1;
+                while field < len - 1 {
+                    let (end, field_len) = self.skip(pos, &signature[field..])?;
+                    pos = end;
+                    field += field_len;
+                }
+                return Some((pos, len));
+            }
+            b'v' => {
+                let (inner, pos) = self.signature_at(pos)?;
+                let (end, len) = self.skip(pos, inner)?;
+                if len != inner.len() {
+                    return None;
+                }
+                end
+            }
+            _ => return None,
+        };
+
+        Some((end, 1))
+    }
+
+    fn recurse(&self) -> Self {
+        let invalid = Self {
+            signature: b"",
+            array: None,
+            ..*self
+        };
+
+        match self.arg_type() as u8 {
+            b'a' => {
+                let pos = align(self.pos, 4);
+                let (Some(len), Some(element_len)) =
+                    (self.u32_at(pos), type_len(&self.signature[1..]))
+                else {
+                    return invalid;
+                };
+                let element = &self.signature[1..1 + element_len];
+                let start = align(pos + 4, alignment(element[0]));
+                Self {
+                    signature: element,
+                    pos: start,
+                    array: Some((element, start.saturating_add(len as usize))),
+                    ..*self
+                }
+            }
+            b'r' | b'e' => match type_len(self.signature) {
+                Some(len) => Self {
+                    signature: &self.signature[1..len - 1],
+                    pos: align(self.pos, 8),
+                    array: None,
+                    ..*self
+                },
+                None => invalid,
+            },
+            b'v' => match self.signature_at(self.pos) {
+                Some((signature, pos)) => Self {
+                    signature,
+                    pos,
+                    array: None,
+                    ..*self
+                },
+                None => invalid,
+            },
+            _ => invalid,
+        }
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let bytes = self.data.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Signature at `pos` and the position after it
+    fn signature_at(&self, pos: usize) -> Option<(&'a [u8], usize)> {
+        let len = *self.data.get(pos)? as usize;
+        let signature = self.data.get(pos + 1..pos + 1 + len)?;
+        Some((signature, pos + len + 2))
+    }
+
+    fn byte(&self) -> Option<u8> {
+        if self.arg_type() != ffi::DBUS_TYPE_BYTE {
+            return None;
+        }
+        self.data.get(self.pos).copied()
+    }
+
+    fn u32(&self) -> Option<u32> {
+        if self.arg_type() != ffi::DBUS_TYPE_UINT32 {
+            return None;
+        }
+        self.u32_at(align(self.pos, 4))
+    }
+
+    /// Strings and object paths
+    fn string(&self) -> Option<CString> {
+        if !matches!(
+            self.arg_type(),
+            ffi::DBUS_TYPE_STRING | ffi::DBUS_TYPE_OBJECT_PATH
+        ) {
+            return None;
+        }
+        let pos = align(self.pos, 4);
+        let len = self.u32_at(pos)? as usize;
+        let bytes = self.data.get(pos + 4..(pos + 4).checked_add(len)?)?;
+        CString::new(bytes).ok()
+    }
+}
+
+struct Container {
+    code: u8,
+    /// Position of the length of an array
+    len_pos: usize,
+    /// Start of the elements of an array
+    start: usize,
+    /// Whether the types of the values in it are part of the message signature
+    signature: bool,
+}
+
+/// Appends values to a message body, the open containers are kept on a stack
+struct Writer<'a> {
+    msg: &'a mut Message,
+    containers: Vec<Container>,
+}
+
+impl Writer<'_> {
+    fn sign(&mut self, signature: &[u8]) {
+        if self.containers.last().is_none_or(|c| c.signature) {
+            self.msg.signature.extend_from_slice(signature);
+        }
+    }
+
+    fn open(&mut self, ty: c_int, signature: Option<&CStr>) {
+        let signature = signature.map(CStr::to_bytes).unwrap_or_default();
+
+        let container = match ty {
+            ffi::DBUS_TYPE_ARRAY => {
+                self.sign(b"a");
+                self.sign(signature);
+                let body = &mut self.msg.body;
+                put_u32(body, 0);
+                let len_pos = body.len() - 4;
+                body.resize(align(body.len(), alignment(signature[0])), 0);
+                Container {
+                    code: b'a',
+                    len_pos,
+                    start: body.len(),
+                    signature: false,
+                }
+            }
+            ffi::DBUS_TYPE_STRUCT | ffi::DBUS_TYPE_DICT_ENTRY => {
+                let code = if ty == ffi::DBUS_TYPE_STRUCT {
+                    b'('
+                } else {
+                    b'{'
+                };
+                let body = &mut self.msg.body;
+                body.resize(align(body.len(), 8), 0);
+                self.sign(&[code]);
+                Container {
+                    code,
+                    len_pos: 0,
+                    start: 0,
+                    signature: self.containers.last().is_none_or(|c| c.signature),
+                }
+            }
+            ffi::DBUS_TYPE_VARIANT => {
+                put_signature(&mut self.msg.body, signature);
+                self.sign(b"v");
+                Container {
+                    code: b'v',
+                    len_pos: 0,
+                    start: 0,
+                    signature: false,
+                }
+            }
+            _ => unreachable!("{ty} is not a container type"),
+        };
+
+        self.containers.push(container);
+    }
+
+    fn close(&mut self) {
+        let container = self.containers.pop().unwrap();
+        match container.code {
+            b'a' => {
+                let len = (self.msg.body.len() - container.start) as u32;
+                self.msg.body[container.len_pos..container.len_pos + 4]
+                    .copy_from_slice(&len.to_le_bytes());
+            }
+            b'(' => self.sign(b")"),
+            b'{' => self.sign(b"}"),
+            _ => {}
+        }
+    }
+
+    fn append_u32(&mut self, code: u8, value: u32) {
+        self.sign(&[code]);
+        put_u32(&mut self.msg.body, value);
+    }
+}
+
+enum Iter<'a> {
+    Read(Reader<'a>),
+    Append(Writer<'a>),
+}
+
+/// Reads a received message, or appends to one that is about to be sent, like
+/// `DBusMessageIter`
+pub struct MessageIter<'a>(Iter<'a>);
+
+impl<'a> MessageIter<'a> {
+    pub fn from_msg(msg: &'a Message) -> Self {
+        Self(Iter::Read(Reader {
+            data: &msg.body,
+            big_endian: msg.big_endian,
+            signature: &msg.signature,
+            pos: 0,
+            array: None,
+        }))
+    }
+
+    pub fn init_append(msg: &'a mut Message) -> Self {
+        Self(Iter::Append(Writer {
+            msg,
+            containers: Vec::new(),
+        }))
+    }
+
+    fn reader(&mut self) -> &mut Reader<'a> {
+        match &mut self.0 {
+            Iter::Read(reader) => reader,
+            Iter::Append(_) => unreachable!("reading from a message that is being built"),
+        }
+    }
+
+    fn writer(&mut self) -> &mut Writer<'a> {
+        match &mut self.0 {
+            Iter::Append(writer) => writer,
+            Iter::Read(_) => unreachable!("appending to a received message"),
+        }
+    }
+
+    pub fn iter_recurse(&mut self) -> Self {
+        Self(Iter::Read(self.reader().recurse()))
+    }
+
+    pub fn next(&mut self) -> bool {
+        self.reader().next()
+    }
+
+    pub fn get_arg_type(&mut self) -> c_int {
+        self.reader().arg_type()
+    }
+
+    pub fn get_u32(&mut self) -> Option<u32> {
+        self.reader().u32()
+    }
+
+    pub fn get_object_path(&mut self) -> Option<CString> {
+        if self.get_arg_type() != ffi::DBUS_TYPE_OBJECT_PATH {
+            return None;
+        }
+        self.reader().string()
+    }
+
+    pub fn get_string(&mut self) -> Option<CString> {
+        if self.get_arg_type() != ffi::DBUS_TYPE_STRING {
+            return None;
+        }
+        self.reader().string()
+    }
+
+    pub fn get_byte_array(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        if self.get_arg_type() == ffi::DBUS_TYPE_ARRAY {
+            let mut array_iter = self.reader().recurse();
+            while let Some(byte) = array_iter.byte() {
+                out.push(byte);
+                array_iter.next();
+            }
+        }
+
+        out
+    }
+
+    #[cfg(not(feature = "testing"))]
+    pub fn get_string_array(&mut self) -> Vec<CString> {
+        let mut out = Vec::new();
+
+        if self.get_arg_type() == ffi::DBUS_TYPE_ARRAY {
+            let mut array_iter = self.iter_recurse();
+            while array_iter.get_arg_type() == ffi::DBUS_TYPE_STRING {
+                if let Some(item) = array_iter.get_string() {
+                    out.push(item);
+                } else {
+                    log::error!("Wrong type in a string array")
+                }
+                array_iter.next();
+            }
+        }
+
+        out
+    }
+
+    pub fn append_string(&mut self, value: &CStr) {
+        let writer = self.writer();
+        writer.sign(b"s");
+        put_string(&mut writer.msg.body, value.to_bytes());
+    }
+
+    #[cfg(any(test, not(feature = "testing")))]
+    pub fn append_byte(&mut self, value: u8) {
+        let writer = self.writer();
+        writer.sign(b"y");
+        writer.msg.body.push(value);
+    }
+
+    #[cfg(any(test, not(feature = "testing")))]
+    pub fn append_u32(&mut self, value: u32) {
+        self.writer().append_u32(b'u', value);
+    }
+
+    /// `fd` is duplicated, it can be closed once the message is built
+    pub fn append_fd(&mut self, fd: RawFd) {
+        let writer = self.writer();
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned();
+        match fd {
+            Ok(fd) => {
+                writer.msg.fds.push(fd);
+                let index = writer.msg.fds.len() as u32 - 1;
+                writer.append_u32(b'h', index);
+            }
+            Err(err) => {
+                // The call then fails with an invalid fd index, instead of a panic
+                log::error!("Failed to duplicate a file descriptor: {err}");
+                writer.append_u32(b'h', u32::MAX);
+            }
+        }
+    }
+
+    pub fn append_bool(&mut self, value: bool) {
+        self.writer().append_u32(b'b', value as u32);
+    }
+
+    #[cfg(all(test, not(feature = "testing")))]
+    pub fn append_object_path(&mut self, value: &CStr) {
+        let writer = self.writer();
+        writer.sign(b"o");
+        put_string(&mut writer.msg.body, value.to_bytes());
+    }
+
+    #[cfg(all(test, not(feature = "testing")))]
+    pub fn get_bool(&mut self) -> Option<bool> {
+        if self.get_arg_type() != ffi::DBUS_TYPE_BOOLEAN {
+            return None;
+        }
+        let reader = self.reader();
+        reader.u32_at(align(reader.pos, 4)).map(|value| value != 0)
+    }
+
+    pub fn with_container<T>(
+        &mut self,
+        ty: c_int,
+        signature: Option<&CStr>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.writer().open(ty, signature);
+        let res = f(self);
+        self.writer().close();
+        res
+    }
+
+    pub fn with_dict_entry<T>(
+        &mut self,
+        key: &CStr,
+        variant_signature: &CStr,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.with_container(ffi::DBUS_TYPE_DICT_ENTRY, None, |entry| {
+            entry.append_string(key);
+            entry.with_container(ffi::DBUS_TYPE_VARIANT, Some(variant_signature), |variant| {
+                f(variant)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ffi, message_len, recv_with_fds, send_with_fds, Message, MessageIter};
+    use std::{collections::VecDeque, os::unix::net::UnixStream};
+
+    fn round_trip(msg: &Message) -> Message {
+        let bytes = msg.serialize(7);
+        assert_eq!(message_len(&bytes).unwrap(), Some(bytes.len()));
+        Message::parse(&bytes).unwrap().0
+    }
+
+    #[test]
+    fn marshal_portal_options() {
+        let mut msg = Message::new_method_call(
+            c"org.freedesktop.portal.Desktop",
+            c"/org/freedesktop/portal/desktop",
+            c"org.freedesktop.portal.FileChooser",
+            c"OpenFile",
+        )
+        .unwrap();
+
+        {
+            let mut iter = MessageIter::init_append(&mut msg);
+            iter.append_string(c"");
+            iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"{sv}"), |dict| {
+                dict.with_dict_entry(c"multiple", c"b", |variant| variant.append_bool(true));
+                dict.with_dict_entry(c"filters", c"a(sa(us))", |variant| {
+                    variant.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(sa(us))"), |array| {
+                        array.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+                            s.append_string(c"Text");
+                            s.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(us)"), |array| {
+                                array.with_container(ffi::DBUS_TYPE_STRUCT, None, |s| {
+                                    s.append_u32(0);
+                                    s.append_string(c"*.txt");
+                                });
+                            });
+                        });
+                    });
+                });
+                dict.with_dict_entry(c"current_folder", c"ay", |variant| {
+                    variant.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"y"), |array| {
+                        for byte in c"/tmp".to_bytes_with_nul() {
+                            array.append_byte(*byte);
+                        }
+                    });
+                });
+            });
+        }
+        assert_eq!(msg.signature, b"sa{sv}");
+
+        let msg = round_trip(&msg);
+        assert_eq!(msg.member.as_deref(), Some(c"OpenFile"));
+        assert_eq!(msg.get_path(), Some(c"/org/freedesktop/portal/desktop"));
+
+        let mut iter = MessageIter::from_msg(&msg);
+        assert_eq!(iter.get_string().as_deref(), Some(c""));
+        assert!(iter.next());
+
+        let mut dict = iter.iter_recurse();
+        let mut entries = Vec::new();
+        while dict.get_arg_type() == ffi::DBUS_TYPE_DICT_ENTRY {
+            let mut entry = dict.iter_recurse();
+            let key = entry.get_string().unwrap();
+            entry.next();
+            let mut variant = entry.iter_recurse();
+            let value = match key.to_bytes() {
+                b"multiple" => format!("{}", variant.get_arg_type() == ffi::DBUS_TYPE_BOOLEAN),
+                b"filters" => {
+                    let mut filter = variant.iter_recurse().iter_recurse();
+                    let name = filter.get_string().unwrap();
+                    filter.next();
+                    let mut glob = filter.iter_recurse().iter_recurse();
+                    let kind = glob.get_u32();
+                    glob.next();
+                    format!("{name:?} {kind:?} {:?}", glob.get_string().unwrap())
+                }
+                _ => format!("{:?}", variant.get_byte_array()),
+            };
+            entries.push(format!("{key:?}: {value}"));
+            dict.next();
+        }
+        assert!(!iter.next());
+
+        assert_eq!(
+            entries,
+            [
+                r#""multiple": true"#,
+                r#""filters": "Text" Some(0) "*.txt""#,
+                r#""current_folder": [47, 116, 109, 112, 0]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_array() {
+        let mut msg = Message::new_method_call(c"a.b", c"/a", c"a.b", c"C").unwrap();
+        {
+            let mut iter = MessageIter::init_append(&mut msg);
+            iter.with_container(ffi::DBUS_TYPE_ARRAY, Some(c"(us)"), |_| {});
+            iter.append_u32(5);
+        }
+
+        let msg = round_trip(&msg);
+        let mut iter = MessageIter::from_msg(&msg);
+        assert_eq!(iter.iter_recurse().get_arg_type(), ffi::DBUS_TYPE_INVALID);
+        assert!(iter.next());
+        assert_eq!(iter.get_u32(), Some(5));
+    }
+
+    #[test]
+    fn pass_fds() {
+        let file = std::fs::File::open("/dev/null").unwrap();
+        let mut msg = Message::new_method_call(c"a.b", c"/a", c"a.b", c"C").unwrap();
+        MessageIter::init_append(&mut msg).append_fd(std::os::fd::AsRawFd::as_raw_fd(&file));
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let bytes = msg.serialize(7);
+        assert_eq!(
+            send_with_fds(&sender, &bytes, &msg.fds).unwrap(),
+            bytes.len()
+        );
+
+        let mut buf = vec![0; bytes.len()];
+        let mut fds = VecDeque::new();
+        assert_eq!(
+            recv_with_fds(&receiver, &mut buf, &mut fds).unwrap(),
+            bytes.len()
+        );
+        assert_eq!(fds.len(), 1);
+
+        let (msg, unix_fds) = Message::parse(&buf).unwrap();
+        assert_eq!(unix_fds, 1);
+        assert_eq!(msg.signature, b"h");
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, future::Future, pin::Pin};
 
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
@@ -21,25 +21,29 @@ impl std::fmt::Display for WindowIdentifier {
 }
 
 impl WindowIdentifier {
-    pub async fn from_raw_handle(
+    /// The raw handles are not `Send`, so they are only read before the returned future is
+    /// polled, which lets it be awaited from any thread.
+    pub fn from_raw_handle(
         window_handle: &RawWindowHandle,
         display_handle: Option<&RawDisplayHandle>,
-    ) -> Option<Self> {
+    ) -> Pin<Box<dyn Future<Output = Option<Self>> + Send>> {
         use raw_window_handle::RawWindowHandle::{Xcb, Xlib};
         #[cfg(feature = "wayland")]
         use raw_window_handle::{
             RawDisplayHandle::Wayland as DisplayHandle, RawWindowHandle::Wayland,
         };
-        match (window_handle, display_handle) {
+        let identifier = match (window_handle, display_handle) {
             #[cfg(feature = "wayland")]
-            (Wayland(wl_handle), Some(DisplayHandle(wl_display))) => unsafe {
-                Self::from_wayland_raw(wl_handle.surface.as_ptr(), wl_display.display.as_ptr())
-                    .await
-            },
+            (Wayland(wl_handle), Some(DisplayHandle(wl_display))) => {
+                return unsafe {
+                    Self::from_wayland_raw(wl_handle.surface.as_ptr(), wl_display.display.as_ptr())
+                };
+            }
             (Xlib(x_handle), _) => Some(Self::from_xid(x_handle.window)),
             (Xcb(x_handle), _) => Some(Self::from_xid(x_handle.window.get().into())),
             _ => None,
-        }
+        };
+        Box::pin(std::future::ready(identifier))
     }
 
     pub fn from_xid(xid: std::os::raw::c_ulong) -> Self {
@@ -47,13 +51,12 @@ impl WindowIdentifier {
     }
 
     #[cfg(feature = "wayland")]
-    pub async unsafe fn from_wayland_raw(
+    pub unsafe fn from_wayland_raw(
         surface_ptr: *mut std::ffi::c_void,
         display_ptr: *mut std::ffi::c_void,
-    ) -> Option<Self> {
-        WaylandWindowIdentifier::from_raw(surface_ptr, display_ptr)
-            .await
-            .map(Self::Wayland)
+    ) -> Pin<Box<dyn Future<Output = Option<Self>> + Send>> {
+        let identifier = unsafe { WaylandWindowIdentifier::from_raw(surface_ptr, display_ptr) };
+        Box::pin(async move { identifier?.await.map(Self::Wayland) })
     }
 }
 
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, future::Future};
 
 use wayland_backend::sys::client::Backend;
 use wayland_client::{
@@ -22,10 +22,11 @@ pub struct WaylandWindowIdentifier {
 }
 
 impl WaylandWindowIdentifier {
-    pub async unsafe fn from_raw(
+    /// Returns the export request, the raw pointers are not used once this returns
+    pub unsafe fn from_raw(
         surface_ptr: *mut std::ffi::c_void,
         display_ptr: *mut std::ffi::c_void,
-    ) -> Option<Self> {
+    ) -> Option<impl Future<Output = Option<Self>> + Send> {
         if surface_ptr.is_null() || display_ptr.is_null() {
             return None;
         }
@@ -40,13 +41,12 @@ impl WaylandWindowIdentifier {
 
         let surface = WlSurface::from_id(&conn, obj_id).ok()?;
 
-        Self::new_inner(conn, &surface).await
+        Some(Self::new_inner(conn, surface))
     }
 
-    async fn new_inner(conn: wayland_client::Connection, surface: &WlSurface) -> Option<Self> {
+    async fn new_inner(conn: wayland_client::Connection, surface: WlSurface) -> Option<Self> {
         let (sender, receiver) = crate::oneshot::channel::<Option<WaylandWindowIdentifier>>();
 
-        let surface = surface.clone();
         std::thread::spawn(move || match wayland_export_handle(conn, &surface) {
             Some(window_handle) => sender.send(Some(window_handle)).unwrap(),
             None => {
@@ -0,0 +1,249 @@
+//! Which options of the file dialogs the backend in use actually honours.
+
+use crate::Backend;
+
+/// Option or dialog of [`crate::FileDialog`] that is not supported everywhere, see
+/// [`capabilities`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    /// `add_filter` and `add_custom_filter`
+    Filters,
+    /// `add_mime_filter`
+    MimeFilters,
+    /// `set_current_filter`, and the filter chosen by the user in `FileResponse::filter`
+    CurrentFilter,
+    /// `add_choice`
+    Choices,
+    /// `set_title`
+    Title,
+    /// `set_accept_label`
+    AcceptLabel,
+    /// `set_directory`
+    StartingDirectory,
+    /// `set_file_name` and `set_existing_file`
+    FileName,
+    /// `set_parent`
+    Parent,
+    /// `set_can_create_directories`
+    CanCreateDirectories,
+    /// `set_show_hidden_files`
+    ShowHiddenFiles,
+    /// `set_format_label`
+    FormatLabel,
+    /// `pick_folders`, selecting several folders at once
+    PickFolders,
+    /// `pick_file_or_folder` and `pick_files_or_folders`
+    PickFileOrFolder,
+    /// Closing the dialog with `DialogHandle`, `set_timeout` or by dropping its future
+    Close,
+}
+
+/// Options honoured by the dialog backend in use, returned by [`capabilities`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    backend: Option<Backend>,
+    /// FileChooser version of the portal, or major version of zenity
+    version: Option<u32>,
+}
+
+impl Capabilities {
+    /// Linux & BSDs backend the next file dialog will use.
+    ///
+    /// `None` on other platforms, or when none of the backends is available.
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+
+    /// Version of the `org.freedesktop.portal.FileChooser` interface, if the portal is in use
+    pub fn portal_version(&self) -> Option<u32> {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        if self.backend != Some(Backend::XdgPortal) {
+            return None;
+        }
+
+        self.version
+    }
+
+    /// Whether `feature` is honoured, options that are not are silently ignored by the dialog
+    pub fn supports(&self, feature: Feature) -> bool {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        return match self.backend {
+            Some(backend) => linux_supports(backend, self.version.unwrap_or(0), feature),
+            None => false,
+        };
+
+        #[cfg(target_os = "macos")]
+        return matches!(
+            feature,
+            Feature::Filters
+                | Feature::Title
+                | Feature::StartingDirectory
+                | Feature::FileName
+                | Feature::Parent
+                | Feature::CanCreateDirectories
+                | Feature::ShowHiddenFiles
+                | Feature::FormatLabel
+                | Feature::PickFolders
+                | Feature::PickFileOrFolder
+        );
+
+        #[cfg(target_os = "windows")]
+        return matches!(
+            feature,
+            Feature::Filters
+                | Feature::Title
+                | Feature::StartingDirectory
+                | Feature::FileName
+                | Feature::Parent
+                | Feature::ShowHiddenFiles
+                | Feature::PickFolders
+        );
+
+        #[cfg(target_arch = "wasm32")]
+        return matches!(
+            feature,
+            Feature::Filters | Feature::MimeFilters | Feature::Title | Feature::Close
+        );
+
+        #[allow(unreachable_code)]
+        {
+            let _ = feature;
+            false
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn linux_supports(backend: Backend, version: u32, feature: Feature) -> bool {
+    use Feature::*;
+
+    match backend {
+        Backend::XdgPortal => match feature {
+            Filters | MimeFilters | CurrentFilter | Choices | Title | AcceptLabel | FileName
+            | Parent | Close => true,
+            // `directory` of OpenFile
+            PickFolders => version >= 3,
+            // `current_folder` of OpenFile, SaveFile always had it
+            StartingDirectory => version >= 4,
+            _ => false,
+        },
+        Backend::Gtk3 => matches!(
+            feature,
+            Filters
+                | MimeFilters
+                | CurrentFilter
+                | Choices
+                | Title
+                | AcceptLabel
+                | StartingDirectory
+                | FileName
+                | Parent
+                | ShowHiddenFiles
+                | PickFolders
+                | Close
+        ),
+        Backend::Zenity => match feature {
+            Filters | MimeFilters | CurrentFilter | Title | AcceptLabel | StartingDirectory
+            | FileName | PickFolders | Close => true,
+            // `--attach`, gone since zenity 4
+            Parent => (1..4).contains(&version),
+            _ => false,
+        },
+        Backend::Kdialog => matches!(
+            feature,
+            Filters | MimeFilters | CurrentFilter | Title | StartingDirectory | FileName | Close
+        ),
+    }
+}
+
+/// Options honoured by the dialog backend that is in use right now.
+///
+/// On Linux & BSDs this probes the backends of [`crate::set_backends`] in order, eg. asks the
+/// XDG portal for its version or looks for zenity in `PATH`, so UIs can hide options the native
+/// dialog can't show and offer their own fallbacks instead.
+///
+/// ```no_run
+/// use rfd::Feature;
+///
+/// let capabilities = rfd::capabilities();
+/// if !capabilities.supports(Feature::ShowHiddenFiles) {
+///     // Filter hidden files in the app instead
+/// }
+/// ```
+pub fn capabilities() -> Capabilities {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    if let Some((backend, version)) = crate::backend::active_backend() {
+        return Capabilities {
+            backend: Some(backend),
+            version,
+        };
+    }
+
+    Capabilities {
+        backend: None,
+        version: None,
+    }
+}
+
+#[cfg(all(
+    test,
+    any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )
+))]
+mod tests {
+    use super::{Capabilities, Feature};
+    use crate::Backend;
+
+    #[test]
+    fn supports() {
+        let capabilities = |backend, version| Capabilities { backend, version };
+
+        let old_portal = capabilities(Some(Backend::XdgPortal), Some(2));
+        assert!(old_portal.supports(Feature::Choices));
+        assert!(!old_portal.supports(Feature::PickFolders));
+        assert!(capabilities(Some(Backend::XdgPortal), Some(4)).supports(Feature::PickFolders));
+
+        let zenity = capabilities(Some(Backend::Zenity), None);
+        assert!(zenity.supports(Feature::CurrentFilter));
+        assert!(!zenity.supports(Feature::ShowHiddenFiles));
+        assert!(!zenity.supports(Feature::Parent));
+        assert!(capabilities(Some(Backend::Zenity), Some(3)).supports(Feature::Parent));
+        assert!(!capabilities(Some(Backend::Zenity), Some(4)).supports(Feature::Parent));
+        assert_eq!(
+            capabilities(Some(Backend::Zenity), Some(3)).portal_version(),
+            None
+        );
+
+        assert!(!capabilities(None, None).supports(Feature::Filters));
+    }
+}
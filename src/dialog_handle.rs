@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use crate::timer::{self, Sleep};
+use crate::Error;
+
+#[derive(Debug, Default)]
+struct State {
+    closed: bool,
+    next_id: u64,
+    /// Wakers of the dialogs waiting on this handle
+    wakers: HashMap<u64, Waker>,
+}
+
+/// Handle closing async dialogs from outside of their future.
+///
+/// Attach it with [`AsyncFileDialog::set_handle`](crate::AsyncFileDialog::set_handle) or
+/// [`AsyncMessageDialog::set_handle`](crate::AsyncMessageDialog::set_handle), then call
+/// [`DialogHandle::close`] to close the dialog, eg. when the document it was opened for gets
+/// closed. The dialog future then resolves to `None`, or `MessageDialogResult::Cancel` for a
+/// message dialog.
+///
+/// Dropping the future of a dialog closes it as well, a handle is only needed when the future
+/// itself is not at hand.
+///
+/// Closing the native dialog is supported on: Linux. On other platforms only the future
+/// resolves, the dialog stays open until the user answers it.
+///
+/// ```no_run
+/// use rfd::{AsyncFileDialog, DialogHandle};
+///
+/// let handle = DialogHandle::new();
+///
+/// let future = AsyncFileDialog::new().set_handle(&handle).save_file();
+///
+/// // Later, from anywhere
+/// handle.close();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DialogHandle {
+    state: Arc<Mutex<State>>,
+}
+
+impl DialogHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Close the dialogs attached to this handle.
+    ///
+    /// A closed handle stays closed, dialogs attached to it later resolve to `None` right away.
+    pub fn close(&self) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            std::mem::take(&mut state.wakers)
+        };
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    /// Whether [`DialogHandle::close`] was called
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+/// Why a dialog future resolved before the user answered the dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Interrupted {
+    /// The [`DialogHandle`] was closed
+    Closed,
+    /// The timeout elapsed
+    TimedOut,
+}
+
+impl Interrupted {
+    /// Result of a file dialog that got interrupted, a closed dialog counts as cancelled
+    pub(crate) fn into_result<T>(self) -> Result<Option<T>, Error> {
+        match self {
+            Self::Closed => Ok(None),
+            Self::TimedOut => Err(Error::TimedOut),
+        }
+    }
+}
+
+/// Resolve `future`, or drop it once `handle` gets closed or `timeout` elapses
+pub(crate) fn cancellable<F: Future>(
+    handle: Option<DialogHandle>,
+    timeout: Option<Duration>,
+    future: F,
+) -> impl Future<Output = Result<F::Output, Interrupted>> {
+    Cancellable {
+        future: Some(Box::pin(future)),
+        handle,
+        id: None,
+        timeout,
+        sleep: None,
+    }
+}
+
+struct Cancellable<F> {
+    /// Dropped as soon as the result is known, closing the dialog
+    future: Option<Pin<Box<F>>>,
+    handle: Option<DialogHandle>,
+    id: Option<u64>,
+    timeout: Option<Duration>,
+    /// Started on first poll, together with the dialog
+    sleep: Option<Sleep>,
+}
+
+impl<F: Future> Future for Cancellable<F> {
+    type Output = Result<F::Output, Interrupted>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let res = this.poll_inner(cx);
+        if res.is_ready() {
+            this.future = None;
+            this.sleep = None;
+        }
+        res
+    }
+}
+
+impl<F: Future> Cancellable<F> {
+    fn poll_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<F::Output, Interrupted>> {
+        let future = self
+            .future
+            .as_mut()
+            .expect("`Cancellable` polled after completion");
+
+        if let Some(handle) = &self.handle {
+            let mut state = handle.state.lock().unwrap();
+            if state.closed {
+                return Poll::Ready(Err(Interrupted::Closed));
+            }
+
+            let id = *self.id.get_or_insert_with(|| {
+                state.next_id += 1;
+                state.next_id
+            });
+            state.wakers.insert(id, cx.waker().clone());
+        }
+
+        if let Some(timeout) = self.timeout {
+            let sleep = self.sleep.get_or_insert_with(|| timer::sleep(timeout));
+            if Pin::new(sleep).poll(cx).is_ready() {
+                return Poll::Ready(Err(Interrupted::TimedOut));
+            }
+        }
+
+        future.as_mut().poll(cx).map(Ok)
+    }
+}
+
+impl<F> Drop for Cancellable<F> {
+    fn drop(&mut self) {
+        if let (Some(handle), Some(id)) = (&self.handle, self.id) {
+            handle.state.lock().unwrap().wakers.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cancellable, DialogHandle, Interrupted};
+    use std::future::Future;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    #[test]
+    fn close() {
+        let handle = DialogHandle::new();
+
+        let res = futures::executor::block_on(async {
+            let close = async {
+                handle.close();
+                Some(())
+            };
+            futures::join!(
+                cancellable(Some(handle.clone()), None, std::future::pending::<()>()),
+                close
+            )
+        });
+
+        assert_eq!(res, (Err(Interrupted::Closed), Some(())));
+        assert!(handle.is_closed());
+    }
+
+    #[test]
+    fn resolves() {
+        let handle = DialogHandle::new();
+        let res = futures::executor::block_on(cancellable(Some(handle), None, async { 1 }));
+        assert_eq!(res, Ok(1));
+    }
+
+    #[test]
+    fn drops_future_once_ready() {
+        struct Dialog(Arc<AtomicBool>);
+        impl Drop for Dialog {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let dialog = Dialog(dropped.clone());
+        let handle = DialogHandle::new();
+        let mut future = Box::pin(cancellable(Some(handle.clone()), None, async move {
+            std::future::pending::<()>().await;
+            drop(dialog);
+        }));
+
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        handle.close();
+        assert_eq!(
+            future.as_mut().poll(&mut cx),
+            Poll::Ready(Err(Interrupted::Closed))
+        );
+        // The dialog is closed right away, not only once the future gets dropped
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn timeout() {
+        let res = futures::executor::block_on(cancellable(
+            None,
+            Some(Duration::from_millis(10)),
+            std::future::pending::<()>(),
+        ));
+        assert_eq!(res, Err(Interrupted::TimedOut));
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt::{Display, Formatter};
+
+/// Reason a dialog did not return an answer of the user, see `FileDialog::try_pick_file`
+/// and similar methods.
+///
+/// A dialog cancelled by the user is not an error, it results in `Ok(None)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// No dialog could be shown, eg. neither an XDG Desktop Portal nor Zenity is installed
+    BackendUnavailable(String),
+    /// The XDG Desktop Portal returned an error
+    Portal {
+        /// D-Bus error name, eg. `org.freedesktop.DBus.Error.InvalidArgs`
+        name: String,
+        message: String,
+    },
+    /// The helper process showing the dialog (eg. Zenity), or opening a file (eg. `xdg-open`) failed
+    Helper(String),
+    /// The dialog was configured with options that can't be shown, eg. a filter index
+    /// out of range
+    InvalidOptions(String),
+    /// The timeout set with `set_timeout` elapsed, and the dialog was closed
+    TimedOut,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BackendUnavailable(reason) => write!(f, "No dialog backend available: {reason}"),
+            Self::Portal { name, message } => write!(f, "XDG portal error {name}: {message}"),
+            Self::Helper(reason) => write!(f, "Dialog helper failed: {reason}"),
+            Self::InvalidOptions(reason) => write!(f, "Invalid dialog options: {reason}"),
+            Self::TimedOut => write!(f, "The dialog timed out"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Result of the infallible API, errors are only logged
+pub(crate) fn log_error<T>(res: Result<Option<T>, Error>) -> Option<T> {
+    res.unwrap_or_else(|err| {
+        match err {
+            // Asked for by the user, not worth an error
+            Error::TimedOut => log::debug!("{err}"),
+            err => log::error!("{err}"),
+        }
+        None
+    })
+}
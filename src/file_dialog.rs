@@ -1,15 +1,246 @@
+use crate::dialog_handle::cancellable;
+use crate::error::log_error;
+use crate::DialogHandle;
+use crate::Error;
 use crate::FileHandle;
 
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
 
+/// File filter, added with `FileDialog::add_custom_filter`.
+///
+/// A file matches the filter if it matches any of its extensions, glob patterns or MIME types.
+///
+/// ```no_run
+/// let filter = rfd::Filter::new("Build files")
+///     .pattern("Makefile")
+///     .pattern("*.config.json")
+///     .extension("cmake");
+///
+/// let path = rfd::FileDialog::new().add_custom_filter(filter).pick_file();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub(crate) name: String,
+    pub(crate) extensions: Vec<String>,
+    pub(crate) patterns: Vec<String>,
+    pub(crate) mime_types: Vec<String>,
+}
+
+impl Filter {
+    /// New filter with the given display name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Match files with the given extension, eg. `"png"`
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Match file names against a glob pattern, eg. `"Makefile"`, `"*.config.json"` or `"backup-*.tar"`.
+    ///
+    /// Supports `*`, `?` and `[...]` wildcards.
+    ///
+    /// Patterns are passed as they are to Linux and Windows dialogs.
+    /// On Mac and WASM32 the dialog can only filter by extension, so it shows a superset of
+    /// the matching files and picked files that don't match are discarded afterwards.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Match files with the given MIME type, eg. `"image/*"`, see `FileDialog::add_mime_filter`
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_types.push(mime_type.into());
+        self
+    }
+
+    /// Display name of the filter
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Extensions, without the leading dot
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Glob patterns, eg. `"*.config.json"`
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// MIME types, eg. `"image/*"`
+    pub fn mime_types(&self) -> &[String] {
+        &self.mime_types
+    }
+
+    /// Whether the file name of `path` matches the extensions or patterns of the filter.
+    ///
+    /// Matching is ASCII case insensitive. MIME types are not taken into account.
+    pub fn matches(&self, path: impl AsRef<Path>) -> bool {
+        let Some(name) = path.as_ref().file_name() else {
+            return false;
+        };
+        let name = name.to_string_lossy();
+        let name = name.as_bytes();
+
+        self.extensions.iter().any(|ext| {
+            ext == "*" || ext.is_empty() || glob_matches(format!("*.{ext}").as_bytes(), name)
+        }) || self
+            .patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern.as_bytes(), name))
+    }
+
+    /// Extensions covering every file matched by the filter, for dialogs that can only filter
+    /// by extension.
+    ///
+    /// `None` if a pattern can't be narrowed down to an extension (eg. `Makefile`),
+    /// in which case every file has to be shown.
+    #[cfg(any(target_os = "macos", target_arch = "wasm32"))]
+    pub(crate) fn approximate_extensions(&self) -> Option<Vec<String>> {
+        let mut extensions = self.extensions.clone();
+
+        for pattern in &self.patterns {
+            let (_, ext) = pattern.rsplit_once('.')?;
+            if ext.is_empty() || ext.contains(['*', '?', '[', ']']) {
+                return None;
+            }
+            extensions.push(ext.to_owned());
+        }
+
+        Some(extensions)
+    }
+}
+
+/// Whether a picked file matches one of the filters, for backends that can't express every
+/// pattern natively and have to check the selection afterwards.
+///
+/// Filters with MIME types can't be checked from the path, so they accept everything.
+#[cfg(any(target_os = "macos", target_arch = "wasm32"))]
+pub(crate) fn filters_accept(filters: &[Filter], path: &Path) -> bool {
+    filters.is_empty()
+        || filters
+            .iter()
+            .any(|filter| !filter.mime_types.is_empty() || filter.matches(path))
+}
+
+/// ASCII case insensitive match of a file name against a glob with `*`, `?` and `[...]`
+pub(crate) fn glob_matches(glob: &[u8], name: &[u8]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(glob, &name[1..]))
+        }
+        Some((b'?', rest)) => !name.is_empty() && glob_matches(rest, &name[1..]),
+        Some((b'[', rest)) => {
+            let Some((c, name)) = name.split_first() else {
+                return false;
+            };
+            let (negate, set) = match rest.first() {
+                Some(b'!' | b'^') => (true, &rest[1..]),
+                _ => (false, rest),
+            };
+            // `]` right after `[` (or `[!`) is part of the set
+            let Some(end) = set.iter().skip(1).position(|b| *b == b']').map(|i| i + 1) else {
+                // Unterminated set, `[` is matched literally
+                return *c == b'[' && glob_matches(rest, name);
+            };
+            let (set, rest) = (&set[..end], &set[end + 1..]);
+
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    let range = set[i].to_ascii_lowercase()..=set[i + 2].to_ascii_lowercase();
+                    found |= range.contains(&c.to_ascii_lowercase());
+                    i += 3;
+                } else {
+                    found |= set[i].eq_ignore_ascii_case(c);
+                    i += 1;
+                }
+            }
+
+            found != negate && glob_matches(rest, name)
+        }
+        Some((g, rest)) => name
+            .split_first()
+            .is_some_and(|(n, name)| g.eq_ignore_ascii_case(n) && glob_matches(rest, name)),
+    }
+}
+
 #[derive(Debug, Clone)]
-pub(crate) struct Filter {
-    #[allow(dead_code)]
-    pub name: String,
-    pub extensions: Vec<String>,
+pub(crate) struct Choice {
+    pub id: String,
+    pub label: String,
+    /// `(id, label)` pairs, empty for a checkbox
+    pub options: Vec<(String, String)>,
+    pub default: String,
+}
+
+/// Selection returned by the `*_with_response` dialog methods.
+///
+/// Besides the picked path(s) it carries the state the dialog was in when the user accepted it.
+#[derive(Debug, Clone)]
+pub struct FileResponse<T> {
+    /// Picked path(s), or file handle(s) in case of `AsyncFileDialog`
+    pub selection: T,
+    /// Index of the filter that was active when the dialog was accepted,
+    /// in the order they were added with `add_filter`.
+    ///
+    /// `None` if no filter was active or the platform can't report it.
+    pub filter: Option<usize>,
+    /// `(id, value)` of every choice added with `add_choice`.
+    ///
+    /// On platforms that can't show choices these are the default values.
+    pub choices: Vec<(String, String)>,
+}
+
+impl<T> FileResponse<T> {
+    pub(crate) fn new(selection: T) -> Self {
+        Self {
+            selection,
+            filter: None,
+            choices: Vec::new(),
+        }
+    }
+
+    /// Value of the choice with the given id, see `add_choice`
+    pub fn choice(&self, id: &str) -> Option<&str> {
+        self.choices
+            .iter()
+            .find(|(choice, _)| choice == id)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn map<U>(self, f: impl FnOnce(T) -> U) -> FileResponse<U> {
+        FileResponse {
+            selection: f(self.selection),
+            filter: self.filter,
+            choices: self.choices,
+        }
+    }
+
+    /// Fill in the default value of every choice the backend did not report
+    fn with_default_choices(mut self, choices: &[Choice]) -> Self {
+        for choice in choices {
+            if self.choice(&choice.id).is_none() {
+                self.choices
+                    .push((choice.id.clone(), choice.default.clone()));
+            }
+        }
+        self
+    }
 }
 
 /// Synchronous File Dialog. Supported platforms:
@@ -27,6 +258,11 @@ pub struct FileDialog {
     pub(crate) can_create_directories: Option<bool>,
     pub(crate) show_hidden_files: Option<bool>,
     pub(crate) format_label: Option<String>,
+    pub(crate) current_filter: Option<usize>,
+    pub(crate) choices: Vec<Choice>,
+    pub(crate) accept_label: Option<String>,
+    pub(crate) existing_file: Option<PathBuf>,
+    pub(crate) timeout: Option<Duration>,
 }
 
 // Oh god, I don't like sending RawWindowHandle between threads but here we go anyways...
@@ -57,6 +293,78 @@ impl FileDialog {
         self.filters.push(Filter {
             name: name.into(),
             extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Add a filter built with `Filter`, which can also match glob patterns and exact
+    /// file names, eg. `Makefile` or `*.config.json`.
+    ///
+    /// It counts as a filter for `set_current_filter` and `FileResponse::filter`.
+    /// See `Filter::pattern` for platform specific notes.
+    pub fn add_custom_filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Add MIME type filter.
+    ///
+    /// Takes in the name of the filter, and list of MIME types, eg. `["image/*", "text/markdown"]`.
+    /// It counts as a filter for `set_current_filter` and `FileResponse::filter`.
+    ///
+    /// On Linux, helpers that only understand extensions (like Zenity) get the MIME types
+    /// expanded through the shared-mime-info database.
+    /// Supported platforms:
+    ///  * Linux
+    ///  * WASM32
+    pub fn add_mime_filter(
+        mut self,
+        name: impl Into<String>,
+        mime_types: &[impl ToString],
+    ) -> Self {
+        self.filters.push(Filter {
+            name: name.into(),
+            mime_types: mime_types.iter().map(|m| m.to_string()).collect(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Select the filter that is active when the dialog opens.
+    ///
+    /// Takes in the index of the filter, in the order they were added with `add_filter`.
+    /// Supported platforms:
+    ///  * Linux
+    pub fn set_current_filter(mut self, index: usize) -> Self {
+        self.current_filter = Some(index);
+        self
+    }
+
+    /// Add an extra choice control to the dialog.
+    ///
+    /// Takes in the id of the choice, its label, a list of `(id, label)` options and the id of
+    /// the option selected by default. An empty list of options adds a checkbox instead of a
+    /// combo box, its value is either `"true"` or `"false"`.
+    ///
+    /// The selected values are reported by the `*_with_response` methods.
+    /// Supported platforms:
+    ///  * Linux (XDG Portal and GTK3 only)
+    pub fn add_choice(
+        mut self,
+        id: impl Into<String>,
+        label: impl Into<String>,
+        options: &[(&str, &str)],
+        default: impl Into<String>,
+    ) -> Self {
+        self.choices.push(Choice {
+            id: id.into(),
+            label: label.into(),
+            options: options
+                .iter()
+                .map(|(id, label)| (id.to_string(), label.to_string()))
+                .collect(),
+            default: default.into(),
         });
         self
     }
@@ -84,6 +392,24 @@ impl FileDialog {
         self
     }
 
+    /// Set the file that is being saved again, for a "Save As" of an already saved document.
+    ///
+    /// The dialog opens in the folder of the file with its name filled in, on top of that
+    /// Linux preselects the existing file itself. Overrides `set_directory` and `set_file_name`.
+    /// Supported platforms:
+    ///  * Windows
+    ///  * Linux
+    ///  * Mac
+    pub fn set_existing_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        let path = path.as_ref();
+        self.starting_directory = path.parent().map(Path::to_path_buf);
+        self.file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        self.existing_file = Some(path.into());
+        self
+    }
+
     /// Set the title of the dialog. Supported platforms:
     ///  * Windows
     ///  * Linux
@@ -93,11 +419,19 @@ impl FileDialog {
         self
     }
 
+    /// Set the label of the accept button of the dialog, eg. "Import" instead of "Open".
+    /// Supported platforms:
+    ///  * Linux
+    pub fn set_accept_label(mut self, label: impl Into<String>) -> Self {
+        self.accept_label = Some(label.into());
+        self
+    }
+
     /// Set parent windows explicitly (optional).
     /// Supported platforms:
     ///  * Windows
     ///  * Mac
-    ///  * Linux (XDG only)
+    ///  * Linux (XDG, and zenity 3 on X11)
     pub fn set_parent<W: HasWindowHandle + HasDisplayHandle + ?Sized>(
         mut self,
         parent: &W,
@@ -131,10 +465,98 @@ impl FileDialog {
         self.format_label = Some(label.into());
         self
     }
+
+    /// Close the dialog if the user did not answer it within `timeout`.
+    ///
+    /// The `try_*` methods then return `Err(Error::TimedOut)`, the other ones `None`.
+    /// Supported platforms:
+    ///  * Linux
+    ///  * WASM32
+    ///
+    /// On Windows and Mac only the future of `AsyncFileDialog` resolves, the dialog itself
+    /// stays open until the user answers it.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl FileDialog {
+    /// Reject options that no backend could show, instead of each one handling them differently
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: String| Err(Error::InvalidOptions(reason));
+
+        if let Some(index) = self.current_filter {
+            if index >= self.filters.len() {
+                return invalid(format!(
+                    "current filter {index} is out of range, {} filters were added",
+                    self.filters.len()
+                ));
+            }
+        }
+
+        for choice in &self.choices {
+            let valid_default = if choice.options.is_empty() {
+                choice.default == "true" || choice.default == "false"
+            } else {
+                choice.options.iter().any(|(id, _)| *id == choice.default)
+            };
+            if !valid_default {
+                return invalid(format!(
+                    "default {:?} of choice {:?} is not one of its options",
+                    choice.default, choice.id
+                ));
+            }
+        }
+
+        let filters = self.filters.iter().flat_map(|filter| {
+            std::iter::once(&filter.name)
+                .chain(&filter.extensions)
+                .chain(&filter.patterns)
+                .chain(&filter.mime_types)
+        });
+        let choices = self.choices.iter().flat_map(|choice| {
+            [&choice.id, &choice.label, &choice.default]
+                .into_iter()
+                .chain(choice.options.iter().flat_map(|(id, label)| [id, label]))
+        });
+        let mut strings = self
+            .title
+            .iter()
+            .chain(&self.file_name)
+            .chain(&self.accept_label)
+            .chain(filters)
+            .chain(choices);
+
+        if let Some(string) = strings.find(|string| string.contains('\0')) {
+            return invalid(format!("{string:?} contains a NUL byte"));
+        }
+
+        Ok(())
+    }
+
+    /// Names of `save_files` have to be plain file names, so every path stays inside of the
+    /// folder chosen by the user
+    fn validate_file_names(file_names: &[String]) -> Result<(), Error> {
+        for name in file_names {
+            let mut components = Path::new(name).components();
+            let plain = matches!(components.next(), Some(Component::Normal(_)))
+                && components.next().is_none()
+                && !name.contains('\0');
+            if !plain {
+                return Err(Error::InvalidOptions(format!(
+                    "{name:?} is not a plain file name"
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-use crate::backend::{FilePickerDialogImpl, FileSaveDialogImpl, FolderPickerDialogImpl};
+use crate::backend::{
+    FilePickerDialogImpl, FileSaveDialogImpl, FilesSaveDialogImpl, FolderPickerDialogImpl,
+};
 
 #[cfg(target_os = "macos")]
 use crate::backend::FileOrFolderPickerDialogImpl;
@@ -143,22 +565,60 @@ use crate::backend::FileOrFolderPickerDialogImpl;
 impl FileDialog {
     /// Pick one file
     pub fn pick_file(self) -> Option<PathBuf> {
-        FilePickerDialogImpl::pick_file(self)
+        log_error(self.try_pick_file())
+    }
+
+    /// Pick one file, returning an error if no answer could be obtained from the user.
+    ///
+    /// `Ok(None)` means the user cancelled the dialog.
+    pub fn try_pick_file(self) -> Result<Option<PathBuf>, Error> {
+        self.show(
+            FilePickerDialogImpl::try_pick_file,
+            AsyncFilePickerDialogImpl::try_pick_file_async,
+            PathBuf::from,
+        )
     }
 
     /// Pick multiple files
     pub fn pick_files(self) -> Option<Vec<PathBuf>> {
-        FilePickerDialogImpl::pick_files(self)
+        log_error(self.try_pick_files())
+    }
+
+    /// Pick multiple files, see `try_pick_file`
+    pub fn try_pick_files(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        self.show(
+            FilePickerDialogImpl::try_pick_files,
+            AsyncFilePickerDialogImpl::try_pick_files_async,
+            into_paths,
+        )
     }
 
     /// Pick one folder
     pub fn pick_folder(self) -> Option<PathBuf> {
-        FolderPickerDialogImpl::pick_folder(self)
+        log_error(self.try_pick_folder())
+    }
+
+    /// Pick one folder, see `try_pick_file`
+    pub fn try_pick_folder(self) -> Result<Option<PathBuf>, Error> {
+        self.show(
+            FolderPickerDialogImpl::try_pick_folder,
+            AsyncFolderPickerDialogImpl::try_pick_folder_async,
+            PathBuf::from,
+        )
     }
 
     /// Pick multiple folders
     pub fn pick_folders(self) -> Option<Vec<PathBuf>> {
-        FolderPickerDialogImpl::pick_folders(self)
+        log_error(self.try_pick_folders())
+    }
+
+    /// Pick multiple folders, see `try_pick_file`
+    pub fn try_pick_folders(self) -> Result<Option<Vec<PathBuf>>, Error> {
+        self.show(
+            FolderPickerDialogImpl::try_pick_folders,
+            AsyncFolderPickerDialogImpl::try_pick_folders_async,
+            into_paths,
+        )
     }
 
     #[cfg(target_os = "macos")]
@@ -197,10 +657,138 @@ impl FileDialog {
     ///     - If selected extension was typed in by the user it will just return
     ///     - If unselected extension was provided it will append selected one at the end, example: `test.png.txt`
     pub fn save_file(self) -> Option<PathBuf> {
-        FileSaveDialogImpl::save_file(self)
+        log_error(self.try_save_file())
+    }
+
+    /// Opens save file dialog, see `save_file` and `try_pick_file`
+    pub fn try_save_file(self) -> Result<Option<PathBuf>, Error> {
+        self.show(
+            FileSaveDialogImpl::try_save_file,
+            AsyncFileSaveDialogImpl::try_save_file_async,
+            PathBuf::from,
+        )
+    }
+
+    /// Save multiple files into one folder chosen by the user
+    ///
+    /// Takes in the proposed file names, and returns the path each of them should be saved to,
+    /// in the same order. The name of a file that would overwrite an existing one gets changed.
+    /// Names containing a path, eg. `../notes.txt`, are rejected with `Error::InvalidOptions`.
+    ///
+    /// Linux (XDG Portal) shows a native dialog for this, on every other platform the user
+    /// picks a folder and colliding names get a suffix, eg. `name (1).png`.
+    pub fn save_files(self, file_names: &[impl ToString]) -> Option<Vec<PathBuf>> {
+        log_error(self.try_save_files(file_names))
+    }
+
+    /// Save multiple files into one folder chosen by the user, see `save_files` and `try_pick_file`
+    pub fn try_save_files(
+        self,
+        file_names: &[impl ToString],
+    ) -> Result<Option<Vec<PathBuf>>, Error> {
+        let file_names: Vec<String> = file_names.iter().map(|name| name.to_string()).collect();
+        Self::validate_file_names(&file_names)?;
+        let async_file_names = file_names.clone();
+        self.show(
+            |dialog| FilesSaveDialogImpl::try_save_files(dialog, file_names),
+            |dialog| AsyncFilesSaveDialogImpl::try_save_files_async(dialog, async_file_names),
+            into_paths,
+        )
+    }
+
+    /// Pick one file, also reporting which filter and choices the user chose
+    ///
+    /// The chosen filter and choices are reported on: Linux
+    pub fn pick_file_with_response(self) -> Option<FileResponse<PathBuf>> {
+        let choices = self.choices.clone();
+        log_error(self.show(
+            FilePickerDialogImpl::try_pick_file_with_response,
+            AsyncFilePickerDialogImpl::try_pick_file_with_response_async,
+            |res| res.map(PathBuf::from),
+        ))
+        .map(|res| res.with_default_choices(&choices))
+    }
+
+    /// Pick multiple files, also reporting which filter and choices the user chose
+    ///
+    /// The chosen filter and choices are reported on: Linux
+    pub fn pick_files_with_response(self) -> Option<FileResponse<Vec<PathBuf>>> {
+        let choices = self.choices.clone();
+        log_error(self.show(
+            FilePickerDialogImpl::try_pick_files_with_response,
+            AsyncFilePickerDialogImpl::try_pick_files_with_response_async,
+            |res| res.map(into_paths),
+        ))
+        .map(|res| res.with_default_choices(&choices))
+    }
+
+    /// Opens save file dialog, also reporting which filter and choices the user chose
+    ///
+    /// The chosen filter and choices are reported on: Linux
+    pub fn save_file_with_response(self) -> Option<FileResponse<PathBuf>> {
+        let choices = self.choices.clone();
+        log_error(self.show(
+            FileSaveDialogImpl::try_save_file_with_response,
+            AsyncFileSaveDialogImpl::try_save_file_with_response_async,
+            |res| res.map(PathBuf::from),
+        ))
+        .map(|res| res.with_default_choices(&choices))
+    }
+
+    /// Show the blocking version of a dialog.
+    ///
+    /// A blocking dialog can't be closed once the timeout elapses, so on Linux the async version
+    /// is blocked on instead when a timeout is set.
+    fn show<T, A, F>(
+        self,
+        dialog: impl FnOnce(Self) -> Result<Option<T>, Error>,
+        async_dialog: impl FnOnce(Self) -> F,
+        from_async: impl FnOnce(A) -> T,
+    ) -> Result<Option<T>, Error>
+    where
+        F: Future<Output = Result<Option<A>, Error>>,
+    {
+        self.validate()?;
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        if let Some(timeout) = self.timeout {
+            let future = cancellable(None, Some(timeout), async_dialog(self));
+            return match pollster::block_on(future) {
+                Ok(res) => res.map(|res| res.map(from_async)),
+                Err(interrupted) => interrupted.into_result(),
+            };
+        }
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        let _ = (async_dialog, from_async);
+
+        dialog(self)
     }
 }
 
+/// Adapt a dialog that can't fail to the `try_*` signature
+#[cfg(target_os = "macos")]
+async fn infallible<T>(future: impl Future<Output = Option<T>>) -> Result<Option<T>, Error> {
+    Ok(future.await)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn into_paths(handles: Vec<FileHandle>) -> Vec<PathBuf> {
+    handles.into_iter().map(PathBuf::from).collect()
+}
+
 /// Asynchronous File Dialog. Supported platforms:
 ///  * Linux
 ///  * Windows
@@ -209,6 +797,7 @@ impl FileDialog {
 #[derive(Default, Debug, Clone)]
 pub struct AsyncFileDialog {
     file_dialog: FileDialog,
+    handle: Option<DialogHandle>,
 }
 
 impl AsyncFileDialog {
@@ -234,6 +823,65 @@ impl AsyncFileDialog {
         self
     }
 
+    /// Add a filter built with `Filter`, which can also match glob patterns and exact
+    /// file names, eg. `Makefile` or `*.config.json`.
+    ///
+    /// It counts as a filter for `set_current_filter` and `FileResponse::filter`.
+    /// See `Filter::pattern` for platform specific notes.
+    pub fn add_custom_filter(mut self, filter: Filter) -> Self {
+        self.file_dialog = self.file_dialog.add_custom_filter(filter);
+        self
+    }
+
+    /// Add MIME type filter.
+    ///
+    /// Takes in the name of the filter, and list of MIME types, eg. `["image/*", "text/markdown"]`.
+    /// It counts as a filter for `set_current_filter` and `FileResponse::filter`.
+    ///
+    /// On Linux, helpers that only understand extensions (like Zenity) get the MIME types
+    /// expanded through the shared-mime-info database.
+    /// Supported platforms:
+    ///  * Linux
+    ///  * WASM32
+    pub fn add_mime_filter(
+        mut self,
+        name: impl Into<String>,
+        mime_types: &[impl ToString],
+    ) -> Self {
+        self.file_dialog = self.file_dialog.add_mime_filter(name, mime_types);
+        self
+    }
+
+    /// Select the filter that is active when the dialog opens.
+    ///
+    /// Takes in the index of the filter, in the order they were added with `add_filter`.
+    /// Supported platforms:
+    ///  * Linux
+    pub fn set_current_filter(mut self, index: usize) -> Self {
+        self.file_dialog = self.file_dialog.set_current_filter(index);
+        self
+    }
+
+    /// Add an extra choice control to the dialog.
+    ///
+    /// Takes in the id of the choice, its label, a list of `(id, label)` options and the id of
+    /// the option selected by default. An empty list of options adds a checkbox instead of a
+    /// combo box, its value is either `"true"` or `"false"`.
+    ///
+    /// The selected values are reported by the `*_with_response` methods.
+    /// Supported platforms:
+    ///  * Linux (XDG Portal and GTK3 only)
+    pub fn add_choice(
+        mut self,
+        id: impl Into<String>,
+        label: impl Into<String>,
+        options: &[(&str, &str)],
+        default: impl Into<String>,
+    ) -> Self {
+        self.file_dialog = self.file_dialog.add_choice(id, label, options, default);
+        self
+    }
+
     /// Set starting directory of the dialog. Supported platforms:
     ///   * Linux
     ///   * Windows
@@ -253,6 +901,20 @@ impl AsyncFileDialog {
         self
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Set the file that is being saved again, for a "Save As" of an already saved document.
+    ///
+    /// The dialog opens in the folder of the file with its name filled in, on top of that
+    /// Linux preselects the existing file itself. Overrides `set_directory` and `set_file_name`.
+    /// Supported platforms:
+    ///  * Windows
+    ///  * Linux
+    ///  * Mac
+    pub fn set_existing_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.file_dialog = self.file_dialog.set_existing_file(path);
+        self
+    }
+
     /// Set the title of the dialog. Supported platforms:
     ///  * Windows
     ///  * Linux
@@ -263,11 +925,19 @@ impl AsyncFileDialog {
         self
     }
 
+    /// Set the label of the accept button of the dialog, eg. "Import" instead of "Open".
+    /// Supported platforms:
+    ///  * Linux
+    pub fn set_accept_label(mut self, label: impl Into<String>) -> Self {
+        self.file_dialog = self.file_dialog.set_accept_label(label);
+        self
+    }
+
     /// Set parent windows explicitly (optional).
     /// Supported platforms:
     ///  * Windows
     ///  * Mac
-    ///  * Linux (XDG only)
+    ///  * Linux (XDG, and zenity 3 on X11)
     pub fn set_parent<W: HasWindowHandle + HasDisplayHandle + ?Sized>(
         mut self,
         parent: &W,
@@ -300,6 +970,18 @@ impl AsyncFileDialog {
         self.file_dialog = self.file_dialog.set_format_label(label);
         self
     }
+
+    /// Close the dialog if the user did not answer it within `timeout`, see `FileDialog::set_timeout`
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.file_dialog = self.file_dialog.set_timeout(timeout);
+        self
+    }
+
+    /// Attach a handle that can close the dialog, see [`DialogHandle`]
+    pub fn set_handle(mut self, handle: &DialogHandle) -> Self {
+        self.handle = Some(handle.clone());
+        self
+    }
 }
 
 #[cfg(target_os = "macos")]
@@ -307,6 +989,8 @@ use crate::backend::AsyncFileOrFolderPickerDialogImpl;
 use crate::backend::AsyncFilePickerDialogImpl;
 use crate::backend::AsyncFileSaveDialogImpl;
 #[cfg(not(target_arch = "wasm32"))]
+use crate::backend::AsyncFilesSaveDialogImpl;
+#[cfg(not(target_arch = "wasm32"))]
 use crate::backend::AsyncFolderPickerDialogImpl;
 
 use std::future::Future;
@@ -314,12 +998,27 @@ use std::future::Future;
 impl AsyncFileDialog {
     /// Pick one file
     pub fn pick_file(self) -> impl Future<Output = Option<FileHandle>> {
-        AsyncFilePickerDialogImpl::pick_file_async(self.file_dialog)
+        let future = self.try_pick_file();
+        async move { log_error(future.await) }
+    }
+
+    /// Pick one file, returning an error if no answer could be obtained from the user.
+    ///
+    /// `Ok(None)` means the user cancelled the dialog, or the dialog was closed through
+    /// its [`DialogHandle`].
+    pub fn try_pick_file(self) -> impl Future<Output = Result<Option<FileHandle>, Error>> {
+        self.show(AsyncFilePickerDialogImpl::try_pick_file_async)
     }
 
     /// Pick multiple files
     pub fn pick_files(self) -> impl Future<Output = Option<Vec<FileHandle>>> {
-        AsyncFilePickerDialogImpl::pick_files_async(self.file_dialog)
+        let future = self.try_pick_files();
+        async move { log_error(future.await) }
+    }
+
+    /// Pick multiple files, see `try_pick_file`
+    pub fn try_pick_files(self) -> impl Future<Output = Result<Option<Vec<FileHandle>>, Error>> {
+        self.show(AsyncFilePickerDialogImpl::try_pick_files_async)
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -327,7 +1026,16 @@ impl AsyncFileDialog {
     ///
     /// Does not exist in `WASM32`
     pub fn pick_folder(self) -> impl Future<Output = Option<FileHandle>> {
-        AsyncFolderPickerDialogImpl::pick_folder_async(self.file_dialog)
+        let future = self.try_pick_folder();
+        async move { log_error(future.await) }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Pick one folder, see `try_pick_file`
+    ///
+    /// Does not exist in `WASM32`
+    pub fn try_pick_folder(self) -> impl Future<Output = Result<Option<FileHandle>, Error>> {
+        self.show(AsyncFolderPickerDialogImpl::try_pick_folder_async)
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -335,7 +1043,16 @@ impl AsyncFileDialog {
     ///
     /// Does not exist in `WASM32`
     pub fn pick_folders(self) -> impl Future<Output = Option<Vec<FileHandle>>> {
-        AsyncFolderPickerDialogImpl::pick_folders_async(self.file_dialog)
+        let future = self.try_pick_folders();
+        async move { log_error(future.await) }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Pick multiple folders, see `try_pick_file`
+    ///
+    /// Does not exist in `WASM32`
+    pub fn try_pick_folders(self) -> impl Future<Output = Result<Option<Vec<FileHandle>>, Error>> {
+        self.show(AsyncFolderPickerDialogImpl::try_pick_folders_async)
     }
 
     #[cfg(target_os = "macos")]
@@ -343,7 +1060,10 @@ impl AsyncFileDialog {
     ///
     /// Supported only on: macos
     pub fn pick_file_or_folder(self) -> impl Future<Output = Option<FileHandle>> {
-        AsyncFileOrFolderPickerDialogImpl::pick_file_or_folder_async(self.file_dialog)
+        let future = self.show(|dialog| {
+            infallible(AsyncFileOrFolderPickerDialogImpl::pick_file_or_folder_async(dialog))
+        });
+        async move { log_error(future.await) }
     }
 
     #[cfg(target_os = "macos")]
@@ -351,7 +1071,10 @@ impl AsyncFileDialog {
     ///
     /// Supported only on: macos
     pub fn pick_files_or_folders(self) -> impl Future<Output = Option<Vec<FileHandle>>> {
-        AsyncFileOrFolderPickerDialogImpl::pick_files_or_folders_async(self.file_dialog)
+        let future = self.show(|dialog| {
+            infallible(AsyncFileOrFolderPickerDialogImpl::pick_files_or_folders_async(dialog))
+        });
+        async move { log_error(future.await) }
     }
 
     /// Opens save file dialog
@@ -378,6 +1101,222 @@ impl AsyncFileDialog {
     ///     - `save_file` returns immediately without a dialog prompt.
     ///       Instead the user is prompted by their browser on where to save the file when [`FileHandle::write`] is used.
     pub fn save_file(self) -> impl Future<Output = Option<FileHandle>> {
-        AsyncFileSaveDialogImpl::save_file_async(self.file_dialog)
+        let future = self.try_save_file();
+        async move { log_error(future.await) }
+    }
+
+    /// Opens save file dialog, see `save_file` and `try_pick_file`
+    pub fn try_save_file(self) -> impl Future<Output = Result<Option<FileHandle>, Error>> {
+        self.show(AsyncFileSaveDialogImpl::try_save_file_async)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Save multiple files into one folder chosen by the user
+    ///
+    /// Takes in the proposed file names, and returns the path each of them should be saved to,
+    /// in the same order. The name of a file that would overwrite an existing one gets changed.
+    /// Names containing a path, eg. `../notes.txt`, are rejected with `Error::InvalidOptions`.
+    ///
+    /// Linux (XDG Portal) shows a native dialog for this, on every other platform the user
+    /// picks a folder and colliding names get a suffix, eg. `name (1).png`.
+    ///
+    /// Does not exist in `WASM32`
+    pub fn save_files(
+        self,
+        file_names: &[impl ToString],
+    ) -> impl Future<Output = Option<Vec<FileHandle>>> {
+        let future = self.try_save_files(file_names);
+        async move { log_error(future.await) }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Save multiple files into one folder chosen by the user, see `save_files` and `try_pick_file`
+    ///
+    /// Does not exist in `WASM32`
+    pub fn try_save_files(
+        self,
+        file_names: &[impl ToString],
+    ) -> impl Future<Output = Result<Option<Vec<FileHandle>>, Error>> {
+        let file_names: Vec<String> = file_names.iter().map(|name| name.to_string()).collect();
+        let future = FileDialog::validate_file_names(&file_names).map(|()| {
+            self.show(|dialog| AsyncFilesSaveDialogImpl::try_save_files_async(dialog, file_names))
+        });
+        async move { future?.await }
+    }
+
+    /// Pick one file, also reporting which filter and choices the user chose
+    ///
+    /// The chosen filter and choices are reported on: Linux
+    pub fn pick_file_with_response(self) -> impl Future<Output = Option<FileResponse<FileHandle>>> {
+        let choices = self.file_dialog.choices.clone();
+        let future = self.show(AsyncFilePickerDialogImpl::try_pick_file_with_response_async);
+        async move {
+            let res = log_error(future.await)?;
+            Some(res.with_default_choices(&choices))
+        }
+    }
+
+    /// Pick multiple files, also reporting which filter and choices the user chose
+    ///
+    /// The chosen filter and choices are reported on: Linux
+    pub fn pick_files_with_response(
+        self,
+    ) -> impl Future<Output = Option<FileResponse<Vec<FileHandle>>>> {
+        let choices = self.file_dialog.choices.clone();
+        let future = self.show(AsyncFilePickerDialogImpl::try_pick_files_with_response_async);
+        async move {
+            let res = log_error(future.await)?;
+            Some(res.with_default_choices(&choices))
+        }
+    }
+
+    /// Opens save file dialog, also reporting which filter and choices the user chose
+    ///
+    /// The chosen filter and choices are reported on: Linux
+    pub fn save_file_with_response(self) -> impl Future<Output = Option<FileResponse<FileHandle>>> {
+        let choices = self.file_dialog.choices.clone();
+        let future = self.show(AsyncFileSaveDialogImpl::try_save_file_with_response_async);
+        async move {
+            let res = log_error(future.await)?;
+            Some(res.with_default_choices(&choices))
+        }
+    }
+
+    /// Show the dialog, closing it once the handle is closed or the timeout elapses
+    fn show<T, F>(
+        self,
+        dialog: impl FnOnce(FileDialog) -> F,
+    ) -> impl Future<Output = Result<Option<T>, Error>>
+    where
+        F: Future<Output = Result<Option<T>, Error>>,
+    {
+        let timeout = self.file_dialog.timeout;
+        let future = self
+            .file_dialog
+            .validate()
+            .map(|()| cancellable(self.handle, timeout, dialog(self.file_dialog)));
+
+        async move {
+            match future?.await {
+                Ok(res) => res,
+                Err(interrupted) => interrupted.into_result(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_matches, Filter};
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches(b"Makefile", b"makefile"));
+        assert!(glob_matches(b"*.config.json", b"app.config.json"));
+        assert!(!glob_matches(b"*.config.json", b"app.json"));
+        assert!(glob_matches(b"backup-*.tar", b"backup-2024-01-01.tar"));
+        assert!(!glob_matches(b"backup-*.tar", b"backup-2024.tar.gz"));
+        assert!(glob_matches(b"file?.txt", b"file1.txt"));
+        assert!(!glob_matches(b"file?.txt", b"file.txt"));
+        assert!(glob_matches(b"[Mm]akefile", b"Makefile"));
+        assert!(glob_matches(b"log[0-9].txt", b"log7.txt"));
+        assert!(!glob_matches(b"log[!0-9].txt", b"log7.txt"));
+        assert!(glob_matches(b"[]].txt", b"].txt"));
+        assert!(glob_matches(b"[abc", b"[abc"));
+    }
+
+    #[test]
+    fn filter_matches() {
+        let filter = Filter::new("Build")
+            .extension("cmake")
+            .pattern("Makefile")
+            .pattern("Dockerfile");
+
+        assert!(filter.matches("/src/CMakeLists.CMAKE"));
+        assert!(filter.matches("/src/Makefile"));
+        assert!(filter.matches("Dockerfile"));
+        assert!(!filter.matches("/src/Makefile.am"));
+        assert!(!filter.matches("/"));
+
+        assert!(Filter::new("All").extension("*").matches("README"));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", target_arch = "wasm32"))]
+    fn approximate_extensions() {
+        let filter = Filter::new("Config")
+            .extension("toml")
+            .pattern("*.config.json");
+        assert_eq!(
+            filter.approximate_extensions(),
+            Some(vec!["toml".into(), "json".into()])
+        );
+
+        let filter = filter.pattern("Makefile");
+        assert_eq!(filter.approximate_extensions(), None);
+
+        let filter = Filter::new("Backup").pattern("backup.*");
+        assert_eq!(filter.approximate_extensions(), None);
+    }
+
+    #[test]
+    fn validate() {
+        use super::{Error, FileDialog, Filter};
+
+        assert_eq!(
+            FileDialog::default()
+                .add_filter("Text", &["txt"])
+                .set_current_filter(0)
+                .validate(),
+            Ok(())
+        );
+        assert!(matches!(
+            FileDialog::default().set_current_filter(0).validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+        for names in [
+            &["../.bashrc"][..],
+            &["/etc/passwd"],
+            &["a/b"],
+            &[".."],
+            &[""],
+        ] {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            assert!(matches!(
+                FileDialog::validate_file_names(&names),
+                Err(Error::InvalidOptions(_))
+            ));
+        }
+        assert_eq!(
+            FileDialog::validate_file_names(&["a.png".into(), "..b".into()]),
+            Ok(())
+        );
+
+        for filter in [
+            Filter::new("Te\0xt").extension("txt"),
+            Filter::new("Text").pattern("*.t\0xt"),
+            Filter::new("Images").mime_type("image/\0*"),
+        ] {
+            assert!(matches!(
+                FileDialog::default().add_custom_filter(filter).validate(),
+                Err(Error::InvalidOptions(_))
+            ));
+        }
+        assert!(matches!(
+            FileDialog::default()
+                .add_choice("encoding", "Encoding", &[("utf8", "UTF-8")], "latin1")
+                .validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            FileDialog::default().set_title("a\0b").validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            FileDialog::default()
+                .add_choice("encoding", "Encoding", &[("utf8", "UTF-\08")], "utf8")
+                .validate(),
+            Err(Error::InvalidOptions(_))
+        ));
     }
 }
@@ -29,5 +29,7 @@ mod tests {
         let _ = FileHandle::inner;
         #[cfg(not(target_arch = "wasm32"))]
         let _ = FileHandle::path;
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = FileHandle::host_path;
     }
 }
@@ -4,6 +4,7 @@ use std::{
 };
 
 use super::super::oneshot;
+use crate::Error;
 
 /// FileHandle is a way of abstracting over a file returned by a dialog
 #[derive(Clone)]
@@ -33,6 +34,53 @@ impl FileHandle {
         &self.0
     }
 
+    /// Gets the path of the file as the user knows it.
+    ///
+    /// Inside of Flatpak and Snap, files picked through the XDG Desktop Portal are made available
+    /// in the document store, eg. `/run/user/1000/doc/<id>/notes.txt`. These paths work for
+    /// reading and writing, but mean nothing to users, so this asks the document portal for the
+    /// real location instead. Same as [`FileHandle::path`] for other files and on other platforms.
+    pub fn host_path(&self) -> PathBuf {
+        #[cfg(all(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ),
+            feature = "xdg-portal"
+        ))]
+        match pollster::block_on(crate::backend::document_host_path(&self.0)) {
+            Ok(Some(path)) => return path,
+            Ok(None) => {}
+            Err(err) => log::warn!("Failed to get the host path of {:?}: {err}", self.0),
+        }
+
+        self.0.clone()
+    }
+
+    /// Keeps the document store entry of this file after the session ends, so the path stays
+    /// valid, eg. for a list of recent files.
+    ///
+    /// Only does something for files in the document store, see [`FileHandle::host_path`].
+    pub fn make_persistent(&self) -> Result<(), Error> {
+        #[cfg(all(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ),
+            feature = "xdg-portal"
+        ))]
+        return pollster::block_on(crate::backend::make_document_persistent(&self.0));
+
+        #[allow(unreachable_code)]
+        Ok(())
+    }
+
     /// Reads a file asynchronously.
     ///
     /// On native platforms it spawns a `std::thread` in the background.
@@ -0,0 +1,88 @@
+use crate::backend::LauncherImpl;
+use crate::Error;
+
+use std::path::{Path, PathBuf};
+
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+
+/// Opens files and URLs with another application, eg. an export right after saving it.
+///
+/// Supported platforms:
+///  * Linux & BSDs, through the XDG Desktop Portal, or `xdg-open` if there is no portal
+///  * Windows
+///  * macOS
+#[derive(Default, Debug, Clone)]
+pub struct Launcher {
+    pub(crate) parent: Option<RawWindowHandle>,
+    pub(crate) parent_display: Option<RawDisplayHandle>,
+    pub(crate) ask: bool,
+}
+
+// Oh god, I don't like sending RawWindowHandle between threads but here we go anyways...
+// fingers crossed
+unsafe impl Send for Launcher {}
+
+impl Launcher {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set parent windows explicitly (optional).
+    /// Supported platforms:
+    ///  * Windows
+    ///  * Linux (XDG only)
+    pub fn set_parent<W: HasWindowHandle + HasDisplayHandle + ?Sized>(
+        mut self,
+        parent: &W,
+    ) -> Self {
+        self.parent = parent.window_handle().ok().map(|x| x.as_raw());
+        self.parent_display = parent.display_handle().ok().map(|x| x.as_raw());
+        self
+    }
+
+    /// Let the user choose the application, instead of using the default one.
+    ///
+    /// Closing the application chooser without picking one is not an error, nothing is opened
+    /// then.
+    /// Supported platforms:
+    ///  * Windows (files only)
+    ///  * Linux (XDG only)
+    pub fn set_ask(mut self, ask: bool) -> Self {
+        self.ask = ask;
+        self
+    }
+
+    /// Open `uri`, eg. `https://example.com` or `mailto:someone@example.com`
+    pub fn open_uri(self, uri: &str) -> Result<(), Error> {
+        LauncherImpl::open_uri(self, uri)
+    }
+
+    /// Open a local file
+    pub fn open_file<P: AsRef<Path>>(self, path: P) -> Result<(), Error> {
+        LauncherImpl::open_file(self, path.as_ref())
+    }
+
+    /// Open the file manager with `paths` selected, eg. "Show in folder" after saving.
+    ///
+    /// On Linux it uses `org.freedesktop.FileManager1`, or the `OpenDirectory` method of the OpenURI
+    /// portal inside of Flatpak and Snap. On Windows only the first path is selected.
+    pub fn show_in_folder<P: AsRef<Path>>(self, paths: &[P]) -> Result<(), Error> {
+        let paths: Vec<PathBuf> = paths.iter().map(|p| p.as_ref().to_path_buf()).collect();
+        LauncherImpl::show_in_folder(self, &paths)
+    }
+}
+
+/// Open `uri` with the default application, see [`Launcher`]
+pub fn open_uri(uri: &str) -> Result<(), Error> {
+    Launcher::new().open_uri(uri)
+}
+
+/// Open a local file with the default application, see [`Launcher`]
+pub fn open_file<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+    Launcher::new().open_file(path)
+}
+
+/// Open the file manager with `paths` selected, see [`Launcher::show_in_folder`]
+pub fn show_in_folder<P: AsRef<Path>>(paths: &[P]) -> Result<(), Error> {
+    Launcher::new().show_in_folder(paths)
+}
@@ -41,8 +41,14 @@
 //! and the other using the [XDG Desktop Portal](https://github.com/flatpak/xdg-desktop-portal)
 //! D-Bus API through `libdbus` or [zenity](https://gitlab.gnome.org/GNOME/zenity).
 //!
+//! Both can be compiled into one binary, the backend is then picked at runtime: each dialog is
+//! shown by the first available backend of the XDG Desktop Portal, GTK, zenity and kdialog, in that
+//! order. In KDE sessions kdialog is tried right after the portal.
+//! The order can be changed with [`set_backends`], or by the user with the `RFD_BACKEND`
+//! environment variable, eg. `RFD_BACKEND=gtk3,zenity`.
+//!
 //! ## GTK backend
-//! The GTK backend is used when the `xdg-portal` feature is disabled with the [`default-features = false`](https://doc.rust-lang.org/cargo/reference/features.html#dependency-features), and `gtk3` is enabled instead. The GTK3
+//! The GTK backend is used when the `gtk3` feature is enabled, either together with `xdg-portal` or instead of it with [`default-features = false`](https://doc.rust-lang.org/cargo/reference/features.html#dependency-features). The GTK3
 //! backend requires the C library and development headers to be installed to build RFD. The package
 //! names on various distributions are:
 //!
@@ -57,7 +63,9 @@
 //! in use at runtime.
 //!
 //! It requires the user to have `libdbus` system library (should be available on any systemd
-//! distro), if the library is not found `zenity` will be used instead.
+//! distro), if the library is not found `zenity` will be used instead. With the `pure-dbus` feature
+//! the portal is reached through a D-Bus client written in Rust instead, so no system library is
+//! needed at all, eg. in minimal containers or static musl builds.
 //!
 //! It requires the user to also have either the
 //! [GTK](https://github.com/flatpak/xdg-desktop-portal-gtk),
@@ -70,12 +78,19 @@
 //! D-Bus API that RFD requires (it does not interfere with the other portal implementations;
 //! they can all be installed simultaneously).
 //!
-//! [Zenity](https://gitlab.gnome.org/GNOME/zenity) is also required to display message dialogs,
-//! and is used for file dialogs if xdg portal fails.
+//! Portals older than version 3 of the FileChooser interface can't pick folders, so folder dialogs
+//! use the next backend instead. Before version 4 the starting directory of open dialogs is
+//! ignored. The version in use is reported by [`Capabilities::portal_version`].
+//!
+//! [Zenity](https://gitlab.gnome.org/GNOME/zenity) or [kdialog](https://invent.kde.org/utilities/kdialog)
+//! is also required to display message dialogs, and is used for file dialogs if xdg portal fails.
 //!
 //! If you are packaging an application that uses RFD, ensure that both a supported portal backend and
 //! Zenity are installed with the package.
 //!
+//! Inside of Flatpak, helpers that are not part of the app run on the host through
+//! `flatpak-spawn --host`, which needs the `--talk-name=org.freedesktop.Flatpak` permission.
+//!
 //! # macOS non-windowed applications, async, and threading
 //!
 //! macOS async dialogs require an `NSApplication` instance, so the dialog is only truly async when
@@ -99,7 +114,8 @@
 //!
 //! # Cargo features
 //!  * `gtk3`: Uses GTK for dialogs on Linux & BSDs; has no effect on Windows and macOS
-//!  * `xdg-portal`: Uses XDG Desktop Portal instead of GTK on Linux & BSDs
+//!  * `xdg-portal`: Uses XDG Desktop Portal for dialogs on Linux & BSDs, can be combined with `gtk3`
+//!  * `testing`: Replaces the dialogs with a mock for the tests of applications, see [`testing`]; has no effect on WASM. Never enable it outside of `[dev-dependencies]`: features are unified, so it would replace the dialogs of the whole build, and dialogs requested without a started mock panic
 //!  * `common-controls-v6`: Use `TaskDialogIndirect` API from ComCtl32.dll v6 for showing message dialog. This is necessary if you need to customize dialog button texts.
 //!
 //! # State
@@ -118,6 +134,8 @@
 //! | StartingPath | ✔     | ✔       | ✔         | ✖      |
 //! | Async        | ✔     | ✔       | ✔         | ✔      |
 //!
+//! Which options the backend in use honours can be checked at runtime with [`capabilities`].
+//!
 //! # rfd-extras
 //!
 //! AKA features that are not file related
@@ -125,6 +143,7 @@
 //! | Feature       | Linux        | Windows | MacOS | Wasm32 |
 //! | ------------- | -----        | ------- | ----- | ------ |
 //! | MessageDialog | ✔            | ✔       | ✔     | ✔      |
+//! | Launcher      | ✔            | ✔       | ✔     |        |
 //! | PromptDialog  |              |         |       |        |
 //! | ColorPicker   |              |         |       |        |
 
@@ -136,10 +155,32 @@ pub use file_handle::FileHandle;
 mod file_dialog;
 mod oneshot;
 
+mod dialog_handle;
+pub use dialog_handle::DialogHandle;
+
+mod error;
+pub use error::Error;
+
+mod timer;
+
+mod linux_backend;
+pub use linux_backend::{set_backends, Backend};
+
+mod capabilities;
+pub use capabilities::{capabilities, Capabilities, Feature};
+
 #[cfg(not(target_arch = "wasm32"))]
 pub use file_dialog::FileDialog;
 
 pub use file_dialog::AsyncFileDialog;
+pub use file_dialog::FileResponse;
+pub use file_dialog::Filter;
+
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
+pub mod testing;
+
+mod launcher;
+pub use launcher::{open_file, open_uri, show_in_folder, Launcher};
 
 mod message_dialog;
 pub use message_dialog::{
@@ -0,0 +1,251 @@
+//! Runtime selection of the dialog backend on Linux & BSDs.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use crate::Error;
+
+/// Dialog backend used on Linux & BSDs, see [`set_backends`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// [XDG Desktop Portal](https://github.com/flatpak/xdg-desktop-portal), requires the
+    /// `xdg-portal` feature. It has no message dialogs, those use the next backend.
+    XdgPortal,
+    /// GTK3, requires the `gtk3` feature
+    Gtk3,
+    /// [Zenity](https://gitlab.gnome.org/GNOME/zenity) helper program
+    Zenity,
+    /// [kdialog](https://invent.kde.org/utilities/kdialog) helper program
+    Kdialog,
+}
+
+impl Backend {
+    /// Fallback chain used unless [`set_backends`] or `RFD_BACKEND` say otherwise.
+    ///
+    /// In KDE sessions kdialog moves up to right after the portal.
+    pub const DEFAULT: &'static [Backend] = &[
+        Backend::XdgPortal,
+        Backend::Gtk3,
+        Backend::Zenity,
+        Backend::Kdialog,
+    ];
+
+    /// Whether RFD was built with this backend, see the `xdg-portal` and `gtk3` features
+    pub fn is_compiled(self) -> bool {
+        let linux = cfg!(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ));
+
+        match self {
+            Self::XdgPortal => linux && cfg!(feature = "xdg-portal"),
+            Self::Gtk3 => linux && cfg!(feature = "gtk3"),
+            Self::Zenity | Self::Kdialog => linux,
+        }
+    }
+}
+
+impl Display for Backend {
+    /// Name used by `RFD_BACKEND`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::XdgPortal => "portal",
+            Self::Gtk3 => "gtk3",
+            Self::Zenity => "zenity",
+            Self::Kdialog => "kdialog",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    /// Parse a name used by `RFD_BACKEND`, eg. `portal`, `gtk3`, `zenity` or `kdialog`
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "portal" | "xdg-portal" | "xdg-desktop-portal" => Ok(Self::XdgPortal),
+            "gtk" | "gtk3" => Ok(Self::Gtk3),
+            "zenity" => Ok(Self::Zenity),
+            "kdialog" => Ok(Self::Kdialog),
+            _ => Err(Error::InvalidOptions(format!("unknown backend {name:?}"))),
+        }
+    }
+}
+
+static BACKENDS: RwLock<Option<Vec<Backend>>> = RwLock::new(None);
+
+/// Set the backends tried on Linux & BSDs, in order.
+///
+/// A backend that is not available at runtime, eg. no portal is running or zenity is not
+/// installed, is skipped in favour of the next one. Backends RFD was not built with are ignored,
+/// and [`Backend::DEFAULT`] is used if none of them is left. Defaults to [`Backend::DEFAULT`].
+///
+/// The `RFD_BACKEND` environment variable takes precedence, so users can pick what works on their
+/// machine, eg. `RFD_BACKEND=zenity` or `RFD_BACKEND=gtk3,zenity`.
+///
+/// Has no effect on other platforms.
+pub fn set_backends(backends: &[Backend]) {
+    *BACKENDS.write().unwrap() = Some(backends.to_vec());
+}
+
+/// Backends to try, in order
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub(crate) fn backends() -> Vec<Backend> {
+    let from_env = std::env::var("RFD_BACKEND")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| parse_backends(&value))
+        .filter(|backends| {
+            if backends.is_empty() {
+                log::warn!("RFD_BACKEND names no known backend, using the default ones");
+            }
+            !backends.is_empty()
+        });
+
+    compiled_or_default(from_env.or_else(|| BACKENDS.read().unwrap().clone()))
+}
+
+/// The selected backends RFD was built with, or the default ones if there are none
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn compiled_or_default(selected: Option<Vec<Backend>>) -> Vec<Backend> {
+    let compiled = |backends: Vec<Backend>| -> Vec<Backend> {
+        backends
+            .into_iter()
+            .filter(|backend| backend.is_compiled())
+            .collect()
+    };
+
+    if let Some(selected) = selected {
+        let backends = compiled(selected.clone());
+        if !backends.is_empty() {
+            return backends;
+        }
+        log::warn!("RFD was built with none of the backends {selected:?}, using the default ones");
+    }
+
+    compiled(default_backends())
+}
+
+/// [`Backend::DEFAULT`], with kdialog preferred over GTK and zenity in KDE sessions
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn default_backends() -> Vec<Backend> {
+    let mut backends = Backend::DEFAULT.to_vec();
+    if is_kde_session() {
+        backends.retain(|backend| *backend != Backend::Kdialog);
+        backends.insert(1, Backend::Kdialog);
+    }
+    backends
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn is_kde_session() -> bool {
+    std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktops| {
+        desktops
+            .split(':')
+            .any(|desktop| desktop.eq_ignore_ascii_case("KDE"))
+    })
+}
+
+/// Comma separated list of `RFD_BACKEND`, unknown names are logged and skipped
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn parse_backends(value: &str) -> Vec<Backend> {
+    value
+        .split(',')
+        .filter_map(|name| {
+            name.parse()
+                .map_err(|err| log::warn!("RFD_BACKEND: {err}"))
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(all(
+    test,
+    any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )
+))]
+mod tests {
+    use super::{compiled_or_default, default_backends, parse_backends, Backend};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_backends("GTK3, zenity,unknown,portal,kdialog"),
+            [
+                Backend::Gtk3,
+                Backend::Zenity,
+                Backend::XdgPortal,
+                Backend::Kdialog
+            ]
+        );
+
+        for backend in Backend::DEFAULT {
+            assert_eq!(backend.to_string().parse::<Backend>(), Ok(*backend));
+        }
+        assert_eq!(parse_backends("unknown, gtk4"), []);
+    }
+
+    #[test]
+    fn fallback() {
+        let compiled = |backends: Vec<Backend>| -> Vec<Backend> {
+            backends.into_iter().filter(|b| b.is_compiled()).collect()
+        };
+
+        assert_eq!(
+            compiled_or_default(Some(vec![Backend::Kdialog, Backend::Zenity])),
+            [Backend::Kdialog, Backend::Zenity]
+        );
+        assert_eq!(compiled_or_default(None), compiled(default_backends()));
+        assert_eq!(
+            compiled_or_default(Some(Vec::new())),
+            compiled(default_backends())
+        );
+        if !Backend::Gtk3.is_compiled() {
+            assert_eq!(
+                compiled_or_default(Some(vec![Backend::Gtk3])),
+                compiled(default_backends())
+            );
+        }
+    }
+}
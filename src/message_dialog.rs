@@ -1,6 +1,10 @@
 use crate::backend::AsyncMessageDialogImpl;
 use crate::backend::MessageDialogImpl;
+use crate::dialog_handle::{cancellable, Interrupted};
+use crate::error::log_error;
+use crate::{DialogHandle, Error};
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
 use std::future::Future;
 
@@ -19,6 +23,7 @@ pub struct MessageDialog {
     pub(crate) buttons: MessageButtons,
     pub(crate) parent: Option<RawWindowHandle>,
     pub(crate) parent_display: Option<RawDisplayHandle>,
+    pub(crate) timeout: Option<Duration>,
 }
 
 // Oh god, I don't like sending RawWindowHandle between threads but here we go anyways...
@@ -68,7 +73,7 @@ impl MessageDialog {
     /// Supported platforms:
     ///  * Windows
     ///  * Mac
-    ///  * Linux (XDG only)
+    ///  * Linux (XDG, and zenity 3 on X11)
     pub fn set_parent<W: HasWindowHandle + HasDisplayHandle + ?Sized>(
         mut self,
         parent: &W,
@@ -78,10 +83,52 @@ impl MessageDialog {
         self
     }
 
+    /// Close the dialog if the user did not answer it within `timeout`.
+    ///
+    /// `try_show` then returns `Err(Error::TimedOut)`, `show` returns `MessageDialogResult::Cancel`.
+    /// Supported platforms:
+    ///  * Linux
+    ///
+    /// On Windows and Mac only the future of `AsyncMessageDialog` resolves, the dialog itself
+    /// stays open until the user answers it. On WASM32 the timeout is ignored, the browser's
+    /// `alert` and `confirm` block the page until they get answered.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Shows a message dialog and returns the button that was pressed.
     pub fn show(self) -> MessageDialogResult {
-        MessageDialogImpl::show(self)
+        log_error(self.try_show().map(Some)).unwrap_or_default()
     }
+
+    /// Shows a message dialog and returns the button that was pressed, or `Error::TimedOut` once
+    /// the timeout set with `set_timeout` elapsed.
+    pub fn try_show(self) -> Result<MessageDialogResult, Error> {
+        // A blocking dialog can't be closed once the timeout elapses, block on the async one
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        if let Some(timeout) = self.timeout {
+            let future = cancellable(
+                None,
+                Some(timeout),
+                AsyncMessageDialogImpl::show_async(self),
+            );
+            return pollster::block_on(future).or_else(interrupted);
+        }
+
+        Ok(MessageDialogImpl::show(self))
+    }
+}
+
+/// Result of a message dialog that got interrupted, a closed dialog counts as cancelled
+fn interrupted(interrupted: Interrupted) -> Result<MessageDialogResult, Error> {
+    interrupted.into_result().map(Option::unwrap_or_default)
 }
 
 /// Asynchronous Message Dialog. Supported platforms:
@@ -90,7 +137,10 @@ impl MessageDialog {
 ///  * Linux
 ///  * WASM
 #[derive(Default, Debug, Clone)]
-pub struct AsyncMessageDialog(MessageDialog);
+pub struct AsyncMessageDialog {
+    message_dialog: MessageDialog,
+    handle: Option<DialogHandle>,
+}
 
 impl AsyncMessageDialog {
     pub fn new() -> Self {
@@ -102,13 +152,13 @@ impl AsyncMessageDialog {
     /// Depending on the system it can result in level specific icon to show up,
     /// the will inform user it message is a error, warning or just information.
     pub fn set_level(mut self, level: MessageLevel) -> Self {
-        self.0 = self.0.set_level(level);
+        self.message_dialog = self.message_dialog.set_level(level);
         self
     }
 
     /// Set title of a dialog
     pub fn set_title(mut self, text: impl Into<String>) -> Self {
-        self.0 = self.0.set_title(text);
+        self.message_dialog = self.message_dialog.set_title(text);
         self
     }
 
@@ -116,7 +166,7 @@ impl AsyncMessageDialog {
     ///
     /// Description is a content of a dialog
     pub fn set_description(mut self, text: impl Into<String>) -> Self {
-        self.0 = self.0.set_description(text);
+        self.message_dialog = self.message_dialog.set_description(text);
         self
     }
 
@@ -127,7 +177,7 @@ impl AsyncMessageDialog {
     /// - `YesNo` dialog, will display 2 buttons yes and no.
     /// - `YesNoCancel` dialog, will display 3 buttons: yes, no, and cancel.
     pub fn set_buttons(mut self, btn: MessageButtons) -> Self {
-        self.0 = self.0.set_buttons(btn);
+        self.message_dialog = self.message_dialog.set_buttons(btn);
         self
     }
 
@@ -135,18 +185,45 @@ impl AsyncMessageDialog {
     /// Supported platforms:
     ///  * Windows
     ///  * Mac
-    ///  * Linux (XDG only)
+    ///  * Linux (XDG, and zenity 3 on X11)
     pub fn set_parent<W: HasWindowHandle + HasDisplayHandle + ?Sized>(
         mut self,
         parent: &W,
     ) -> Self {
-        self.0 = self.0.set_parent(parent);
+        self.message_dialog = self.message_dialog.set_parent(parent);
+        self
+    }
+
+    /// Close the dialog if the user did not answer it within `timeout`, see `MessageDialog::set_timeout`
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.message_dialog = self.message_dialog.set_timeout(timeout);
+        self
+    }
+
+    /// Attach a handle that can close the dialog, see [`DialogHandle`]
+    ///
+    /// A dialog closed through the handle resolves to `MessageDialogResult::Cancel`.
+    pub fn set_handle(mut self, handle: &DialogHandle) -> Self {
+        self.handle = Some(handle.clone());
         self
     }
 
     /// Shows a message dialog and returns the button that was pressed.
     pub fn show(self) -> impl Future<Output = MessageDialogResult> {
-        AsyncMessageDialogImpl::show_async(self.0)
+        let future = self.try_show();
+        async move { log_error(future.await.map(Some)).unwrap_or_default() }
+    }
+
+    /// Shows a message dialog and returns the button that was pressed, or `Error::TimedOut` once
+    /// the timeout set with `set_timeout` elapsed.
+    pub fn try_show(self) -> impl Future<Output = Result<MessageDialogResult, Error>> {
+        let timeout = self.message_dialog.timeout;
+        let future = cancellable(
+            self.handle,
+            timeout,
+            AsyncMessageDialogImpl::show_async(self.message_dialog),
+        );
+        async move { future.await.or_else(interrupted) }
     }
 }
 
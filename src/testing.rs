@@ -0,0 +1,361 @@
+//! Mock dialog backend for the tests of applications, enabled by the `testing` feature.
+//!
+//! With the feature enabled no native dialog is ever shown, both [`crate::FileDialog`] and
+//! [`crate::AsyncFileDialog`] (and the message dialogs) return the answers queued on
+//! [`MockDialogs`] instead, and record what they were asked to show. Enable it only for the tests,
+//! eg. with `rfd = { version = "*", features = ["testing"] }` in `[dev-dependencies]`.
+//!
+//! Cargo features are unified, so a crate of the workspace that enables `testing` replaces the
+//! dialogs of every other one that depends on rfd, its release builds included. Dialogs requested
+//! while no [`MockDialogs`] is alive panic, so that doesn't go unnoticed.
+//!
+//! ```
+//! use rfd::testing::{Answer, MockDialogs, Request};
+//!
+//! let mock = MockDialogs::start();
+//! mock.answer(Answer::path("/tmp/a.txt"));
+//!
+//! let path = rfd::FileDialog::new()
+//!     .set_title("Open a text file")
+//!     .add_filter("Text", &["txt"])
+//!     .pick_file();
+//! assert_eq!(path, Some("/tmp/a.txt".into()));
+//!
+//! let Request::File(request) = &mock.requests()[0] else {
+//!     unreachable!()
+//! };
+//! assert_eq!(request.title.as_deref(), Some("Open a text file"));
+//! assert_eq!(request.filters[0].extensions(), ["txt"]);
+//! ```
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::message_dialog::MessageDialog;
+use crate::{Error, FileDialog, Filter, MessageButtons, MessageDialogResult, MessageLevel};
+
+/// Answer of the next dialog, queued with [`MockDialogs::answer`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    /// Path(s) picked by the user, `save_files` takes the folder to save the files into
+    Paths(Vec<PathBuf>),
+    /// Button of a message dialog clicked by the user
+    Message(MessageDialogResult),
+    /// The user closed the dialog, also the answer when none is queued
+    Cancel,
+    /// The dialog failed, returned by the `try_*` methods and logged by the others
+    Error(Error),
+}
+
+impl Answer {
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        Self::Paths(vec![path.into()])
+    }
+
+    pub fn paths<P: Into<PathBuf>>(paths: impl IntoIterator<Item = P>) -> Self {
+        Self::Paths(paths.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Method of [`crate::FileDialog`] (or [`crate::AsyncFileDialog`]) that opened the dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FileMethod {
+    PickFile,
+    PickFiles,
+    PickFolder,
+    PickFolders,
+    SaveFile,
+    SaveFiles,
+    PickFileOrFolder,
+    PickFilesOrFolders,
+}
+
+/// File dialog the application asked for
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FileRequest {
+    pub method: FileMethod,
+    pub title: Option<String>,
+    pub filters: Vec<Filter>,
+    /// `set_current_filter`
+    pub current_filter: Option<usize>,
+    /// `set_directory`
+    pub directory: Option<PathBuf>,
+    /// `set_file_name`
+    pub file_name: Option<String>,
+    /// `set_existing_file`
+    pub existing_file: Option<PathBuf>,
+    pub accept_label: Option<String>,
+    /// Names passed to `save_files`
+    pub file_names: Vec<String>,
+    /// Whether `set_parent` was called
+    pub has_parent: bool,
+}
+
+/// Message dialog the application asked for
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MessageRequest {
+    pub title: String,
+    pub description: String,
+    pub level: MessageLevel,
+    pub buttons: MessageButtons,
+    /// Whether `set_parent` was called
+    pub has_parent: bool,
+}
+
+/// Dialog the application asked for, returned by [`MockDialogs::requests`]
+#[derive(Debug, Clone)]
+pub enum Request {
+    File(FileRequest),
+    Message(MessageRequest),
+}
+
+#[derive(Default)]
+struct State {
+    answers: VecDeque<Answer>,
+    requests: Vec<Request>,
+    /// A [`MockDialogs`] is alive
+    started: bool,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    answers: VecDeque::new(),
+    requests: Vec::new(),
+    started: false,
+});
+
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// The mock dialogs, tests using it run one after another as the answers are shared by the
+/// whole process
+pub struct MockDialogs {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl MockDialogs {
+    /// Wait for the other tests using the mock to finish, and forget their answers and requests
+    pub fn start() -> Self {
+        let lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        *state() = State {
+            started: true,
+            ..State::default()
+        };
+
+        Self { _lock: lock }
+    }
+
+    /// Answer the next dialog with `answer`, dialogs are cancelled if there is none left
+    pub fn answer(&self, answer: Answer) {
+        state().answers.push_back(answer);
+    }
+
+    /// Dialogs requested so far, in order
+    pub fn requests(&self) -> Vec<Request> {
+        state().requests.clone()
+    }
+}
+
+impl Drop for MockDialogs {
+    fn drop(&mut self) {
+        state().started = false;
+    }
+}
+
+/// Record a file dialog, and take the answer to it
+pub(crate) fn file_dialog(
+    method: FileMethod,
+    dialog: &FileDialog,
+    file_names: Vec<String>,
+) -> Result<Option<Vec<PathBuf>>, Error> {
+    let request = Request::File(FileRequest {
+        method,
+        title: dialog.title.clone(),
+        filters: dialog.filters.clone(),
+        current_filter: dialog.current_filter,
+        directory: dialog.starting_directory.clone(),
+        file_name: dialog.file_name.clone(),
+        existing_file: dialog.existing_file.clone(),
+        accept_label: dialog.accept_label.clone(),
+        file_names,
+        has_parent: dialog.parent.is_some(),
+    });
+
+    match take_answer(request) {
+        Answer::Paths(paths) => Ok((!paths.is_empty()).then_some(paths)),
+        Answer::Cancel => Ok(None),
+        Answer::Error(err) => Err(err),
+        answer => panic!("{method:?} was answered with {answer:?}"),
+    }
+}
+
+/// Record a message dialog, and take the answer to it
+pub(crate) fn message_dialog(dialog: &MessageDialog) -> MessageDialogResult {
+    let request = Request::Message(MessageRequest {
+        title: dialog.title.clone(),
+        description: dialog.description.clone(),
+        level: dialog.level,
+        buttons: dialog.buttons.clone(),
+        has_parent: dialog.parent.is_some(),
+    });
+
+    match take_answer(request) {
+        Answer::Message(result) => result,
+        Answer::Cancel => MessageDialogResult::Cancel,
+        Answer::Error(err) => {
+            log::error!("Failed to show the message dialog: {err}");
+            MessageDialogResult::Cancel
+        }
+        answer => panic!("Message dialog was answered with {answer:?}"),
+    }
+}
+
+/// Panics without a [`MockDialogs`], the feature may have been enabled by accident, eg. by another
+/// crate of the workspace, and the application would silently get no dialogs
+fn take_answer(request: Request) -> Answer {
+    let mut state = state();
+    if !state.started {
+        drop(state);
+        panic!(
+            "rfd is built with the `testing` feature, which never shows dialogs, but no \
+             `rfd::testing::MockDialogs` was started to answer {request:?}"
+        );
+    }
+    state.requests.push(request);
+    state.answers.pop_front().unwrap_or(Answer::Cancel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Answer, FileMethod, MockDialogs, Request, LOCK};
+    use crate::{
+        AsyncFileDialog, AsyncMessageDialog, DialogHandle, Error, FileDialog, MessageButtons,
+        MessageDialog, MessageDialogResult,
+    };
+    use std::path::PathBuf;
+
+    fn file_request(request: &Request) -> &super::FileRequest {
+        match request {
+            Request::File(request) => request,
+            request => panic!("{request:?} is not a file dialog"),
+        }
+    }
+
+    #[test]
+    fn file_dialogs() {
+        let mock = MockDialogs::start();
+        mock.answer(Answer::paths(["/tmp/a.png", "/tmp/b.txt"]));
+        mock.answer(Answer::Cancel);
+        mock.answer(Answer::Error(Error::TimedOut));
+
+        let res = FileDialog::new()
+            .add_filter("Text", &["txt"])
+            .add_filter("Images", &["png"])
+            .set_directory("/tmp")
+            .pick_files_with_response()
+            .unwrap();
+        assert_eq!(
+            res.selection,
+            [PathBuf::from("/tmp/a.png"), "/tmp/b.txt".into()]
+        );
+        assert_eq!(res.filter, Some(1));
+
+        assert_eq!(FileDialog::new().pick_folder(), None);
+        assert_eq!(
+            FileDialog::new().set_file_name("a.txt").try_save_file(),
+            Err(Error::TimedOut)
+        );
+        // Nothing left to answer with
+        assert_eq!(FileDialog::new().try_pick_file(), Ok(None));
+
+        let requests = mock.requests();
+        let methods: Vec<_> = requests
+            .iter()
+            .map(|request| file_request(request).method)
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                FileMethod::PickFiles,
+                FileMethod::PickFolder,
+                FileMethod::SaveFile,
+                FileMethod::PickFile
+            ]
+        );
+        let pick_files = file_request(&requests[0]);
+        assert_eq!(pick_files.filters[1].name(), "Images");
+        assert_eq!(pick_files.directory, Some("/tmp".into()));
+        assert_eq!(
+            file_request(&requests[2]).file_name.as_deref(),
+            Some("a.txt")
+        );
+    }
+
+    #[test]
+    fn async_file_dialogs() {
+        let mock = MockDialogs::start();
+        mock.answer(Answer::path("/tmp/a.txt"));
+        mock.answer(Answer::path("/nonexistent/folder"));
+
+        let file =
+            futures::executor::block_on(AsyncFileDialog::new().set_title("Open").pick_file());
+        assert_eq!(file.unwrap().path(), PathBuf::from("/tmp/a.txt"));
+
+        let files =
+            futures::executor::block_on(AsyncFileDialog::new().save_files(&["a.txt", "b.txt"]));
+        let paths: Vec<_> = files.unwrap().iter().map(|f| f.path().to_owned()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("/nonexistent/folder/a.txt"),
+                "/nonexistent/folder/b.txt".into()
+            ]
+        );
+
+        let requests = mock.requests();
+        assert_eq!(file_request(&requests[0]).title.as_deref(), Some("Open"));
+        assert_eq!(file_request(&requests[1]).file_names, ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn message_dialogs() {
+        let mock = MockDialogs::start();
+        mock.answer(Answer::Message(MessageDialogResult::Yes));
+
+        let res = MessageDialog::new()
+            .set_title("Delete?")
+            .set_buttons(MessageButtons::YesNo)
+            .show();
+        assert_eq!(res, MessageDialogResult::Yes);
+        let res = futures::executor::block_on(AsyncMessageDialog::new().show());
+        assert_eq!(res, MessageDialogResult::Cancel);
+
+        // A closed handle cancels the dialog, whatever the answer
+        let handle = DialogHandle::new();
+        handle.close();
+        mock.answer(Answer::Message(MessageDialogResult::Ok));
+        let res = futures::executor::block_on(AsyncMessageDialog::new().set_handle(&handle).show());
+        assert_eq!(res, MessageDialogResult::Cancel);
+
+        let Request::Message(request) = &mock.requests()[0] else {
+            panic!("not a message dialog");
+        };
+        assert_eq!(request.title, "Delete?");
+        assert!(matches!(request.buttons, MessageButtons::YesNo));
+    }
+
+    #[test]
+    fn dialog_without_mock() {
+        let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        let res = std::panic::catch_unwind(|| FileDialog::new().pick_file());
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! Runtime independent timer used for dialog timeouts.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use native::Sleep;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasm::Sleep;
+
+/// Resolves once `duration` elapsed
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    Sleep::new(duration)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, OnceLock},
+        task::Waker,
+        thread::Thread,
+        time::Instant,
+    };
+
+    struct Timer {
+        deadline: Instant,
+        waker: Waker,
+    }
+
+    #[derive(Default)]
+    struct State {
+        next_id: u64,
+        timers: HashMap<u64, Timer>,
+    }
+
+    /// A single thread, spawned on first use, wakes up every pending timer, so a dialog with a
+    /// timeout doesn't cost a thread of its own
+    struct Timers {
+        state: Arc<Mutex<State>>,
+        thread: Thread,
+    }
+
+    static TIMERS: OnceLock<Option<Timers>> = OnceLock::new();
+
+    impl Timers {
+        fn get() -> Option<&'static Timers> {
+            TIMERS.get_or_init(Self::spawn).as_ref()
+        }
+
+        fn spawn() -> Option<Self> {
+            let state = Arc::new(Mutex::default());
+            let res = std::thread::Builder::new().name("rfd-timer".into()).spawn({
+                let state = state.clone();
+                move || run(&state)
+            });
+
+            match res {
+                Ok(handle) => Some(Self {
+                    state,
+                    thread: handle.thread().clone(),
+                }),
+                Err(err) => {
+                    log::error!("Failed to spawn the timer thread: {err}");
+                    None
+                }
+            }
+        }
+    }
+
+    fn run(state: &Mutex<State>) -> ! {
+        let mut wakers = Vec::new();
+
+        loop {
+            let now = Instant::now();
+            let next = {
+                let mut state = state.lock().unwrap();
+                state.timers.retain(|_, timer| {
+                    let elapsed = timer.deadline <= now;
+                    if elapsed {
+                        wakers.push(timer.waker.clone());
+                    }
+                    !elapsed
+                });
+                state.timers.values().map(|timer| timer.deadline).min()
+            };
+
+            for waker in wakers.drain(..) {
+                waker.wake();
+            }
+
+            match next {
+                Some(deadline) => std::thread::park_timeout(deadline - now),
+                None => std::thread::park(),
+            }
+        }
+    }
+
+    /// Registered with the timer thread on first poll, and unregistered once dropped
+    pub struct Sleep {
+        deadline: Instant,
+        id: Option<u64>,
+    }
+
+    impl Sleep {
+        pub(super) fn new(duration: Duration) -> Self {
+            Self {
+                deadline: Instant::now() + duration,
+                id: None,
+            }
+        }
+    }
+
+    impl Future for Sleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if Instant::now() >= self.deadline {
+                return Poll::Ready(());
+            }
+
+            // Nothing could wake up the future, so the dialog times out at once instead of never
+            let Some(timers) = Timers::get() else {
+                return Poll::Ready(());
+            };
+
+            let registered = self.id.is_some();
+            {
+                let mut state = timers.state.lock().unwrap();
+                let id = *self.id.get_or_insert_with(|| {
+                    state.next_id += 1;
+                    state.next_id
+                });
+                let timer = Timer {
+                    deadline: self.deadline,
+                    waker: cx.waker().clone(),
+                };
+                state.timers.insert(id, timer);
+            }
+
+            // The thread may be parked until an earlier deadline, or for good
+            if !registered {
+                timers.thread.unpark();
+            }
+
+            Poll::Pending
+        }
+    }
+
+    impl Drop for Sleep {
+        fn drop(&mut self) {
+            if let (Some(id), Some(timers)) = (self.id, TIMERS.get().and_then(Option::as_ref)) {
+                timers.state.lock().unwrap().timers.remove(&id);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use super::*;
+    use wasm_bindgen_futures::JsFuture;
+
+    /// Promise resolved by `setTimeout`
+    pub struct Sleep(JsFuture);
+
+    impl Sleep {
+        pub(super) fn new(duration: Duration) -> Self {
+            let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+            let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+                web_sys::window()
+                    .expect("Window not found")
+                    .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis)
+                    .ok();
+            });
+            Self(JsFuture::from(promise))
+        }
+    }
+
+    impl Future for Sleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            Pin::new(&mut self.0).poll(cx).map(|_| ())
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn sleep() {
+        let start = Instant::now();
+        futures::executor::block_on(super::sleep(Duration::from_millis(50)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn sleeps() {
+        let start = Instant::now();
+        futures::executor::block_on(async {
+            futures::join!(
+                super::sleep(Duration::from_millis(60)),
+                super::sleep(Duration::from_millis(20)),
+                super::sleep(Duration::from_millis(40)),
+            )
+        });
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+}